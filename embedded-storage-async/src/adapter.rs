@@ -0,0 +1,975 @@
+use core::{
+	future::Future,
+	pin::pin,
+	task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use embedded_storage::{
+	block_device as blocking_block, eeprom as blocking_eeprom, nand_flash as blocking_nand,
+	nor_flash as blocking,
+};
+
+use crate::{
+	block_device::{self, BlockDevice, ReadBlockDevice},
+	eeprom::{self, Eeprom, ReadEeprom},
+	nand_flash::{self, EccStatus, NandFlash, ReadNandFlash},
+	nor_flash::{
+		ErrorType, MultiEraseNorFlash, MultiwriteNorFlash, NorFlash, ReadNorFlash, SectorNorFlash,
+		SectorRun,
+	},
+	ReadStorage, Storage,
+};
+
+/// Wrapper implementing the async traits on top of the blocking `embedded-storage` traits.
+///
+/// Every operation runs to completion before the returned future is first polled, so the
+/// executor is blocked for as long as the operation takes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockingAsync<T> {
+	wrapped: T,
+}
+
+impl<T> BlockingAsync<T> {
+	/// Wrap the blocking `wrapped`.
+	pub fn new(wrapped: T) -> Self {
+		Self { wrapped }
+	}
+
+	/// Release the wrapped storage.
+	pub fn into_inner(self) -> T {
+		self.wrapped
+	}
+}
+
+impl<T: ErrorType> ErrorType for BlockingAsync<T> {
+	type Error = T::Error;
+}
+
+impl<T: blocking::ReadNorFlash> ReadNorFlash for BlockingAsync<T> {
+	const READ_SIZE: usize = T::READ_SIZE;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.wrapped.read(offset, bytes)
+	}
+
+	fn capacity(&self) -> usize {
+		self.wrapped.capacity()
+	}
+}
+
+impl<T: blocking::NorFlash> NorFlash for BlockingAsync<T> {
+	const WRITE_SIZE: usize = T::WRITE_SIZE;
+	const ERASE_SIZE: usize = T::ERASE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = T::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = T::ERASE_VALUE;
+
+	async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.wrapped.erase(from, to)
+	}
+
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.wrapped.write(offset, bytes)
+	}
+}
+
+impl<T: blocking::MultiwriteNorFlash> MultiwriteNorFlash for BlockingAsync<T> {}
+
+impl<T: blocking::MultiEraseNorFlash> MultiEraseNorFlash for BlockingAsync<T> {
+	const ERASE_SIZES: &'static [usize] = T::ERASE_SIZES;
+	const CHIP_ERASE: bool = T::CHIP_ERASE;
+
+	async fn erase_block(&mut self, offset: u32, size: usize) -> Result<(), Self::Error> {
+		self.wrapped.erase_block(offset, size)
+	}
+}
+
+impl<T: blocking::SectorNorFlash> SectorNorFlash for BlockingAsync<T> {
+	const WRITE_SIZE: usize = T::WRITE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = T::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = T::ERASE_VALUE;
+
+	fn sector_map(&self) -> &'static [SectorRun] {
+		self.wrapped.sector_map()
+	}
+
+	async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.wrapped.erase(from, to)
+	}
+
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.wrapped.write(offset, bytes)
+	}
+}
+
+impl<T: nand_flash::ErrorType> nand_flash::ErrorType for BlockingAsync<T> {
+	type Error = T::Error;
+}
+
+impl<T: blocking_nand::ReadNandFlash> ReadNandFlash for BlockingAsync<T> {
+	const PAGE_SIZE: usize = T::PAGE_SIZE;
+	const SPARE_SIZE: usize = T::SPARE_SIZE;
+	const BLOCK_SIZE: usize = T::BLOCK_SIZE;
+
+	async fn read_page(
+		&mut self,
+		page: u32,
+		data: &mut [u8],
+		spare: &mut [u8],
+	) -> Result<EccStatus, Self::Error> {
+		self.wrapped.read_page(page, data, spare)
+	}
+
+	async fn is_bad_block(&mut self, block: u32) -> Result<bool, Self::Error> {
+		self.wrapped.is_bad_block(block)
+	}
+
+	fn block_count(&self) -> usize {
+		self.wrapped.block_count()
+	}
+}
+
+impl<T: blocking_nand::NandFlash> NandFlash for BlockingAsync<T> {
+	async fn erase_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		self.wrapped.erase_block(block)
+	}
+
+	async fn program_page(
+		&mut self,
+		page: u32,
+		data: &[u8],
+		spare: &[u8],
+	) -> Result<(), Self::Error> {
+		self.wrapped.program_page(page, data, spare)
+	}
+
+	async fn mark_bad_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		self.wrapped.mark_bad_block(block)
+	}
+}
+
+impl<T: block_device::ErrorType> block_device::ErrorType for BlockingAsync<T> {
+	type Error = T::Error;
+}
+
+impl<T: blocking_block::ReadBlockDevice> ReadBlockDevice for BlockingAsync<T> {
+	const BLOCK_SIZE: usize = T::BLOCK_SIZE;
+
+	async fn read_blocks(&mut self, block: u32, data: &mut [u8]) -> Result<(), Self::Error> {
+		self.wrapped.read_blocks(block, data)
+	}
+
+	fn block_count(&self) -> usize {
+		self.wrapped.block_count()
+	}
+}
+
+impl<T: blocking_block::BlockDevice> BlockDevice for BlockingAsync<T> {
+	async fn write_blocks(&mut self, block: u32, data: &[u8]) -> Result<(), Self::Error> {
+		self.wrapped.write_blocks(block, data)
+	}
+
+	async fn trim(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.wrapped.trim(from, to)
+	}
+}
+
+impl<T: eeprom::ErrorType> eeprom::ErrorType for BlockingAsync<T> {
+	type Error = T::Error;
+}
+
+impl<T: blocking_eeprom::ReadEeprom> ReadEeprom for BlockingAsync<T> {
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.wrapped.read(offset, bytes)
+	}
+
+	fn capacity(&self) -> usize {
+		self.wrapped.capacity()
+	}
+}
+
+impl<T: blocking_eeprom::Eeprom> Eeprom for BlockingAsync<T> {
+	const PAGE_SIZE: usize = T::PAGE_SIZE;
+
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.wrapped.write(offset, bytes)
+	}
+}
+
+impl<T: embedded_storage::ReadStorage> ReadStorage for BlockingAsync<T> {
+	type Error = T::Error;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.wrapped.read(offset, bytes)
+	}
+
+	fn capacity(&self) -> usize {
+		self.wrapped.capacity()
+	}
+}
+
+impl<T: embedded_storage::Storage> Storage for BlockingAsync<T> {
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.wrapped.write(offset, bytes)
+	}
+}
+
+/// Executor running a future to completion, blocking until it does.
+pub trait BlockOn {
+	/// Run `future` to completion and return its output.
+	fn block_on<F: Future>(&mut self, future: F) -> F::Output;
+}
+
+/// Minimal executor, polling the future in a busy loop until it completes.
+///
+/// This never sleeps, so it is best suited for futures which complete after a few polls. Other
+/// executors can be plugged in through [`BlockOn`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpinBlockOn;
+
+impl BlockOn for SpinBlockOn {
+	fn block_on<F: Future>(&mut self, future: F) -> F::Output {
+		let mut future = pin!(future);
+		let waker = noop_waker();
+		let mut cx = Context::from_waker(&waker);
+		loop {
+			if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+				return output;
+			}
+		}
+	}
+}
+
+fn noop_waker() -> Waker {
+	fn clone(_: *const ()) -> RawWaker {
+		RAW_WAKER
+	}
+	fn noop(_: *const ()) {}
+
+	const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+	const RAW_WAKER: RawWaker = RawWaker::new(core::ptr::null(), &VTABLE);
+
+	// SAFETY: The waker never dereferences its data pointer, and all of its functions are no-ops,
+	// which trivially upholds the `RawWaker` contract.
+	#[allow(unsafe_code)]
+	unsafe {
+		Waker::from_raw(RAW_WAKER)
+	}
+}
+
+/// Wrapper implementing the blocking `embedded-storage` traits on top of the async traits.
+///
+/// Every operation is driven to completion by the executor `B`, which defaults to the bundled
+/// [`SpinBlockOn`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockOnAsync<T, B = SpinBlockOn> {
+	wrapped: T,
+	executor: B,
+}
+
+impl<T> BlockOnAsync<T> {
+	/// Wrap the async `wrapped`, using the bundled [`SpinBlockOn`] executor.
+	pub fn new(wrapped: T) -> Self {
+		Self::with_executor(wrapped, SpinBlockOn)
+	}
+}
+
+impl<T, B> BlockOnAsync<T, B> {
+	/// Wrap the async `wrapped`, using `executor` to run its operations.
+	pub fn with_executor(wrapped: T, executor: B) -> Self {
+		Self { wrapped, executor }
+	}
+
+	/// Release the wrapped storage.
+	pub fn into_inner(self) -> T {
+		self.wrapped
+	}
+}
+
+impl<T: ErrorType, B> ErrorType for BlockOnAsync<T, B> {
+	type Error = T::Error;
+}
+
+impl<T: ReadNorFlash, B: BlockOn> blocking::ReadNorFlash for BlockOnAsync<T, B> {
+	const READ_SIZE: usize = T::READ_SIZE;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.read(offset, bytes))
+	}
+
+	fn capacity(&self) -> usize {
+		self.wrapped.capacity()
+	}
+}
+
+impl<T: NorFlash, B: BlockOn> blocking::NorFlash for BlockOnAsync<T, B> {
+	const WRITE_SIZE: usize = T::WRITE_SIZE;
+	const ERASE_SIZE: usize = T::ERASE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = T::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = T::ERASE_VALUE;
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.erase(from, to))
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.write(offset, bytes))
+	}
+}
+
+impl<T: MultiwriteNorFlash, B: BlockOn> blocking::MultiwriteNorFlash for BlockOnAsync<T, B> {}
+
+impl<T: MultiEraseNorFlash, B: BlockOn> blocking::MultiEraseNorFlash for BlockOnAsync<T, B> {
+	const ERASE_SIZES: &'static [usize] = T::ERASE_SIZES;
+	const CHIP_ERASE: bool = T::CHIP_ERASE;
+
+	fn erase_block(&mut self, offset: u32, size: usize) -> Result<(), Self::Error> {
+		self.executor
+			.block_on(self.wrapped.erase_block(offset, size))
+	}
+}
+
+impl<T: SectorNorFlash, B: BlockOn> blocking::SectorNorFlash for BlockOnAsync<T, B> {
+	const WRITE_SIZE: usize = T::WRITE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = T::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = T::ERASE_VALUE;
+
+	fn sector_map(&self) -> &'static [SectorRun] {
+		self.wrapped.sector_map()
+	}
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.erase(from, to))
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.write(offset, bytes))
+	}
+}
+
+impl<T: nand_flash::ErrorType, B> nand_flash::ErrorType for BlockOnAsync<T, B> {
+	type Error = T::Error;
+}
+
+impl<T: ReadNandFlash, B: BlockOn> blocking_nand::ReadNandFlash for BlockOnAsync<T, B> {
+	const PAGE_SIZE: usize = T::PAGE_SIZE;
+	const SPARE_SIZE: usize = T::SPARE_SIZE;
+	const BLOCK_SIZE: usize = T::BLOCK_SIZE;
+
+	fn read_page(
+		&mut self,
+		page: u32,
+		data: &mut [u8],
+		spare: &mut [u8],
+	) -> Result<EccStatus, Self::Error> {
+		self.executor
+			.block_on(self.wrapped.read_page(page, data, spare))
+	}
+
+	fn is_bad_block(&mut self, block: u32) -> Result<bool, Self::Error> {
+		self.executor.block_on(self.wrapped.is_bad_block(block))
+	}
+
+	fn block_count(&self) -> usize {
+		self.wrapped.block_count()
+	}
+}
+
+impl<T: NandFlash, B: BlockOn> blocking_nand::NandFlash for BlockOnAsync<T, B> {
+	fn erase_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.erase_block(block))
+	}
+
+	fn program_page(&mut self, page: u32, data: &[u8], spare: &[u8]) -> Result<(), Self::Error> {
+		self.executor
+			.block_on(self.wrapped.program_page(page, data, spare))
+	}
+
+	fn mark_bad_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.mark_bad_block(block))
+	}
+}
+
+impl<T: block_device::ErrorType, B> block_device::ErrorType for BlockOnAsync<T, B> {
+	type Error = T::Error;
+}
+
+impl<T: ReadBlockDevice, B: BlockOn> blocking_block::ReadBlockDevice for BlockOnAsync<T, B> {
+	const BLOCK_SIZE: usize = T::BLOCK_SIZE;
+
+	fn read_blocks(&mut self, block: u32, data: &mut [u8]) -> Result<(), Self::Error> {
+		self.executor
+			.block_on(self.wrapped.read_blocks(block, data))
+	}
+
+	fn block_count(&self) -> usize {
+		self.wrapped.block_count()
+	}
+}
+
+impl<T: BlockDevice, B: BlockOn> blocking_block::BlockDevice for BlockOnAsync<T, B> {
+	fn write_blocks(&mut self, block: u32, data: &[u8]) -> Result<(), Self::Error> {
+		self.executor
+			.block_on(self.wrapped.write_blocks(block, data))
+	}
+
+	fn trim(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.trim(from, to))
+	}
+}
+
+impl<T: eeprom::ErrorType, B> eeprom::ErrorType for BlockOnAsync<T, B> {
+	type Error = T::Error;
+}
+
+impl<T: ReadEeprom, B: BlockOn> blocking_eeprom::ReadEeprom for BlockOnAsync<T, B> {
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.read(offset, bytes))
+	}
+
+	fn capacity(&self) -> usize {
+		self.wrapped.capacity()
+	}
+}
+
+impl<T: Eeprom, B: BlockOn> blocking_eeprom::Eeprom for BlockOnAsync<T, B> {
+	const PAGE_SIZE: usize = T::PAGE_SIZE;
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.write(offset, bytes))
+	}
+}
+
+impl<T: ReadStorage, B: BlockOn> embedded_storage::ReadStorage for BlockOnAsync<T, B> {
+	type Error = T::Error;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.read(offset, bytes))
+	}
+
+	fn capacity(&self) -> usize {
+		self.wrapped.capacity()
+	}
+}
+
+impl<T: Storage, B: BlockOn> embedded_storage::Storage for BlockOnAsync<T, B> {
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.executor.block_on(self.wrapped.write(offset, bytes))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use core::future::poll_fn;
+
+	use embedded_storage::{
+		nand_flash::{MockNandFlash, MockNandFlashError},
+		nor_flash::{MockFlash, MockFlashError, MultiwriteMockFlash},
+		StorageErrorKind,
+	};
+
+	use super::*;
+
+	type Mock = MockFlash<32, 1, 4, 8>;
+
+	/// Async flash whose operations are pending `YIELDS` times before completing.
+	struct YieldingFlash<const YIELDS: usize> {
+		flash: Mock,
+		polls: usize,
+	}
+
+	impl<const YIELDS: usize> YieldingFlash<YIELDS> {
+		fn new() -> Self {
+			Self {
+				flash: Mock::new(),
+				polls: 0,
+			}
+		}
+
+		async fn wait(&mut self) {
+			let mut left = YIELDS;
+			let polls = &mut self.polls;
+			poll_fn(|cx| {
+				*polls += 1;
+				if left == 0 {
+					return Poll::Ready(());
+				}
+				left -= 1;
+				cx.waker().wake_by_ref();
+				Poll::Pending
+			})
+			.await
+		}
+	}
+
+	impl<const YIELDS: usize> ErrorType for YieldingFlash<YIELDS> {
+		type Error = MockFlashError;
+	}
+
+	impl<const YIELDS: usize> ReadNorFlash for YieldingFlash<YIELDS> {
+		const READ_SIZE: usize = 1;
+
+		async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			self.wait().await;
+			blocking::ReadNorFlash::read(&mut self.flash, offset, bytes)
+		}
+
+		fn capacity(&self) -> usize {
+			blocking::ReadNorFlash::capacity(&self.flash)
+		}
+	}
+
+	impl<const YIELDS: usize> NorFlash for YieldingFlash<YIELDS> {
+		const WRITE_SIZE: usize = 4;
+		const ERASE_SIZE: usize = 8;
+
+		async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+			self.wait().await;
+			blocking::NorFlash::erase(&mut self.flash, from, to)
+		}
+
+		async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+			self.wait().await;
+			blocking::NorFlash::write(&mut self.flash, offset, bytes)
+		}
+	}
+
+	/// Executor counting the futures it runs, on top of [`SpinBlockOn`].
+	#[derive(Default)]
+	struct CountingBlockOn(usize);
+
+	impl BlockOn for CountingBlockOn {
+		fn block_on<F: Future>(&mut self, future: F) -> F::Output {
+			self.0 += 1;
+			SpinBlockOn.block_on(future)
+		}
+	}
+
+	#[test]
+	fn spin_block_on_drives_pending_operations() {
+		use blocking::{NorFlash, ReadNorFlash};
+
+		let mut flash = BlockOnAsync::new(YieldingFlash::<3>::new());
+		flash.write(4, &[1, 2, 3, 4]).unwrap();
+		let mut data = [0; 8];
+		flash.read(0, &mut data).unwrap();
+		assert_eq!(data, [0xff, 0xff, 0xff, 0xff, 1, 2, 3, 4]);
+		flash.erase(0, 8).unwrap();
+		flash.read(0, &mut data).unwrap();
+		assert_eq!(data, [0xff; 8]);
+
+		// Every operation was polled until it stopped yielding
+		assert_eq!(flash.into_inner().polls, 4 * 4);
+	}
+
+	#[test]
+	fn block_on_async_forwards_errors_and_geometry() {
+		use blocking::{NorFlash, ReadNorFlash};
+
+		type Flash = BlockOnAsync<YieldingFlash<1>>;
+		assert_eq!(<Flash as ReadNorFlash>::READ_SIZE, 1);
+		assert_eq!(<Flash as NorFlash>::WRITE_SIZE, 4);
+		assert_eq!(<Flash as NorFlash>::ERASE_SIZE, 8);
+
+		let mut flash = Flash::new(YieldingFlash::new());
+		assert_eq!(flash.capacity(), 32);
+		flash.write(0, &[0; 4]).unwrap();
+		assert_eq!(flash.write(0, &[0; 4]), Err(MockFlashError::NotErased));
+		assert_eq!(flash.write(2, &[0; 4]), Err(MockFlashError::NotAligned));
+		assert_eq!(
+			flash.read(32, &mut [0; 1]),
+			Err(MockFlashError::OutOfBounds)
+		);
+	}
+
+	#[test]
+	fn block_on_async_uses_the_given_executor() {
+		use blocking::NorFlash;
+
+		let mut flash =
+			BlockOnAsync::with_executor(YieldingFlash::<2>::new(), CountingBlockOn::default());
+		flash.write(0, &[0; 4]).unwrap();
+		flash.erase(0, 8).unwrap();
+		assert_eq!(flash.executor.0, 2);
+	}
+
+	#[test]
+	fn block_on_async_runs_blocking_storages() {
+		use embedded_storage::{nor_flash::RmwNorFlashStorage, ReadStorage, Storage};
+
+		let mut buffer = [0; 8];
+		let mut storage =
+			RmwNorFlashStorage::new(BlockOnAsync::new(YieldingFlash::<2>::new()), &mut buffer);
+		storage.write(3, &[0x5a; 7]).unwrap();
+		storage.write(5, &[0xa5; 2]).unwrap();
+
+		let mut data = [0; 12];
+		storage.read(0, &mut data).unwrap();
+		assert_eq!(
+			data,
+			[0xff, 0xff, 0xff, 0x5a, 0x5a, 0xa5, 0xa5, 0x5a, 0x5a, 0x5a, 0xff, 0xff]
+		);
+	}
+
+	static SECTOR_MAP: [SectorRun; 2] = [SectorRun::new(2, 8), SectorRun::new(1, 16)];
+
+	/// Sectors of 8, 8 and 16 bytes on top of a [`MockFlash`].
+	struct SectorMock(Mock);
+
+	impl ErrorType for SectorMock {
+		type Error = MockFlashError;
+	}
+
+	impl blocking::ReadNorFlash for SectorMock {
+		const READ_SIZE: usize = 1;
+
+		fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			blocking::ReadNorFlash::read(&mut self.0, offset, bytes)
+		}
+
+		fn capacity(&self) -> usize {
+			blocking::ReadNorFlash::capacity(&self.0)
+		}
+	}
+
+	impl blocking::SectorNorFlash for SectorMock {
+		const WRITE_SIZE: usize = 4;
+		const PROGRAM_PAGE_SIZE: usize = 16;
+		const ERASE_VALUE: u8 = 0xff;
+
+		fn sector_map(&self) -> &'static [SectorRun] {
+			&SECTOR_MAP
+		}
+
+		fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+			blocking::check_sector_erase(self, from, to).map_err(|_| MockFlashError::NotAligned)?;
+			blocking::NorFlash::erase(&mut self.0, from, to)
+		}
+
+		fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+			blocking::NorFlash::write(&mut self.0, offset, bytes)
+		}
+	}
+
+	/// Flash erasing blocks of 8 or 16 bytes, recording the size of the last erased block.
+	struct MultiEraseMock(Mock, usize);
+
+	impl ErrorType for MultiEraseMock {
+		type Error = MockFlashError;
+	}
+
+	impl blocking::ReadNorFlash for MultiEraseMock {
+		const READ_SIZE: usize = 1;
+
+		fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			blocking::ReadNorFlash::read(&mut self.0, offset, bytes)
+		}
+
+		fn capacity(&self) -> usize {
+			blocking::ReadNorFlash::capacity(&self.0)
+		}
+	}
+
+	impl blocking::NorFlash for MultiEraseMock {
+		const WRITE_SIZE: usize = 4;
+		const ERASE_SIZE: usize = 8;
+
+		fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+			blocking::NorFlash::erase(&mut self.0, from, to)
+		}
+
+		fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+			blocking::NorFlash::write(&mut self.0, offset, bytes)
+		}
+	}
+
+	impl blocking::MultiEraseNorFlash for MultiEraseMock {
+		const ERASE_SIZES: &'static [usize] = &[8, 16];
+		const CHIP_ERASE: bool = true;
+
+		fn erase_block(&mut self, offset: u32, size: usize) -> Result<(), Self::Error> {
+			self.1 = size;
+			blocking::NorFlash::erase(&mut self.0, offset, offset + size as u32)
+		}
+	}
+
+	/// EEPROM of 16 bytes in pages of 4 bytes.
+	struct RamEeprom([u8; 16]);
+
+	impl blocking_eeprom::ErrorType for RamEeprom {
+		type Error = StorageErrorKind;
+	}
+
+	impl blocking_eeprom::ReadEeprom for RamEeprom {
+		fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			blocking_eeprom::check_read(self, offset, bytes.len())?;
+			let offset = offset as usize;
+			bytes.copy_from_slice(&self.0[offset..offset + bytes.len()]);
+			Ok(())
+		}
+
+		fn capacity(&self) -> usize {
+			self.0.len()
+		}
+	}
+
+	impl blocking_eeprom::Eeprom for RamEeprom {
+		const PAGE_SIZE: usize = 4;
+
+		fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+			blocking_eeprom::check_write(self, offset, bytes.len())?;
+			let offset = offset as usize;
+			self.0[offset..offset + bytes.len()].copy_from_slice(bytes);
+			Ok(())
+		}
+	}
+
+	/// Block device of 4 blocks of 4 bytes, recording the last trimmed range.
+	struct RamBlockDevice([u8; 16], (u32, u32));
+
+	impl blocking_block::ErrorType for RamBlockDevice {
+		type Error = StorageErrorKind;
+	}
+
+	impl blocking_block::ReadBlockDevice for RamBlockDevice {
+		const BLOCK_SIZE: usize = 4;
+
+		fn read_blocks(&mut self, block: u32, data: &mut [u8]) -> Result<(), Self::Error> {
+			blocking_block::check_read(self, block, data.len())?;
+			let offset = block as usize * 4;
+			data.copy_from_slice(&self.0[offset..offset + data.len()]);
+			Ok(())
+		}
+
+		fn block_count(&self) -> usize {
+			4
+		}
+	}
+
+	impl blocking_block::BlockDevice for RamBlockDevice {
+		fn write_blocks(&mut self, block: u32, data: &[u8]) -> Result<(), Self::Error> {
+			blocking_block::check_write(self, block, data.len())?;
+			let offset = block as usize * 4;
+			self.0[offset..offset + data.len()].copy_from_slice(data);
+			Ok(())
+		}
+
+		fn trim(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+			blocking_block::check_trim(self, from, to)?;
+			self.1 = (from, to);
+			Ok(())
+		}
+	}
+
+	fn run<F: Future>(future: F) -> F::Output {
+		SpinBlockOn.block_on(future)
+	}
+
+	#[test]
+	fn blocking_async_forwards_nor_flash() {
+		type Flash = BlockingAsync<Mock>;
+		assert_eq!(Flash::READ_SIZE, 1);
+		assert_eq!(Flash::WRITE_SIZE, 4);
+		assert_eq!(Flash::ERASE_SIZE, 8);
+		assert_eq!(Flash::PROGRAM_PAGE_SIZE, 8);
+		assert_eq!(Flash::ERASE_VALUE, 0xff);
+
+		let mut flash = Flash::new(Mock::new());
+		assert_eq!(flash.capacity(), 32);
+		run(flash.write(8, &[1, 2, 3, 4])).unwrap();
+		let mut data = [0; 4];
+		run(flash.read(8, &mut data)).unwrap();
+		assert_eq!(data, [1, 2, 3, 4]);
+
+		assert_eq!(run(flash.write(8, &[0; 4])), Err(MockFlashError::NotErased));
+		assert_eq!(
+			run(flash.write(6, &[0; 4])),
+			Err(MockFlashError::NotAligned)
+		);
+		assert_eq!(run(flash.erase(4, 8)), Err(MockFlashError::NotAligned));
+		assert_eq!(
+			run(flash.read(30, &mut data)),
+			Err(MockFlashError::OutOfBounds)
+		);
+
+		run(flash.erase(8, 16)).unwrap();
+		assert_eq!(flash.into_inner().as_bytes(), &[0xff; 32]);
+	}
+
+	#[test]
+	fn blocking_async_forwards_multiwrite_nor_flash() {
+		async fn write_twice<F: MultiwriteNorFlash>(flash: &mut F) -> Result<(), F::Error> {
+			flash.write(0, &[0b1100; 4]).await?;
+			flash.write(0, &[0b1010; 4]).await
+		}
+
+		let mut flash = BlockingAsync::new(MultiwriteMockFlash::<32, 1, 4, 8>::new());
+		run(write_twice(&mut flash)).unwrap();
+		let mut data = [0; 4];
+		run(flash.read(0, &mut data)).unwrap();
+		assert_eq!(data, [0b1000; 4]);
+		assert_eq!(
+			run(flash.write(32, &[0; 4])),
+			Err(MockFlashError::OutOfBounds)
+		);
+	}
+
+	#[test]
+	fn blocking_async_forwards_multi_erase_nor_flash() {
+		type Flash = BlockingAsync<MultiEraseMock>;
+		assert_eq!(
+			(Flash::ERASE_SIZES, Flash::CHIP_ERASE),
+			(&[8, 16][..], true)
+		);
+
+		let mut flash = Flash::new(MultiEraseMock(Mock::new(), 0));
+		run(NorFlash::write(&mut flash, 16, &[0; 4])).unwrap();
+		run(flash.erase_block(16, 16)).unwrap();
+		let mut data = [0; 4];
+		run(ReadNorFlash::read(&mut flash, 16, &mut data)).unwrap();
+		assert_eq!(data, [0xff; 4]);
+		assert_eq!(flash.into_inner().1, 16);
+
+		let mut flash = Flash::new(MultiEraseMock(Mock::new(), 0));
+		assert_eq!(
+			run(flash.erase_block(24, 16)),
+			Err(MockFlashError::OutOfBounds)
+		);
+	}
+
+	#[test]
+	fn blocking_async_forwards_sector_nor_flash() {
+		type Flash = BlockingAsync<SectorMock>;
+		assert_eq!(<Flash as SectorNorFlash>::WRITE_SIZE, 4);
+		assert_eq!(<Flash as SectorNorFlash>::PROGRAM_PAGE_SIZE, 16);
+		assert_eq!(<Flash as SectorNorFlash>::ERASE_VALUE, 0xff);
+
+		let mut flash = Flash::new(SectorMock(Mock::new()));
+		assert_eq!(flash.sector_map(), &SECTOR_MAP);
+		run(SectorNorFlash::write(&mut flash, 20, &[1, 2, 3, 4])).unwrap();
+		let mut data = [0; 4];
+		run(ReadNorFlash::read(&mut flash, 20, &mut data)).unwrap();
+		assert_eq!(data, [1, 2, 3, 4]);
+
+		assert_eq!(
+			run(SectorNorFlash::erase(&mut flash, 16, 24)),
+			Err(MockFlashError::NotAligned)
+		);
+		run(SectorNorFlash::erase(&mut flash, 16, 32)).unwrap();
+		run(ReadNorFlash::read(&mut flash, 20, &mut data)).unwrap();
+		assert_eq!(data, [0xff; 4]);
+	}
+
+	#[test]
+	fn blocking_async_forwards_nand_flash() {
+		type Flash = BlockingAsync<MockNandFlash<4, 2, 2, 4>>;
+		assert_eq!(Flash::PAGE_SIZE, 4);
+		assert_eq!(Flash::SPARE_SIZE, 2);
+		assert_eq!(<Flash as ReadNandFlash>::BLOCK_SIZE, 8);
+
+		let mut nand = MockNandFlash::new();
+		nand.set_factory_bad_block(3);
+		nand.fail_erase(2);
+		nand.inject_bit_errors(1, 2);
+		let mut flash = Flash::new(nand);
+		assert_eq!(ReadNandFlash::block_count(&flash), 4);
+
+		run(flash.program_page(1, &[1, 2, 3, 4], &[5, 6])).unwrap();
+		let (mut data, mut spare) = ([0; 4], [0; 2]);
+		assert_eq!(
+			run(flash.read_page(1, &mut data, &mut spare)),
+			Ok(EccStatus::Corrected(2))
+		);
+		assert_eq!((data, spare), ([1, 2, 3, 4], [5, 6]));
+		assert_eq!(
+			run(flash.program_page(0, &[0; 4], &[0; 2])),
+			Err(MockNandFlashError::OutOfOrder)
+		);
+
+		run(nand_flash::NandFlash::erase_block(&mut flash, 0)).unwrap();
+		assert_eq!(
+			run(flash.read_page(1, &mut data, &mut spare)),
+			Ok(EccStatus::Clean)
+		);
+		assert_eq!(data, [0xff; 4]);
+		assert_eq!(
+			run(nand_flash::NandFlash::erase_block(&mut flash, 2)),
+			Err(MockNandFlashError::EraseFailed)
+		);
+
+		assert_eq!(run(flash.is_bad_block(1)), Ok(false));
+		assert_eq!(run(flash.is_bad_block(3)), Ok(true));
+		run(flash.mark_bad_block(1)).unwrap();
+		assert_eq!(run(flash.is_bad_block(1)), Ok(true));
+		assert_eq!(
+			run(flash.is_bad_block(4)),
+			Err(MockNandFlashError::OutOfBounds)
+		);
+	}
+
+	#[test]
+	fn blocking_async_forwards_eeprom() {
+		assert_eq!(<BlockingAsync<RamEeprom> as Eeprom>::PAGE_SIZE, 4);
+
+		let mut eeprom = BlockingAsync::new(RamEeprom([0; 16]));
+		assert_eq!(ReadEeprom::capacity(&eeprom), 16);
+		run(Eeprom::write(&mut eeprom, 3, &[1, 2, 3])).unwrap();
+		let mut data = [0xff; 5];
+		run(ReadEeprom::read(&mut eeprom, 2, &mut data)).unwrap();
+		assert_eq!(data, [0, 1, 2, 3, 0]);
+
+		assert_eq!(
+			run(Eeprom::write(&mut eeprom, 14, &[0; 3])),
+			Err(StorageErrorKind::OutOfBounds)
+		);
+		assert_eq!(
+			run(ReadEeprom::read(&mut eeprom, 17, &mut [])),
+			Err(StorageErrorKind::OutOfBounds)
+		);
+	}
+
+	#[test]
+	fn blocking_async_forwards_block_device() {
+		assert_eq!(
+			<BlockingAsync<RamBlockDevice> as ReadBlockDevice>::BLOCK_SIZE,
+			4
+		);
+
+		let mut device = BlockingAsync::new(RamBlockDevice([0; 16], (0, 0)));
+		assert_eq!(ReadBlockDevice::block_count(&device), 4);
+		assert_eq!(ReadBlockDevice::capacity(&device), 16);
+		run(device.write_blocks(1, &[1, 2, 3, 4, 5, 6, 7, 8])).unwrap();
+		let mut data = [0; 4];
+		run(device.read_blocks(2, &mut data)).unwrap();
+		assert_eq!(data, [5, 6, 7, 8]);
+
+		assert_eq!(
+			run(device.write_blocks(0, &[0; 3])),
+			Err(StorageErrorKind::NotAligned)
+		);
+		assert_eq!(
+			run(device.read_blocks(3, &mut [0; 8])),
+			Err(StorageErrorKind::OutOfBounds)
+		);
+		assert_eq!(run(device.trim(3, 2)), Err(StorageErrorKind::OutOfBounds));
+		run(device.trim(1, 3)).unwrap();
+		assert_eq!(device.into_inner().1, (1, 3));
+	}
+
+	#[test]
+	fn blocking_async_forwards_storage() {
+		use embedded_storage::nor_flash::RmwNorFlashStorage;
+
+		let mut buffer = [0; 8];
+		let mut storage = BlockingAsync::new(RmwNorFlashStorage::new(Mock::new(), &mut buffer));
+		assert_eq!(ReadStorage::capacity(&storage), 32);
+		run(Storage::write(&mut storage, 6, &[0x5a; 4])).unwrap();
+		let mut data = [0; 6];
+		run(ReadStorage::read(&mut storage, 5, &mut data)).unwrap();
+		assert_eq!(data, [0xff, 0x5a, 0x5a, 0x5a, 0x5a, 0xff]);
+
+		assert_eq!(
+			run(ReadStorage::read(&mut storage, 30, &mut [0; 4])),
+			Err(MockFlashError::OutOfBounds)
+		);
+	}
+}
@@ -0,0 +1,85 @@
+pub use embedded_storage::block_device::{BlockDeviceStorageError, ErrorType};
+
+mod storage;
+
+pub use self::storage::BlockDeviceStorage;
+
+/// Read only block device trait.
+///
+/// Block devices, like SD cards or eMMC, are addressed by block index, and read and write whole
+/// blocks of `BLOCK_SIZE` bytes. Blocks can be overwritten without being erased first.
+pub trait ReadBlockDevice: ErrorType {
+	/// The number of bytes in a block
+	const BLOCK_SIZE: usize;
+
+	/// Read consecutive blocks, starting at `block`, into `data`, whose length must be a multiple
+	/// of `BLOCK_SIZE`.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the length of `data` is not a multiple of `BLOCK_SIZE`, or if the
+	/// blocks are out of bounds.
+	async fn read_blocks(&mut self, block: u32, data: &mut [u8]) -> Result<(), Self::Error>;
+
+	/// The number of blocks of the device.
+	fn block_count(&self) -> usize;
+
+	/// The capacity of the device in bytes, saturating at `usize::MAX`.
+	fn capacity(&self) -> usize {
+		self.block_count().saturating_mul(Self::BLOCK_SIZE)
+	}
+}
+
+/// Block device trait.
+pub trait BlockDevice: ReadBlockDevice {
+	/// Write `data`, whose length must be a multiple of `BLOCK_SIZE`, to consecutive blocks
+	/// starting at `block`.
+	///
+	/// If power is lost during write, the contents of the blocks being written are undefined.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the length of `data` is not a multiple of `BLOCK_SIZE`, or if the
+	/// blocks are out of bounds.
+	async fn write_blocks(&mut self, block: u32, data: &[u8]) -> Result<(), Self::Error>;
+
+	/// Tell the device that the blocks `from..to` are no longer in use, so that it can discard
+	/// their contents. Trimmed blocks read back undefined data until they are written again.
+	///
+	/// The default implementation does nothing, for devices without trim support.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the blocks are out of bounds (the case where `from > to` is considered
+	/// out of bounds).
+	async fn trim(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		let _ = (from, to);
+		Ok(())
+	}
+}
+
+impl<T: ReadBlockDevice> ReadBlockDevice for &mut T {
+	const BLOCK_SIZE: usize = T::BLOCK_SIZE;
+
+	async fn read_blocks(&mut self, block: u32, data: &mut [u8]) -> Result<(), Self::Error> {
+		T::read_blocks(self, block, data).await
+	}
+
+	fn block_count(&self) -> usize {
+		T::block_count(self)
+	}
+
+	fn capacity(&self) -> usize {
+		T::capacity(self)
+	}
+}
+
+impl<T: BlockDevice> BlockDevice for &mut T {
+	async fn write_blocks(&mut self, block: u32, data: &[u8]) -> Result<(), Self::Error> {
+		T::write_blocks(self, block, data).await
+	}
+
+	async fn trim(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		T::trim(self, from, to).await
+	}
+}
@@ -0,0 +1,169 @@
+use crate::{
+	block_device::{BlockDevice, BlockDeviceStorageError, ReadBlockDevice},
+	ReadStorage, Storage,
+};
+
+/// A [`Storage`] implementation on top of a [`BlockDevice`], doing read/modify/write operations
+/// on partially written blocks through a block buffer.
+///
+/// Whole blocks are read and written straight from and to the caller's slice, only the partial
+/// blocks at the start and the end of an access go through the buffer. Partial blocks already
+/// holding the data are not written again.
+///
+/// As [`Storage`] offsets are `u32`, the storage is limited to the first 4 GiB of larger devices.
+#[derive(Debug)]
+pub struct BlockDeviceStorage<'a, D> {
+	device: D,
+	buffer: &'a mut [u8],
+}
+
+impl<'a, D> BlockDeviceStorage<'a, D>
+where
+	D: ReadBlockDevice,
+{
+	/// Instantiate a new generic `Storage` from a `BlockDevice`
+	///
+	/// **NOTE** This will panic if the provided buffer,
+	/// is smaller than the block size of the device
+	pub fn new(device: D, buffer: &'a mut [u8]) -> Self {
+		if buffer.len() < D::BLOCK_SIZE {
+			panic!("Block buffer is too small");
+		}
+
+		Self { device, buffer }
+	}
+
+	/// Release the underlying block device.
+	pub fn into_inner(self) -> D {
+		self.device
+	}
+
+	/// The capacity of the device, limited to the 4 GiB reachable with `u32` offsets.
+	fn limited_capacity(&self) -> usize {
+		let capacity = self.device.block_count() as u64 * D::BLOCK_SIZE as u64;
+		capacity.min(1 << 32).min(usize::MAX as u64) as usize
+	}
+
+	fn check(&self, offset: u32, length: usize) -> Result<(), BlockDeviceStorageError<D::Error>> {
+		let capacity = self.limited_capacity();
+		if length > capacity || offset as usize > capacity - length {
+			return Err(BlockDeviceStorageError::OutOfBounds);
+		}
+		Ok(())
+	}
+}
+
+impl<'a, D> ReadStorage for BlockDeviceStorage<'a, D>
+where
+	D: ReadBlockDevice,
+{
+	type Error = BlockDeviceStorageError<D::Error>;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.check(offset, bytes.len())?;
+		let buffer = &mut self.buffer[..D::BLOCK_SIZE];
+		let mut block = offset / D::BLOCK_SIZE as u32;
+		let mut bytes = bytes;
+
+		let head = offset as usize % D::BLOCK_SIZE;
+		if head != 0 && !bytes.is_empty() {
+			self.device
+				.read_blocks(block, buffer)
+				.await
+				.map_err(BlockDeviceStorageError::Device)?;
+			let len = bytes.len().min(D::BLOCK_SIZE - head);
+			bytes[..len].copy_from_slice(&buffer[head..head + len]);
+			block += 1;
+			bytes = &mut bytes[len..];
+		}
+
+		let aligned = bytes.len() - bytes.len() % D::BLOCK_SIZE;
+		if aligned != 0 {
+			self.device
+				.read_blocks(block, &mut bytes[..aligned])
+				.await
+				.map_err(BlockDeviceStorageError::Device)?;
+			block += (aligned / D::BLOCK_SIZE) as u32;
+		}
+
+		let tail = &mut bytes[aligned..];
+		if !tail.is_empty() {
+			self.device
+				.read_blocks(block, buffer)
+				.await
+				.map_err(BlockDeviceStorageError::Device)?;
+			let len = tail.len();
+			tail.copy_from_slice(&buffer[..len]);
+		}
+		Ok(())
+	}
+
+	fn capacity(&self) -> usize {
+		self.limited_capacity()
+	}
+}
+
+impl<'a, D> Storage for BlockDeviceStorage<'a, D>
+where
+	D: BlockDevice,
+{
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.check(offset, bytes.len())?;
+		let mut block = offset / D::BLOCK_SIZE as u32;
+		let mut bytes = bytes;
+
+		let head = offset as usize % D::BLOCK_SIZE;
+		if head != 0 && !bytes.is_empty() {
+			let len = bytes.len().min(D::BLOCK_SIZE - head);
+			self.merge(block, head, &bytes[..len]).await?;
+			block += 1;
+			bytes = &bytes[len..];
+		}
+
+		let aligned = bytes.len() - bytes.len() % D::BLOCK_SIZE;
+		if aligned != 0 {
+			self.device
+				.write_blocks(block, &bytes[..aligned])
+				.await
+				.map_err(BlockDeviceStorageError::Device)?;
+			block += (aligned / D::BLOCK_SIZE) as u32;
+		}
+
+		let tail = &bytes[aligned..];
+		if !tail.is_empty() {
+			self.merge(block, 0, tail).await?;
+		}
+		Ok(())
+	}
+}
+
+impl<'a, D> BlockDeviceStorage<'a, D>
+where
+	D: BlockDevice,
+{
+	/// Write `data` at `offset` into `block`, keeping the rest of the block.
+	async fn merge(
+		&mut self,
+		block: u32,
+		offset: usize,
+		data: &[u8],
+	) -> Result<(), BlockDeviceStorageError<D::Error>> {
+		let buffer = &mut self.buffer[..D::BLOCK_SIZE];
+		self.device
+			.read_blocks(block, buffer)
+			.await
+			.map_err(BlockDeviceStorageError::Device)?;
+
+		let range = offset..offset + data.len();
+		// Nothing to do if the data is already there
+		if buffer[range.clone()] == *data {
+			return Ok(());
+		}
+
+		buffer[range].copy_from_slice(data);
+		self.device
+			.write_blocks(block, buffer)
+			.await
+			.map_err(BlockDeviceStorageError::Device)
+	}
+}
@@ -0,0 +1,58 @@
+pub use embedded_storage::eeprom::{page_writes, ErrorType, PageWrites};
+
+/// Read only EEPROM trait.
+///
+/// EEPROMs are byte addressable and need no erase: any byte can be read and overwritten on its
+/// own.
+pub trait ReadEeprom: ErrorType {
+	/// Read a slice of data from the EEPROM, starting at `offset` and reading `bytes.len()`
+	/// bytes. Reads can cross page boundaries.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds.
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error>;
+
+	/// The capacity of the peripheral in bytes.
+	fn capacity(&self) -> usize;
+}
+
+/// EEPROM trait.
+pub trait Eeprom: ReadEeprom {
+	/// The number of bytes a single write command of the peripheral can cover. Pages start at
+	/// multiples of `PAGE_SIZE`.
+	const PAGE_SIZE: usize;
+
+	/// Write a slice of data to the EEPROM, starting at `offset`. Bytes outside of the slice are
+	/// left unchanged.
+	///
+	/// Most chips wrap writes around to the start of the page when they reach its end, so unlike
+	/// their page write command, this function must split writes crossing page boundaries. The
+	/// implementation can use the [`page_writes`] helper function.
+	///
+	/// If power is lost during write, the contents of the page being written are undefined, but
+	/// the pages already written are guaranteed to hold the new data.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds.
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl<T: ReadEeprom> ReadEeprom for &mut T {
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		T::read(self, offset, bytes).await
+	}
+
+	fn capacity(&self) -> usize {
+		T::capacity(self)
+	}
+}
+
+impl<T: Eeprom> Eeprom for &mut T {
+	const PAGE_SIZE: usize = T::PAGE_SIZE;
+
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		T::write(self, offset, bytes).await
+	}
+}
@@ -4,14 +4,31 @@
 //! data asynchronously.
 
 #![no_std]
+#![deny(missing_docs)]
+#![deny(unsafe_code)]
 #![allow(async_fn_in_trait)]
 
+mod crc;
+
+/// Adapters between the blocking and the async traits
+pub mod adapter;
+/// Technology specific traits for block devices
+pub mod block_device;
+/// Technology specific traits for EEPROMs
+pub mod eeprom;
+/// Technology specific traits for NAND Flashes
+pub mod nand_flash;
+/// Technology specific traits for NOR Flashes
 pub mod nor_flash;
+/// Technology specific traits for non-volatile RAMs
+pub mod nvram;
+
+pub use embedded_storage::{StorageError, StorageErrorKind};
 
 /// Transparent read only storage trait
 pub trait ReadStorage {
 	/// An enumeration of storage errors
-	type Error;
+	type Error: StorageError;
 
 	/// Read a slice of data from the storage peripheral, starting the read
 	/// operation at the given address offset, and reading `bytes.len()` bytes.
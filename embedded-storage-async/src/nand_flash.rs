@@ -0,0 +1,136 @@
+pub use embedded_storage::nand_flash::{
+	EccStatus, ErrorType, FtlError, NandFlashError, NandFlashErrorKind,
+};
+
+mod ftl;
+
+pub use self::ftl::Ftl;
+
+/// Read only NAND flash trait.
+///
+/// NAND flashes are addressed by page index, pages `n * PAGES_PER_BLOCK..(n + 1) * PAGES_PER_BLOCK`
+/// forming block `n`, where `PAGES_PER_BLOCK` is `BLOCK_SIZE / PAGE_SIZE`. Every page has a data
+/// area of `PAGE_SIZE` bytes and a spare (OOB) area of `SPARE_SIZE` bytes.
+pub trait ReadNandFlash: ErrorType {
+	/// The number of data bytes in a page
+	const PAGE_SIZE: usize;
+
+	/// The number of spare bytes in a page
+	const SPARE_SIZE: usize;
+
+	/// The number of data bytes in a block, a multiple of `PAGE_SIZE`
+	const BLOCK_SIZE: usize;
+
+	/// Read the start of the data area of `page` into `data`, and the start of its spare area
+	/// into `spare`. Either slice can be empty.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds, or if the data has uncorrectable
+	/// errors.
+	async fn read_page(
+		&mut self,
+		page: u32,
+		data: &mut [u8],
+		spare: &mut [u8],
+	) -> Result<EccStatus, Self::Error>;
+
+	/// Check whether `block` is marked as bad, either by the factory or by
+	/// [`NandFlash::mark_bad_block`].
+	///
+	/// # Errors
+	///
+	/// Returns an error if the block is out of bounds.
+	async fn is_bad_block(&mut self, block: u32) -> Result<bool, Self::Error>;
+
+	/// The number of blocks of the peripheral, including bad blocks.
+	fn block_count(&self) -> usize;
+
+	/// The capacity of the data areas of the peripheral in bytes, including bad blocks, saturating
+	/// at `usize::MAX`.
+	fn capacity(&self) -> usize {
+		self.block_count().saturating_mul(Self::BLOCK_SIZE)
+	}
+}
+
+/// NAND flash trait.
+pub trait NandFlash: ReadNandFlash {
+	/// Erase `block`, setting all data and spare bytes of its pages to 0xff.
+	///
+	/// If power is lost during erase, contents of the block are undefined.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the block is out of bounds, or if erasing failed.
+	async fn erase_block(&mut self, block: u32) -> Result<(), Self::Error>;
+
+	/// Program the start of the data area of `page` with `data`, and the start of its spare area
+	/// with `spare`. Bytes not covered by either slice are left erased.
+	///
+	/// Each page can only be programmed once between erases, and pages of a block have to be
+	/// programmed in ascending order. If power is lost during program, the contents of the page
+	/// are undefined.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds, or if programming failed.
+	async fn program_page(
+		&mut self,
+		page: u32,
+		data: &[u8],
+		spare: &[u8],
+	) -> Result<(), Self::Error>;
+
+	/// Mark `block` as bad, so that [`ReadNandFlash::is_bad_block`] reports it from now on.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the block is out of bounds.
+	async fn mark_bad_block(&mut self, block: u32) -> Result<(), Self::Error>;
+}
+
+impl<T: ReadNandFlash> ReadNandFlash for &mut T {
+	const PAGE_SIZE: usize = T::PAGE_SIZE;
+	const SPARE_SIZE: usize = T::SPARE_SIZE;
+	const BLOCK_SIZE: usize = T::BLOCK_SIZE;
+
+	async fn read_page(
+		&mut self,
+		page: u32,
+		data: &mut [u8],
+		spare: &mut [u8],
+	) -> Result<EccStatus, Self::Error> {
+		T::read_page(self, page, data, spare).await
+	}
+
+	async fn is_bad_block(&mut self, block: u32) -> Result<bool, Self::Error> {
+		T::is_bad_block(self, block).await
+	}
+
+	fn block_count(&self) -> usize {
+		T::block_count(self)
+	}
+
+	fn capacity(&self) -> usize {
+		T::capacity(self)
+	}
+}
+
+impl<T: NandFlash> NandFlash for &mut T {
+	async fn erase_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		T::erase_block(self, block).await
+	}
+
+	async fn program_page(
+		&mut self,
+		page: u32,
+		data: &[u8],
+		spare: &[u8],
+	) -> Result<(), Self::Error> {
+		T::program_page(self, page, data, spare).await
+	}
+
+	async fn mark_bad_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		T::mark_bad_block(self, block).await
+	}
+}
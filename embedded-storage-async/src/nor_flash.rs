@@ -1,9 +1,25 @@
+use embedded_storage::eeprom::{page_writes, PageWrites};
 use embedded_storage::iter::IterableByOverlaps;
-pub use embedded_storage::nor_flash::{ErrorType, NorFlashError, NorFlashErrorKind};
+pub use embedded_storage::nor_flash::{
+	check_sector_map, EraseOps, ErrorType, MergeBufferTooSmall, NorFlashError, NorFlashErrorKind,
+	Sector, SectorRun, SectorStorageError, Sectors,
+};
 use embedded_storage::Region;
 
 use crate::{ReadStorage, Storage};
 
+mod multi_erase;
+mod partition;
+mod sector;
+#[cfg(feature = "embassy-sync")]
+mod shared;
+
+pub use self::multi_erase::{erase_planned, plan_erase, MultiEraseNorFlash};
+pub use self::partition::{Partition, PartitionError};
+pub use self::sector::{SectorMapped, SectorNorFlash};
+#[cfg(feature = "embassy-sync")]
+pub use self::shared::{MutexPartition, SharedPartitions};
+
 /// Read only NOR flash trait.
 pub trait ReadNorFlash: ErrorType {
 	/// The minumum number of bytes the storage peripheral can read
@@ -30,8 +46,22 @@ pub trait NorFlash: ReadNorFlash {
 	/// The minumum number of bytes the storage peripheral can erase
 	const ERASE_SIZE: usize;
 
+	/// The largest number of bytes the storage peripheral programs with a single command, like
+	/// the 256 byte program page of SPI NOR flashes. Program pages start at multiples of
+	/// `PROGRAM_PAGE_SIZE`, which is a multiple of `WRITE_SIZE` and divides `ERASE_SIZE`.
+	///
+	/// Writes are not restricted to a program page, the implementation can split them with the
+	/// [`program_pages`] helper function. It defaults to `ERASE_SIZE`.
+	const PROGRAM_PAGE_SIZE: usize = Self::ERASE_SIZE;
+
+	/// The value every byte reads as after an erase, either `0xff` for flashes erasing to all 1s,
+	/// or `0x00` for flashes erasing to all 0s. Writes can only move bits away from this value.
+	///
+	/// It defaults to `0xff`, the erased state of nearly all NOR flashes.
+	const ERASE_VALUE: u8 = 0xff;
+
 	/// Erase the given storage range, clearing all data within `[from..to]`.
-	/// The given range will contain all 1s afterwards.
+	/// Every byte of the given range will read as `ERASE_VALUE` afterwards.
 	///
 	/// If power is lost during erase, contents of the page are undefined.
 	///
@@ -53,6 +83,16 @@ pub trait NorFlash: ReadNorFlash {
 	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
 }
 
+/// Split the write of `bytes` at `offset` into writes that each stay within a program page of
+/// `T`.
+///
+/// Fails to compile if `T::PROGRAM_PAGE_SIZE` is not a multiple of `T::WRITE_SIZE` dividing
+/// `T::ERASE_SIZE`.
+pub fn program_pages<T: NorFlash>(bytes: &[u8], offset: u32) -> PageWrites<'_> {
+	let () = <T as EraseWrite>::GEOMETRY;
+	page_writes(bytes, offset, T::PROGRAM_PAGE_SIZE)
+}
+
 impl<T: ReadNorFlash> ReadNorFlash for &mut T {
 	const READ_SIZE: usize = T::READ_SIZE;
 
@@ -68,6 +108,8 @@ impl<T: ReadNorFlash> ReadNorFlash for &mut T {
 impl<T: NorFlash> NorFlash for &mut T {
 	const WRITE_SIZE: usize = T::WRITE_SIZE;
 	const ERASE_SIZE: usize = T::ERASE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = T::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = T::ERASE_VALUE;
 
 	async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
 		T::erase(self, from, to).await
@@ -82,11 +124,13 @@ impl<T: NorFlash> NorFlash for &mut T {
 ///
 /// Writes to the same word twice are now allowed. The result is the logical AND of the
 /// previous data and the written data. That is, it is only possible to change 1 bits to 0 bits.
+/// On flashes whose [`NorFlash::ERASE_VALUE`] is `0x00` the roles of the bits are swapped: the
+/// result is the logical OR, and it is only possible to change 0 bits to 1 bits.
 ///
-/// If power is lost during write:
-/// - Bits that were 1 on flash and are written to 1 are guaranteed to stay as 1
-/// - Bits that were 1 on flash and are written to 0 are undefined
-/// - Bits that were 0 on flash are guaranteed to stay as 0
+/// If power is lost during write, with bits in their erased state called erased below:
+/// - Bits that were erased on flash and are written as erased are guaranteed to stay erased
+/// - Bits that were erased on flash and are written as programmed are undefined
+/// - Bits that were programmed on flash are guaranteed to stay programmed
 /// - Rest of the bits in the page are guaranteed to be unchanged
 pub trait MultiwriteNorFlash: NorFlash {}
 impl<T: MultiwriteNorFlash> MultiwriteNorFlash for &mut T {}
@@ -115,7 +159,199 @@ impl Region for Page {
 	}
 }
 
+/// The erase and write operations of a NOR flash needed by `rmw_regions`, whose erase units
+/// are given separately.
+trait EraseWrite: ReadNorFlash {
+	const WRITE_SIZE: usize;
+	const PROGRAM_PAGE_SIZE: usize;
+	const ERASE_VALUE: u8;
+
+	/// Fails to compile if the program page size does not fit the write and erase sizes.
+	const GEOMETRY: ();
+
+	async fn erase_range(&mut self, from: u32, to: u32) -> Result<(), Self::Error>;
+
+	/// Write `bytes`, which stay within a program page, at `offset`.
+	async fn write_page(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+
+	async fn write_words(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		let () = Self::GEOMETRY;
+		for (data, addr) in page_writes(bytes, offset, Self::PROGRAM_PAGE_SIZE) {
+			self.write_page(addr, data).await?;
+		}
+		Ok(())
+	}
+}
+
+impl<T: NorFlash> EraseWrite for T {
+	const WRITE_SIZE: usize = T::WRITE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = T::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = T::ERASE_VALUE;
+
+	const GEOMETRY: () = assert!(
+		T::PROGRAM_PAGE_SIZE % T::WRITE_SIZE == 0 && T::ERASE_SIZE % T::PROGRAM_PAGE_SIZE == 0,
+		"Program page size must be a multiple of the write size dividing the erase size"
+	);
+
+	async fn erase_range(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.erase(from, to).await
+	}
+
+	async fn write_page(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.write(offset, bytes).await
+	}
+}
+
+/// Perform read/modify/write operations on the byte slice, page by page.
+///
+/// Pages already holding `bytes` are left alone, and words which are still erased are written
+/// without erasing the page first. With `multiwrite`, which is only allowed on a
+/// [`MultiwriteNorFlash`], data is also written on top of existing data whenever no bit has to be
+/// changed back to its erased state.
+async fn rmw_write<S: NorFlash>(
+	storage: &mut S,
+	merge_buffer: &mut [u8],
+	offset: u32,
+	bytes: &[u8],
+	multiwrite: bool,
+) -> Result<(), S::Error> {
+	let last_page = storage.capacity() / S::ERASE_SIZE;
+	let pages = (0..last_page as u32).map(move |i| Page::new(i, S::ERASE_SIZE));
+	rmw_regions(storage, pages, merge_buffer, offset, bytes, multiwrite).await
+}
+
+/// Perform read/modify/write operations on the byte slice, erase unit by erase unit, the erase
+/// units being the `regions` covering the flash in ascending order.
+///
+/// `merge_buffer` must hold the largest region.
+async fn rmw_regions<S: EraseWrite, R: Region>(
+	storage: &mut S,
+	regions: impl Iterator<Item = R>,
+	merge_buffer: &mut [u8],
+	offset: u32,
+	bytes: &[u8],
+	multiwrite: bool,
+) -> Result<(), S::Error> {
+	// `data` is the part of `bytes` contained within `page`,
+	// and `addr` in the address offset of `page` + any offset into the page as requested by `address`
+	for (data, page, addr) in regions.overlaps(bytes, offset) {
+		let merge_buffer = &mut merge_buffer[..(page.end() - page.start()) as usize];
+		let offset_into_page = addr.saturating_sub(page.start()) as usize;
+		let range = offset_into_page..offset_into_page + data.len();
+
+		storage.read(page.start(), merge_buffer).await?;
+
+		// Nothing to do if the data is already there
+		if merge_buffer[range.clone()] == *data {
+			continue;
+		}
+
+		// The words overlapping with `data`, which need to be written
+		let words = range.start - range.start % S::WRITE_SIZE
+			..range.end.div_ceil(S::WRITE_SIZE) * S::WRITE_SIZE;
+
+		// Check if we can write the data block directly, under the limitations imposed by NorFlash:
+		// - We can only change bits away from their erased state
+		// - Unless the flash supports multiple writes, we can only write erased words
+		let writable = if multiwrite {
+			// Flip the bits so that erased bits are 1, as on a flash erasing to 0xff
+			let flip = !S::ERASE_VALUE;
+			data.iter()
+				.zip(&merge_buffer[range.clone()])
+				.all(|(a, b)| (*a ^ flip) & (*b ^ flip) == *a ^ flip)
+		} else {
+			merge_buffer[words.clone()]
+				.iter()
+				.all(|byte| *byte == S::ERASE_VALUE)
+		};
+
+		// Words left erased are skipped, so that they remain writable
+		merge_buffer[range].copy_from_slice(data);
+		if writable {
+			write_non_erased(
+				storage,
+				page.start() + words.start as u32,
+				&merge_buffer[words],
+			)
+			.await?;
+		} else {
+			storage.erase_range(page.start(), page.end()).await?;
+			write_non_erased(storage, page.start(), merge_buffer).await?;
+		}
+	}
+	Ok(())
+}
+
+/// Write `data` at `offset`, skipping words which are still erased, so they remain writable.
+async fn write_non_erased<S: EraseWrite>(
+	storage: &mut S,
+	offset: u32,
+	data: &[u8],
+) -> Result<(), S::Error> {
+	let mut start = None;
+	for (i, word) in data.chunks(S::WRITE_SIZE).enumerate() {
+		let erased = word.iter().all(|byte| *byte == S::ERASE_VALUE);
+		match (start, erased) {
+			(None, false) => start = Some(i * S::WRITE_SIZE),
+			(Some(from), true) => {
+				storage
+					.write_words(offset + from as u32, &data[from..i * S::WRITE_SIZE])
+					.await?;
+				start = None;
+			}
+			_ => {}
+		}
+	}
+	if let Some(from) = start {
+		storage
+			.write_words(offset + from as u32, &data[from..])
+			.await?;
+	}
+	Ok(())
+}
+
+/// Read `bytes` at any `offset`, bouncing the unaligned head and tail through `buffer`.
 ///
+/// `buffer` must hold at least [`ReadNorFlash::READ_SIZE`] bytes.
+async fn read_unaligned<S: ReadNorFlash>(
+	storage: &mut S,
+	buffer: &mut [u8],
+	offset: u32,
+	bytes: &mut [u8],
+) -> Result<(), S::Error> {
+	let buffer = &mut buffer[..S::READ_SIZE];
+	let mut offset = offset;
+	let mut bytes = bytes;
+
+	let head = offset as usize % S::READ_SIZE;
+	if head != 0 && !bytes.is_empty() {
+		storage.read(offset - head as u32, buffer).await?;
+		let len = bytes.len().min(S::READ_SIZE - head);
+		bytes[..len].copy_from_slice(&buffer[head..head + len]);
+		offset += len as u32;
+		bytes = &mut bytes[len..];
+	}
+
+	let aligned = bytes.len() - bytes.len() % S::READ_SIZE;
+	if aligned != 0 {
+		storage.read(offset, &mut bytes[..aligned]).await?;
+	}
+
+	let tail = &mut bytes[aligned..];
+	if !tail.is_empty() {
+		storage.read(offset + aligned as u32, buffer).await?;
+		let len = tail.len();
+		tail.copy_from_slice(&buffer[..len]);
+	}
+	Ok(())
+}
+
+/// A [`Storage`] implementation on top of a [`NorFlash`], doing read/modify/write operations
+/// through a merge buffer.
+///
+/// It also works on top of a [`SectorNorFlash`] created with
+/// [`with_sectors`](RmwNorFlashStorage::with_sectors), doing read/modify/write operations sector
+/// by sector.
 #[derive(Debug)]
 pub struct RmwNorFlashStorage<'a, S> {
 	storage: S,
@@ -131,14 +367,23 @@ where
 	/// **NOTE** This will panic if the provided merge buffer,
 	/// is smaller than the erase size of the flash peripheral
 	pub fn new(nor_flash: S, merge_buffer: &'a mut [u8]) -> Self {
+		match Self::try_new(nor_flash, merge_buffer) {
+			Ok(storage) => storage,
+			Err(_) => panic!("Merge buffer is too small"),
+		}
+	}
+
+	/// Instantiate a new generic `Storage` from a `NorFlash` peripheral, failing if the provided
+	/// merge buffer is smaller than the erase size of the flash peripheral
+	pub fn try_new(nor_flash: S, merge_buffer: &'a mut [u8]) -> Result<Self, MergeBufferTooSmall> {
 		if merge_buffer.len() < S::ERASE_SIZE {
-			panic!("Merge buffer is too small");
+			return Err(MergeBufferTooSmall);
 		}
 
-		Self {
+		Ok(Self {
 			storage: nor_flash,
 			merge_buffer,
-		}
+		})
 	}
 }
 
@@ -149,8 +394,7 @@ where
 	type Error = S::Error;
 
 	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
-		// Nothing special to be done for reads
-		self.storage.read(offset, bytes).await
+		read_unaligned(&mut self.storage, self.merge_buffer, offset, bytes).await
 	}
 
 	fn capacity(&self) -> usize {
@@ -163,37 +407,12 @@ where
 	S: NorFlash,
 {
 	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-		// Perform read/modify/write operations on the byte slice.
-		let last_page = self.storage.capacity() / S::ERASE_SIZE;
-
-		// `data` is the part of `bytes` contained within `page`,
-		// and `addr` in the address offset of `page` + any offset into the page as requested by `address`
-		for (data, page, addr) in (0..last_page as u32)
-			.map(move |i| Page::new(i, S::ERASE_SIZE))
-			.overlaps(bytes, offset)
-		{
-			let offset_into_page = addr.saturating_sub(page.start) as usize;
-
-			self.storage
-				.read(page.start, &mut self.merge_buffer[..S::ERASE_SIZE])
-				.await?;
-
-			// If we cannot write multiple times to the same page, we will have to erase it
-			self.storage.erase(page.start, page.end()).await?;
-			self.merge_buffer[..S::ERASE_SIZE]
-				.iter_mut()
-				.skip(offset_into_page)
-				.zip(data)
-				.for_each(|(byte, input)| *byte = *input);
-			self.storage
-				.write(page.start, &self.merge_buffer[..S::ERASE_SIZE])
-				.await?;
-		}
-		Ok(())
+		rmw_write(&mut self.storage, self.merge_buffer, offset, bytes, false).await
 	}
 }
 
-///
+/// A [`Storage`] implementation on top of a [`MultiwriteNorFlash`], only erasing pages when the
+/// new data cannot be written on top of the existing data.
 pub struct RmwMultiwriteNorFlashStorage<'a, S> {
 	storage: S,
 	merge_buffer: &'a mut [u8],
@@ -208,14 +427,23 @@ where
 	/// **NOTE** This will panic if the provided merge buffer,
 	/// is smaller than the erase size of the flash peripheral
 	pub fn new(nor_flash: S, merge_buffer: &'a mut [u8]) -> Self {
+		match Self::try_new(nor_flash, merge_buffer) {
+			Ok(storage) => storage,
+			Err(_) => panic!("Merge buffer is too small"),
+		}
+	}
+
+	/// Instantiate a new generic `Storage` from a `NorFlash` peripheral, failing if the provided
+	/// merge buffer is smaller than the erase size of the flash peripheral
+	pub fn try_new(nor_flash: S, merge_buffer: &'a mut [u8]) -> Result<Self, MergeBufferTooSmall> {
 		if merge_buffer.len() < S::ERASE_SIZE {
-			panic!("Merge buffer is too small");
+			return Err(MergeBufferTooSmall);
 		}
 
-		Self {
+		Ok(Self {
 			storage: nor_flash,
 			merge_buffer,
-		}
+		})
 	}
 }
 
@@ -226,8 +454,7 @@ where
 	type Error = S::Error;
 
 	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
-		// Nothing special to be done for reads
-		self.storage.read(offset, bytes).await
+		read_unaligned(&mut self.storage, self.merge_buffer, offset, bytes).await
 	}
 
 	fn capacity(&self) -> usize {
@@ -240,47 +467,177 @@ where
 	S: MultiwriteNorFlash,
 {
 	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-		// Perform read/modify/write operations on the byte slice.
-		let last_page = self.storage.capacity() / S::ERASE_SIZE;
-
-		// `data` is the part of `bytes` contained within `page`,
-		// and `addr` in the address offset of `page` + any offset into the page as requested by `address`
-		for (data, page, addr) in (0..last_page as u32)
-			.map(move |i| Page::new(i, S::ERASE_SIZE))
-			.overlaps(bytes, offset)
-		{
-			let offset_into_page = addr.saturating_sub(page.start) as usize;
-
-			self.storage
-				.read(page.start, &mut self.merge_buffer[..S::ERASE_SIZE])
-				.await?;
-
-			let rhs = &self.merge_buffer[offset_into_page..S::ERASE_SIZE];
-			let is_subset = data.iter().zip(rhs.iter()).all(|(a, b)| *a & *b == *a);
-
-			// Check if we can write the data block directly, under the limitations imposed by NorFlash:
-			// - We can only change 1's to 0's
-			if is_subset {
-				// Use `merge_buffer` as allocation for padding `data` to `WRITE_SIZE`
-				let offset = addr as usize % S::WRITE_SIZE;
-				let aligned_end = data.len() % S::WRITE_SIZE + offset + data.len();
-				self.merge_buffer[..aligned_end].fill(0xff);
-				self.merge_buffer[offset..offset + data.len()].copy_from_slice(data);
-				self.storage
-					.write(addr - offset as u32, &self.merge_buffer[..aligned_end])
-					.await?;
-			} else {
-				self.storage.erase(page.start, page.end()).await?;
-				self.merge_buffer[..S::ERASE_SIZE]
-					.iter_mut()
-					.skip(offset_into_page)
-					.zip(data)
-					.for_each(|(byte, input)| *byte = *input);
-				self.storage
-					.write(page.start, &self.merge_buffer[..S::ERASE_SIZE])
-					.await?;
-			}
+		rmw_write(&mut self.storage, self.merge_buffer, offset, bytes, true).await
+	}
+}
+
+/// A [`Storage`] implementation on top of a [`NorFlash`], doing read/modify/write operations
+/// through a merge buffer of `N` bytes held internally.
+///
+/// Unlike the borrowed variant, it has no lifetime and can be created in a `const` context, for
+/// instance to live in a `static`. Using it with a merge buffer smaller than the erase size of the
+/// flash fails to compile.
+#[derive(Debug)]
+pub struct OwnedRmwNorFlashStorage<S, const N: usize> {
+	storage: S,
+	merge_buffer: [u8; N],
+}
+
+impl<S, const N: usize> OwnedRmwNorFlashStorage<S, N> {
+	/// Instantiate a new generic `Storage` from a `NorFlash` peripheral
+	pub const fn new(nor_flash: S) -> Self {
+		Self {
+			storage: nor_flash,
+			merge_buffer: [0; N],
 		}
-		Ok(())
+	}
+}
+
+impl<S, const N: usize> OwnedRmwNorFlashStorage<S, N>
+where
+	S: NorFlash,
+{
+	const BUFFER: () = assert!(N >= S::ERASE_SIZE, "Merge buffer is too small");
+}
+
+impl<S, const N: usize> ReadStorage for OwnedRmwNorFlashStorage<S, N>
+where
+	S: NorFlash,
+{
+	type Error = S::Error;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		let () = Self::BUFFER;
+		read_unaligned(&mut self.storage, &mut self.merge_buffer, offset, bytes).await
+	}
+
+	fn capacity(&self) -> usize {
+		self.storage.capacity()
+	}
+}
+
+impl<S, const N: usize> Storage for OwnedRmwNorFlashStorage<S, N>
+where
+	S: NorFlash,
+{
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		let () = Self::BUFFER;
+		rmw_write(
+			&mut self.storage,
+			&mut self.merge_buffer,
+			offset,
+			bytes,
+			false,
+		)
+		.await
+	}
+}
+
+/// A [`Storage`] implementation on top of a [`MultiwriteNorFlash`], only erasing pages when the
+/// new data cannot be written on top of the existing data, with a merge buffer of `N` bytes held
+/// internally.
+///
+/// Unlike the borrowed variant, it has no lifetime and can be created in a `const` context, for
+/// instance to live in a `static`. Using it with a merge buffer smaller than the erase size of the
+/// flash fails to compile.
+#[derive(Debug)]
+pub struct OwnedRmwMultiwriteNorFlashStorage<S, const N: usize> {
+	storage: S,
+	merge_buffer: [u8; N],
+}
+
+impl<S, const N: usize> OwnedRmwMultiwriteNorFlashStorage<S, N> {
+	/// Instantiate a new generic `Storage` from a `NorFlash` peripheral
+	pub const fn new(nor_flash: S) -> Self {
+		Self {
+			storage: nor_flash,
+			merge_buffer: [0; N],
+		}
+	}
+}
+
+impl<S, const N: usize> OwnedRmwMultiwriteNorFlashStorage<S, N>
+where
+	S: NorFlash,
+{
+	const BUFFER: () = assert!(N >= S::ERASE_SIZE, "Merge buffer is too small");
+}
+
+impl<S, const N: usize> ReadStorage for OwnedRmwMultiwriteNorFlashStorage<S, N>
+where
+	S: NorFlash,
+{
+	type Error = S::Error;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		let () = Self::BUFFER;
+		read_unaligned(&mut self.storage, &mut self.merge_buffer, offset, bytes).await
+	}
+
+	fn capacity(&self) -> usize {
+		self.storage.capacity()
+	}
+}
+
+impl<S, const N: usize> Storage for OwnedRmwMultiwriteNorFlashStorage<S, N>
+where
+	S: MultiwriteNorFlash,
+{
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		let () = Self::BUFFER;
+		rmw_write(
+			&mut self.storage,
+			&mut self.merge_buffer,
+			offset,
+			bytes,
+			true,
+		)
+		.await
+	}
+}
+
+/// A [`ReadStorage`] implementation on top of a [`ReadNorFlash`], allowing reads of any length at
+/// any offset.
+///
+/// Reads not aligned to [`ReadNorFlash::READ_SIZE`] are bounced through a buffer, aligned reads go
+/// straight to the flash.
+#[derive(Debug)]
+pub struct ReadNorFlashStorage<'a, S> {
+	storage: S,
+	buffer: &'a mut [u8],
+}
+
+impl<'a, S> ReadNorFlashStorage<'a, S>
+where
+	S: ReadNorFlash,
+{
+	/// Instantiate a new generic `ReadStorage` from a `ReadNorFlash` peripheral
+	///
+	/// **NOTE** This will panic if the provided buffer,
+	/// is smaller than the read size of the flash peripheral
+	pub fn new(nor_flash: S, buffer: &'a mut [u8]) -> Self {
+		if buffer.len() < S::READ_SIZE {
+			panic!("Read buffer is too small");
+		}
+
+		Self {
+			storage: nor_flash,
+			buffer,
+		}
+	}
+}
+
+impl<'a, S> ReadStorage for ReadNorFlashStorage<'a, S>
+where
+	S: ReadNorFlash,
+{
+	type Error = S::Error;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		read_unaligned(&mut self.storage, self.buffer, offset, bytes).await
+	}
+
+	fn capacity(&self) -> usize {
+		self.storage.capacity()
 	}
 }
@@ -0,0 +1,182 @@
+use crate::nor_flash::{EraseOps, NorFlash};
+
+/// NOR flash trait for flashes with erase commands of several sizes, like the 4K, 32K and 64K
+/// erases of most SPI NOR flashes.
+///
+/// Large erases are much faster than the equivalent sequence of small ones. Implementations can
+/// erase any range with the fewest commands by forwarding [`NorFlash::erase`] to
+/// [`erase_planned`], after checking the range.
+pub trait MultiEraseNorFlash: NorFlash {
+	/// The sizes of the supported erase commands in ascending order, starting with
+	/// [`NorFlash::ERASE_SIZE`]. Every size must be a multiple of the previous one.
+	const ERASE_SIZES: &'static [usize];
+
+	/// Whether the whole flash can be erased with a single command.
+	const CHIP_ERASE: bool;
+
+	/// Erase `size` bytes at `offset` with a single command.
+	///
+	/// `size` is one of [`ERASE_SIZES`](Self::ERASE_SIZES) and `offset` a multiple of it, or, if
+	/// [`CHIP_ERASE`](Self::CHIP_ERASE) is set, `offset` is 0 and `size` the capacity for a chip
+	/// erase.
+	///
+	/// If power is lost during erase, contents of the erased range are undefined.
+	async fn erase_block(&mut self, offset: u32, size: usize) -> Result<(), Self::Error>;
+}
+
+impl<T: MultiEraseNorFlash> MultiEraseNorFlash for &mut T {
+	const ERASE_SIZES: &'static [usize] = T::ERASE_SIZES;
+	const CHIP_ERASE: bool = T::CHIP_ERASE;
+
+	async fn erase_block(&mut self, offset: u32, size: usize) -> Result<(), Self::Error> {
+		T::erase_block(self, offset, size).await
+	}
+}
+
+/// Return whether `sizes` starts with `erase_size`, every size being a multiple of the previous
+/// one.
+const fn valid_erase_sizes(sizes: &[usize], erase_size: usize) -> bool {
+	if sizes.is_empty() || sizes[0] != erase_size || erase_size == 0 {
+		return false;
+	}
+	let mut i = 1;
+	while i < sizes.len() {
+		if sizes[i] % sizes[i - 1] != 0 {
+			return false;
+		}
+		i += 1;
+	}
+	true
+}
+
+/// Compile time check of the erase sizes of a [`MultiEraseNorFlash`].
+trait EraseSizes {
+	const GEOMETRY: ();
+}
+
+impl<T: MultiEraseNorFlash> EraseSizes for T {
+	const GEOMETRY: () = assert!(
+		valid_erase_sizes(T::ERASE_SIZES, T::ERASE_SIZE),
+		"Erase sizes must start with the erase size, each being a multiple of the previous one"
+	);
+}
+
+/// Plan the erase of `from..to` on `flash`, which must be aligned to its erase size.
+///
+/// Flashes whose [`ERASE_SIZES`](MultiEraseNorFlash::ERASE_SIZES) do not start with their erase
+/// size, or are not multiples of each other, fail to compile.
+///
+/// **NOTE** This will panic if the range is not aligned to the erase size
+pub fn plan_erase<T: MultiEraseNorFlash>(flash: &T, from: u32, to: u32) -> EraseOps<'static> {
+	let () = <T as EraseSizes>::GEOMETRY;
+
+	let chip = if T::CHIP_ERASE {
+		Some(flash.capacity())
+	} else {
+		None
+	};
+	EraseOps::new(T::ERASE_SIZES, chip, from, to)
+}
+
+/// Erase `from..to` on `flash` with the fewest commands, as planned by [`plan_erase`].
+///
+/// The range must be aligned to the erase size of the flash.
+pub async fn erase_planned<T: MultiEraseNorFlash>(
+	flash: &mut T,
+	from: u32,
+	to: u32,
+) -> Result<(), T::Error> {
+	for (offset, size) in plan_erase(flash, from, to) {
+		flash.erase_block(offset, size).await?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::adapter::{BlockOn, SpinBlockOn};
+	use crate::nor_flash::{ErrorType, NorFlashErrorKind, ReadNorFlash};
+
+	/// A 256K flash recording the erase commands it receives.
+	#[derive(Default)]
+	struct CountingFlash {
+		erases: [(u32, usize); 8],
+		count: usize,
+	}
+
+	impl CountingFlash {
+		fn erases(&self) -> &[(u32, usize)] {
+			&self.erases[..self.count]
+		}
+	}
+
+	impl ErrorType for CountingFlash {
+		type Error = NorFlashErrorKind;
+	}
+
+	impl ReadNorFlash for CountingFlash {
+		const READ_SIZE: usize = 1;
+
+		async fn read(&mut self, _offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			bytes.fill(0xff);
+			Ok(())
+		}
+
+		fn capacity(&self) -> usize {
+			0x4_0000
+		}
+	}
+
+	impl NorFlash for CountingFlash {
+		const WRITE_SIZE: usize = 1;
+		const ERASE_SIZE: usize = 0x1000;
+
+		async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+			erase_planned(self, from, to).await
+		}
+
+		async fn write(&mut self, _offset: u32, _bytes: &[u8]) -> Result<(), Self::Error> {
+			Ok(())
+		}
+	}
+
+	impl MultiEraseNorFlash for CountingFlash {
+		const ERASE_SIZES: &'static [usize] = &[0x1000, 0x8000, 0x1_0000];
+		const CHIP_ERASE: bool = true;
+
+		async fn erase_block(&mut self, offset: u32, size: usize) -> Result<(), Self::Error> {
+			assert_eq!(offset as usize % size, 0);
+			self.erases[self.count] = (offset, size);
+			self.count += 1;
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn erases_with_the_fewest_commands() {
+		let mut flash = CountingFlash::default();
+		SpinBlockOn.block_on(flash.erase(0x7000, 0x2_9000)).unwrap();
+		assert_eq!(
+			flash.erases(),
+			[
+				(0x7000, 0x1000),
+				(0x8000, 0x8000),
+				(0x1_0000, 0x1_0000),
+				(0x2_0000, 0x8000),
+				(0x2_8000, 0x1000),
+			]
+		);
+
+		let mut flash = CountingFlash::default();
+		SpinBlockOn.block_on(flash.erase(0, 0x4_0000)).unwrap();
+		assert_eq!(flash.erases(), [(0, 0x4_0000)]);
+	}
+
+	#[test]
+	#[should_panic(expected = "Erase range is not aligned to the smallest erase size")]
+	fn misaligned_ranges_are_rejected() {
+		let mut flash = CountingFlash::default();
+		let _ = SpinBlockOn.block_on(erase_planned(&mut flash, 0x8000, 0x1_0800));
+	}
+}
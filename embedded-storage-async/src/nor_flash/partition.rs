@@ -0,0 +1,215 @@
+pub use embedded_storage::nor_flash::PartitionError;
+
+use crate::nor_flash::{ErrorType, MultiwriteNorFlash, NorFlash, NorFlashErrorKind, ReadNorFlash};
+
+/// A sub-range of a NOR flash, exposed as a NOR flash of its own.
+///
+/// Addresses are relative to the start of the partition, and any access outside of the
+/// partition is rejected with [`NorFlashErrorKind::OutOfBounds`] before reaching the underlying
+/// flash. The underlying flash can be owned, or borrowed through `&mut`.
+#[derive(Debug)]
+pub struct Partition<F> {
+	flash: F,
+	offset: u32,
+	size: usize,
+}
+
+impl<F> Partition<F>
+where
+	F: NorFlash,
+{
+	/// Create a partition of `size` bytes, starting at `offset` in `flash`.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `offset` and `size` are not multiples of the erase size of `flash`, or
+	/// if the partition does not fit in `flash`.
+	pub fn new(flash: F, offset: u32, size: usize) -> Result<Self, NorFlashErrorKind> {
+		let end = (offset as usize)
+			.checked_add(size)
+			.filter(|end| *end <= u32::MAX as usize)
+			.ok_or(NorFlashErrorKind::OutOfBounds)?;
+		check_range(flash.capacity(), F::ERASE_SIZE, offset as usize, end)?;
+		Ok(Self {
+			flash,
+			offset,
+			size,
+		})
+	}
+
+	/// Create a partition which is already known to fit in `flash`.
+	#[cfg(feature = "embassy-sync")]
+	pub(crate) fn new_unchecked(flash: F, offset: u32, size: usize) -> Self {
+		Self {
+			flash,
+			offset,
+			size,
+		}
+	}
+
+	/// The offset of the partition in the underlying flash.
+	pub fn offset(&self) -> u32 {
+		self.offset
+	}
+
+	/// Release the underlying flash.
+	pub fn into_inner(self) -> F {
+		self.flash
+	}
+
+	fn check(
+		&self,
+		align: usize,
+		offset: u32,
+		length: usize,
+	) -> Result<(), PartitionError<F::Error>> {
+		let end = (offset as usize).saturating_add(length);
+		check_range(self.size, align, offset as usize, end).map_err(PartitionError::Partition)
+	}
+}
+
+fn check_range(
+	capacity: usize,
+	align: usize,
+	from: usize,
+	to: usize,
+) -> Result<(), NorFlashErrorKind> {
+	if from > to || to > capacity {
+		return Err(NorFlashErrorKind::OutOfBounds);
+	}
+	if from % align != 0 || to % align != 0 {
+		return Err(NorFlashErrorKind::NotAligned);
+	}
+	Ok(())
+}
+
+impl<F> ErrorType for Partition<F>
+where
+	F: ErrorType,
+{
+	type Error = PartitionError<F::Error>;
+}
+
+impl<F> ReadNorFlash for Partition<F>
+where
+	F: NorFlash,
+{
+	const READ_SIZE: usize = F::READ_SIZE;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.check(F::READ_SIZE, offset, bytes.len())?;
+		self.flash
+			.read(self.offset + offset, bytes)
+			.await
+			.map_err(PartitionError::Flash)
+	}
+
+	fn capacity(&self) -> usize {
+		self.size
+	}
+}
+
+impl<F> NorFlash for Partition<F>
+where
+	F: NorFlash,
+{
+	const WRITE_SIZE: usize = F::WRITE_SIZE;
+	const ERASE_SIZE: usize = F::ERASE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = F::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = F::ERASE_VALUE;
+
+	async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		check_range(self.size, F::ERASE_SIZE, from as usize, to as usize)
+			.map_err(PartitionError::Partition)?;
+		self.flash
+			.erase(self.offset + from, self.offset + to)
+			.await
+			.map_err(PartitionError::Flash)
+	}
+
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.check(F::WRITE_SIZE, offset, bytes.len())?;
+		self.flash
+			.write(self.offset + offset, bytes)
+			.await
+			.map_err(PartitionError::Flash)
+	}
+}
+
+impl<F> MultiwriteNorFlash for Partition<F> where F: MultiwriteNorFlash {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::adapter::{BlockOn, BlockingAsync, SpinBlockOn};
+	use embedded_storage::nor_flash::{MockFlash, MockFlashError, NorFlashError};
+
+	type Flash = MockFlash<32, 1, 4, 8>;
+
+	#[test]
+	fn rejects_partitions_not_fitting_the_flash() {
+		let new =
+			|offset, size| Partition::new(BlockingAsync::new(Flash::new()), offset, size).err();
+		assert_eq!(new(4, 8), Some(NorFlashErrorKind::NotAligned));
+		assert_eq!(new(8, 12), Some(NorFlashErrorKind::NotAligned));
+		assert_eq!(new(24, 16), Some(NorFlashErrorKind::OutOfBounds));
+		assert_eq!(new(u32::MAX - 7, 16), Some(NorFlashErrorKind::OutOfBounds));
+	}
+
+	#[test]
+	fn translates_offsets() {
+		let mut flash = Flash::new();
+		let mut partition = Partition::new(BlockingAsync::new(&mut flash), 8, 16).unwrap();
+		assert_eq!(partition.offset(), 8);
+		assert_eq!(partition.capacity(), 16);
+
+		SpinBlockOn.block_on(async {
+			partition.write(0, &[1, 2, 3, 4]).await.unwrap();
+			partition.write(12, &[5, 6, 7, 8]).await.unwrap();
+			let mut data = [0; 16];
+			partition.read(0, &mut data).await.unwrap();
+			assert_eq!(data[..4], [1, 2, 3, 4]);
+			assert_eq!(data[12..], [5, 6, 7, 8]);
+
+			partition.erase(8, 16).await.unwrap();
+		});
+		assert_eq!(flash.as_bytes()[..8], [0xff; 8]);
+		assert_eq!(flash.as_bytes()[8..12], [1, 2, 3, 4]);
+		assert_eq!(flash.as_bytes()[12..], [0xff; 20]);
+	}
+
+	#[test]
+	fn rejects_accesses_beyond_both_edges() {
+		let mut flash = Flash::new();
+		let mut partition = Partition::new(BlockingAsync::new(&mut flash), 8, 16).unwrap();
+		let out_of_bounds = Err(PartitionError::Partition(NorFlashErrorKind::OutOfBounds));
+		let not_aligned = Err(PartitionError::Partition(NorFlashErrorKind::NotAligned));
+
+		SpinBlockOn.block_on(async {
+			assert_eq!(partition.read(15, &mut [0; 2]).await, out_of_bounds);
+			assert_eq!(partition.read(u32::MAX, &mut [0; 1]).await, out_of_bounds);
+			assert_eq!(partition.write(16, &[0; 4]).await, out_of_bounds);
+			assert_eq!(partition.write(12, &[0; 8]).await, out_of_bounds);
+			assert_eq!(partition.erase(8, 24).await, out_of_bounds);
+			assert_eq!(partition.erase(8, 0).await, out_of_bounds);
+			assert_eq!(partition.erase(0, 4).await, not_aligned);
+			assert_eq!(partition.write(2, &[0; 4]).await, not_aligned);
+		});
+
+		// Nothing reached the flash, not even the neighbouring erase units
+		assert_eq!(flash.as_bytes(), [0xff; 32]);
+	}
+
+	#[test]
+	fn forwards_flash_errors() {
+		let mut flash = Flash::new();
+		let mut partition = Partition::new(BlockingAsync::new(&mut flash), 8, 16).unwrap();
+
+		let error = SpinBlockOn.block_on(async {
+			partition.write(4, &[0; 4]).await.unwrap();
+			partition.write(4, &[0; 4]).await.unwrap_err()
+		});
+		assert_eq!(error, PartitionError::Flash(MockFlashError::NotErased));
+		assert_eq!(error.kind(), NorFlashErrorKind::Other);
+	}
+}
@@ -0,0 +1,251 @@
+use crate::{
+	nor_flash::{
+		check_sector_map, rmw_regions, EraseWrite, ErrorType, ReadNorFlash, RmwNorFlashStorage,
+		SectorRun, SectorStorageError, Sectors,
+	},
+	Storage,
+};
+
+/// NOR flash trait for flashes with sectors of different sizes, like the internal flash of many
+/// MCUs or SPI NOR flashes with small boot sectors.
+///
+/// The layout of the flash is described by a sector map, and erases operate on whole sectors.
+/// Flashes whose sectors all have the same size should implement [`NorFlash`](super::NorFlash)
+/// instead.
+pub trait SectorNorFlash: ReadNorFlash {
+	/// The minumum number of bytes the storage peripheral can write
+	const WRITE_SIZE: usize;
+
+	/// The largest number of bytes the storage peripheral programs with a single command, like
+	/// [`NorFlash::PROGRAM_PAGE_SIZE`](super::NorFlash::PROGRAM_PAGE_SIZE). Program pages start at
+	/// multiples of `PROGRAM_PAGE_SIZE`, which is a multiple of `WRITE_SIZE` and divides the size
+	/// of every sector.
+	///
+	/// It defaults to `WRITE_SIZE`, which is always valid but has writes split into single words.
+	const PROGRAM_PAGE_SIZE: usize = Self::WRITE_SIZE;
+
+	/// The value every byte reads as after an erase, like
+	/// [`NorFlash::ERASE_VALUE`](super::NorFlash::ERASE_VALUE). It defaults to `0xff`.
+	const ERASE_VALUE: u8 = 0xff;
+
+	/// The sector map of the flash, as runs of equally sized sectors in ascending address order
+	/// covering the whole capacity. It is usually a constant table.
+	fn sector_map(&self) -> &'static [SectorRun];
+
+	/// The sectors of the flash, in ascending address order.
+	fn sectors(&self) -> Sectors {
+		Sectors::new(self.sector_map())
+	}
+
+	/// Erase the given storage range, clearing all data within `[from..to]`.
+	/// Every byte of the given range will read as `ERASE_VALUE` afterwards.
+	///
+	/// If power is lost during erase, contents of the sectors are undefined.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `from` and `to` are not sector boundaries, or out of bounds (the case
+	/// where `from > to` is considered out of bounds).
+	async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error>;
+
+	/// If power is lost during write, the contents of the written words are undefined,
+	/// but the rest of the sector is guaranteed to be unchanged.
+	/// It is not allowed to write to the same word twice.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are not aligned or out of bounds.
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl<T: SectorNorFlash> SectorNorFlash for &mut T {
+	const WRITE_SIZE: usize = T::WRITE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = T::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = T::ERASE_VALUE;
+
+	fn sector_map(&self) -> &'static [SectorRun] {
+		T::sector_map(self)
+	}
+
+	fn sectors(&self) -> Sectors {
+		T::sectors(self)
+	}
+
+	async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		T::erase(self, from, to).await
+	}
+
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		T::write(self, offset, bytes).await
+	}
+}
+
+/// A [`SectorNorFlash`] wrapped by [`RmwNorFlashStorage`], which then does read/modify/write
+/// operations sector by sector. It is created by [`RmwNorFlashStorage::with_sectors`].
+#[derive(Debug)]
+pub struct SectorMapped<S>(S);
+
+impl<S: ErrorType> ErrorType for SectorMapped<S> {
+	type Error = S::Error;
+}
+
+impl<S: ReadNorFlash> ReadNorFlash for SectorMapped<S> {
+	const READ_SIZE: usize = S::READ_SIZE;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.0.read(offset, bytes).await
+	}
+
+	fn capacity(&self) -> usize {
+		self.0.capacity()
+	}
+}
+
+impl<S: SectorNorFlash> EraseWrite for SectorMapped<S> {
+	const WRITE_SIZE: usize = S::WRITE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = S::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = S::ERASE_VALUE;
+
+	// Whether it divides the sectors is only known at runtime
+	const GEOMETRY: () = assert!(
+		S::PROGRAM_PAGE_SIZE % S::WRITE_SIZE == 0,
+		"Program page size must be a multiple of the write size"
+	);
+
+	async fn erase_range(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.0.erase(from, to).await
+	}
+
+	async fn write_page(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.0.write(offset, bytes).await
+	}
+}
+
+impl<'a, S> RmwNorFlashStorage<'a, SectorMapped<S>>
+where
+	S: SectorNorFlash,
+{
+	/// Instantiate a new generic `Storage` from a `SectorNorFlash` peripheral
+	///
+	/// **NOTE** This will panic if the provided merge buffer,
+	/// is smaller than the largest sector of the flash peripheral,
+	/// if the sector map does not cover the capacity of the flash peripheral,
+	/// or if the program page size does not divide every sector
+	pub fn with_sectors(nor_flash: S, merge_buffer: &'a mut [u8]) -> Self {
+		match Self::try_with_sectors(nor_flash, merge_buffer) {
+			Ok(storage) => storage,
+			Err(error) => panic!("{}", error),
+		}
+	}
+
+	/// Instantiate a new generic `Storage` from a `SectorNorFlash` peripheral, failing if the
+	/// provided merge buffer is smaller than the largest sector of the flash peripheral, if the
+	/// sector map does not cover the capacity of the flash peripheral, or if the program page size
+	/// does not divide every sector
+	pub fn try_with_sectors(
+		nor_flash: S,
+		merge_buffer: &'a mut [u8],
+	) -> Result<Self, SectorStorageError> {
+		let sector_map = nor_flash.sector_map();
+		check_sector_map(sector_map, nor_flash.capacity(), S::PROGRAM_PAGE_SIZE)?;
+
+		let largest = sector_map.iter().map(|run| run.size).max();
+		if merge_buffer.len() < largest.unwrap_or(0).max(S::READ_SIZE) {
+			return Err(SectorStorageError::MergeBufferTooSmall);
+		}
+
+		Ok(Self {
+			storage: SectorMapped(nor_flash),
+			merge_buffer,
+		})
+	}
+}
+
+impl<'a, S> Storage for RmwNorFlashStorage<'a, SectorMapped<S>>
+where
+	S: SectorNorFlash,
+{
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		let sectors = self.storage.0.sectors();
+		rmw_regions(
+			&mut self.storage,
+			sectors,
+			self.merge_buffer,
+			offset,
+			bytes,
+			false,
+		)
+		.await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use embedded_storage::nor_flash::NorFlashErrorKind;
+
+	use super::*;
+
+	static SECTOR_MAP: [SectorRun; 2] = [SectorRun::new(2, 8), SectorRun::new(1, 16)];
+
+	/// Sectors of 8, 8 and 16 bytes in program pages of `PAGE` bytes, on a flash of `CAPACITY`
+	/// bytes which is never accessed.
+	struct Geometry<const PAGE: usize, const CAPACITY: usize>;
+
+	impl<const PAGE: usize, const CAPACITY: usize> ErrorType for Geometry<PAGE, CAPACITY> {
+		type Error = NorFlashErrorKind;
+	}
+
+	impl<const PAGE: usize, const CAPACITY: usize> ReadNorFlash for Geometry<PAGE, CAPACITY> {
+		const READ_SIZE: usize = 1;
+
+		async fn read(&mut self, _offset: u32, _bytes: &mut [u8]) -> Result<(), Self::Error> {
+			unreachable!()
+		}
+
+		fn capacity(&self) -> usize {
+			CAPACITY
+		}
+	}
+
+	impl<const PAGE: usize, const CAPACITY: usize> SectorNorFlash for Geometry<PAGE, CAPACITY> {
+		const WRITE_SIZE: usize = 2;
+		const PROGRAM_PAGE_SIZE: usize = PAGE;
+
+		fn sector_map(&self) -> &'static [SectorRun] {
+			&SECTOR_MAP
+		}
+
+		async fn erase(&mut self, _from: u32, _to: u32) -> Result<(), Self::Error> {
+			unreachable!()
+		}
+
+		async fn write(&mut self, _offset: u32, _bytes: &[u8]) -> Result<(), Self::Error> {
+			unreachable!()
+		}
+	}
+
+	#[test]
+	fn reports_invalid_geometries() {
+		let mut buffer = [0; 16];
+		assert!(RmwNorFlashStorage::try_with_sectors(Geometry::<4, 32>, &mut buffer).is_ok());
+		assert_eq!(
+			RmwNorFlashStorage::try_with_sectors(Geometry::<16, 32>, &mut buffer).err(),
+			Some(SectorStorageError::ProgramPageMismatch)
+		);
+		assert_eq!(
+			RmwNorFlashStorage::try_with_sectors(Geometry::<4, 48>, &mut buffer).err(),
+			Some(SectorStorageError::SectorMapMismatch)
+		);
+		assert_eq!(
+			RmwNorFlashStorage::try_with_sectors(Geometry::<4, 32>, &mut buffer[..8]).err(),
+			Some(SectorStorageError::MergeBufferTooSmall)
+		);
+	}
+
+	#[test]
+	#[should_panic(expected = "Sector map must cover the capacity")]
+	fn panics_on_invalid_geometries() {
+		let mut buffer = [0; 16];
+		RmwNorFlashStorage::with_sectors(Geometry::<4, 16>, &mut buffer);
+	}
+}
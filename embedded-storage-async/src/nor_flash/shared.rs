@@ -0,0 +1,317 @@
+use embassy_sync::{blocking_mutex::raw::RawMutex, mutex::Mutex};
+
+use crate::nor_flash::{
+	ErrorType, MultiwriteNorFlash, NorFlash, NorFlashErrorKind, Partition, PartitionError,
+	ReadNorFlash,
+};
+
+/// Hands out disjoint partitions of a flash shared between multiple drivers.
+///
+/// `T` is the shared flash, an async [`Mutex`], so that operations on different partitions
+/// never run concurrently. Partitions have to be requested in increasing address order, which
+/// guarantees that they never overlap.
+#[derive(Debug)]
+pub struct SharedPartitions<'a, T> {
+	flash: &'a T,
+	next: u32,
+}
+
+impl<'a, T> SharedPartitions<'a, T> {
+	/// Start handing out partitions of `flash`.
+	pub fn new(flash: &'a T) -> Self {
+		Self { flash, next: 0 }
+	}
+
+	/// Reserve `size` bytes at `offset`, returning the end of the reserved range.
+	fn reserve(&mut self, offset: u32, size: usize) -> Result<u32, NorFlashErrorKind> {
+		if offset < self.next {
+			return Err(NorFlashErrorKind::OutOfBounds);
+		}
+		let end = (offset as usize)
+			.checked_add(size)
+			.filter(|end| *end <= u32::MAX as usize)
+			.ok_or(NorFlashErrorKind::OutOfBounds)?;
+		Ok(end as u32)
+	}
+}
+
+impl<'a, M, F> SharedPartitions<'a, Mutex<M, F>>
+where
+	M: RawMutex,
+	F: NorFlash,
+{
+	/// Hand out the partition of `size` bytes starting at `offset`.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `offset` and `size` are not multiples of the erase size of the flash,
+	/// if the partition does not fit in the flash, if it starts before the end of the previous
+	/// partition, or if the flash is currently locked.
+	pub fn partition(
+		&mut self,
+		offset: u32,
+		size: usize,
+	) -> Result<MutexPartition<'a, M, F>, NorFlashErrorKind> {
+		let end = self.reserve(offset, size)?;
+		let partition = MutexPartition::new(self.flash, offset, size)?;
+		self.next = end;
+		Ok(partition)
+	}
+}
+
+/// A partition of a flash shared through an async [`Mutex`], handed out by [`SharedPartitions`].
+///
+/// Operations on other partitions wait until the pending operation completes, so the flash can
+/// be shared between concurrently running tasks.
+pub struct MutexPartition<'a, M, F>
+where
+	M: RawMutex,
+{
+	flash: &'a Mutex<M, F>,
+	offset: u32,
+	size: usize,
+}
+
+impl<'a, M, F> MutexPartition<'a, M, F>
+where
+	M: RawMutex,
+	F: NorFlash,
+{
+	/// Create a partition of `size` bytes, starting at `offset` in the shared `flash`.
+	///
+	/// Only [`SharedPartitions`] creates partitions, making sure that they do not overlap.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `offset` and `size` are not multiples of the erase size of `flash`, if
+	/// the partition does not fit in `flash`, or [`NorFlashErrorKind::Busy`] if `flash` is
+	/// currently locked.
+	pub(crate) fn new(
+		flash: &'a Mutex<M, F>,
+		offset: u32,
+		size: usize,
+	) -> Result<Self, NorFlashErrorKind> {
+		let mut guard = flash.try_lock().map_err(|_| NorFlashErrorKind::Busy)?;
+		Partition::new(&mut *guard, offset, size)?;
+		Ok(Self {
+			flash,
+			offset,
+			size,
+		})
+	}
+}
+
+impl<'a, M, F> ErrorType for MutexPartition<'a, M, F>
+where
+	M: RawMutex,
+	F: ErrorType,
+{
+	type Error = PartitionError<F::Error>;
+}
+
+impl<'a, M, F> ReadNorFlash for MutexPartition<'a, M, F>
+where
+	M: RawMutex,
+	F: NorFlash,
+{
+	const READ_SIZE: usize = F::READ_SIZE;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		let mut flash = self.flash.lock().await;
+		Partition::new_unchecked(&mut *flash, self.offset, self.size)
+			.read(offset, bytes)
+			.await
+	}
+
+	fn capacity(&self) -> usize {
+		self.size
+	}
+}
+
+impl<'a, M, F> NorFlash for MutexPartition<'a, M, F>
+where
+	M: RawMutex,
+	F: NorFlash,
+{
+	const WRITE_SIZE: usize = F::WRITE_SIZE;
+	const ERASE_SIZE: usize = F::ERASE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = F::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = F::ERASE_VALUE;
+
+	async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		let mut flash = self.flash.lock().await;
+		Partition::new_unchecked(&mut *flash, self.offset, self.size)
+			.erase(from, to)
+			.await
+	}
+
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		let mut flash = self.flash.lock().await;
+		Partition::new_unchecked(&mut *flash, self.offset, self.size)
+			.write(offset, bytes)
+			.await
+	}
+}
+
+impl<'a, M, F> MultiwriteNorFlash for MutexPartition<'a, M, F>
+where
+	M: RawMutex,
+	F: MultiwriteNorFlash,
+{
+}
+
+#[cfg(test)]
+mod tests {
+	use core::{
+		future::{poll_fn, Future},
+		pin::pin,
+		task::Poll,
+	};
+
+	use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+	use embedded_storage::nor_flash::{MockFlash, MockFlashError};
+
+	use super::*;
+	use crate::adapter::{BlockOn, BlockingAsync, SpinBlockOn};
+
+	/// A flash whose operations are pending once before completing.
+	struct YieldingFlash(BlockingAsync<MockFlash<32, 1, 4, 8>>);
+
+	impl YieldingFlash {
+		fn new() -> Self {
+			Self(BlockingAsync::new(MockFlash::new()))
+		}
+	}
+
+	async fn yield_once() {
+		let mut pending = true;
+		poll_fn(|cx| {
+			if core::mem::take(&mut pending) {
+				cx.waker().wake_by_ref();
+				Poll::Pending
+			} else {
+				Poll::Ready(())
+			}
+		})
+		.await
+	}
+
+	impl ErrorType for YieldingFlash {
+		type Error = MockFlashError;
+	}
+
+	impl ReadNorFlash for YieldingFlash {
+		const READ_SIZE: usize = 1;
+
+		async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			yield_once().await;
+			self.0.read(offset, bytes).await
+		}
+
+		fn capacity(&self) -> usize {
+			self.0.capacity()
+		}
+	}
+
+	impl NorFlash for YieldingFlash {
+		const WRITE_SIZE: usize = 4;
+		const ERASE_SIZE: usize = 8;
+
+		async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+			yield_once().await;
+			self.0.erase(from, to).await
+		}
+
+		async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+			yield_once().await;
+			self.0.write(offset, bytes).await
+		}
+	}
+
+	/// Run both futures to completion, polling them in turn.
+	async fn join<A: Future, B: Future>(a: A, b: B) -> (A::Output, B::Output) {
+		let (mut a, mut b) = (pin!(a), pin!(b));
+		let (mut a_output, mut b_output) = (None, None);
+		poll_fn(|cx| {
+			if a_output.is_none() {
+				if let Poll::Ready(output) = a.as_mut().poll(cx) {
+					a_output = Some(output);
+				}
+			}
+			if b_output.is_none() {
+				if let Poll::Ready(output) = b.as_mut().poll(cx) {
+					b_output = Some(output);
+				}
+			}
+			match (a_output.take(), b_output.take()) {
+				(Some(a), Some(b)) => Poll::Ready((a, b)),
+				(a, b) => {
+					a_output = a;
+					b_output = b;
+					Poll::Pending
+				}
+			}
+		})
+		.await
+	}
+
+	#[test]
+	fn interleaved_operations_wait_for_each_other() {
+		let flash = Mutex::<NoopRawMutex, _>::new(YieldingFlash::new());
+		let mut partitions = SharedPartitions::new(&flash);
+		let mut first = partitions.partition(0, 8).unwrap();
+		let mut second = partitions.partition(16, 16).unwrap();
+
+		let (a, b) = SpinBlockOn.block_on(join(
+			first.write(4, &[1, 2, 3, 4]),
+			second.write(0, &[5, 6, 7, 8]),
+		));
+		a.unwrap();
+		b.unwrap();
+
+		let mut data = [0; 4];
+		SpinBlockOn.block_on(first.read(4, &mut data)).unwrap();
+		assert_eq!(data, [1, 2, 3, 4]);
+		SpinBlockOn.block_on(second.read(0, &mut data)).unwrap();
+		assert_eq!(data, [5, 6, 7, 8]);
+		assert_eq!(
+			SpinBlockOn.block_on(second.write(16, &[0; 4])),
+			Err(PartitionError::Partition(NorFlashErrorKind::OutOfBounds))
+		);
+	}
+
+	#[test]
+	fn rejects_overlapping_partitions() {
+		let flash = Mutex::<NoopRawMutex, _>::new(YieldingFlash::new());
+		let mut partitions = SharedPartitions::new(&flash);
+		partitions.partition(8, 16).unwrap();
+
+		assert_eq!(
+			partitions.partition(0, 8).err(),
+			Some(NorFlashErrorKind::OutOfBounds)
+		);
+		assert_eq!(
+			partitions.partition(16, 8).err(),
+			Some(NorFlashErrorKind::OutOfBounds)
+		);
+		assert_eq!(
+			partitions.partition(24, 4).err(),
+			Some(NorFlashErrorKind::NotAligned)
+		);
+		partitions.partition(24, 8).unwrap();
+	}
+
+	#[test]
+	fn reports_a_locked_flash_as_busy() {
+		let flash = Mutex::<NoopRawMutex, _>::new(YieldingFlash::new());
+		let mut partitions = SharedPartitions::new(&flash);
+
+		let guard = flash.try_lock().unwrap();
+		assert_eq!(
+			partitions.partition(0, 8).err(),
+			Some(NorFlashErrorKind::Busy)
+		);
+		drop(guard);
+		partitions.partition(0, 8).unwrap();
+	}
+}
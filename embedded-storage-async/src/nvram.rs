@@ -0,0 +1,54 @@
+pub use embedded_storage::nvram::{ErrorType, NvramNorFlashError};
+
+mod nor;
+mod storage;
+
+pub use self::nor::NvramNorFlash;
+pub use self::storage::NvramStorage;
+
+/// Read only non-volatile RAM trait.
+///
+/// Non-volatile RAMs, like FRAM or MRAM, are byte addressable, need no erase and can rewrite
+/// any byte an unlimited number of times. [`NvramStorage`] exposes them as a
+/// [`Storage`](crate::Storage) writing in place, without any read/modify/write cycle.
+pub trait ReadNvram: ErrorType {
+	/// Read a slice of data from the memory, starting at `offset` and reading `bytes.len()`
+	/// bytes.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds.
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error>;
+
+	/// The capacity of the peripheral in bytes.
+	fn capacity(&self) -> usize;
+}
+
+/// Non-volatile RAM trait.
+pub trait Nvram: ReadNvram {
+	/// Write a slice of data to the memory, starting at `offset`. Bytes outside of the slice are
+	/// left unchanged.
+	///
+	/// If power is lost during write, the contents of the bytes being written are undefined.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds.
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl<T: ReadNvram> ReadNvram for &mut T {
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		T::read(self, offset, bytes).await
+	}
+
+	fn capacity(&self) -> usize {
+		T::capacity(self)
+	}
+}
+
+impl<T: Nvram> Nvram for &mut T {
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		T::write(self, offset, bytes).await
+	}
+}
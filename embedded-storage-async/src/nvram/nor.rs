@@ -0,0 +1,249 @@
+use crate::{
+	nor_flash::{ErrorType, MultiwriteNorFlash, NorFlash, NorFlashErrorKind, ReadNorFlash},
+	nvram::{Nvram, NvramNorFlashError, ReadNvram},
+};
+
+/// Number of bytes erased or merged at once.
+const CHUNK_SIZE: usize = 32;
+
+/// A non-volatile RAM exposed as a [`MultiwriteNorFlash`], so that code written for NOR flashes
+/// runs on it unchanged.
+///
+/// Reads and writes work on single bytes, and erases on pages of `ERASE_SIZE` bytes, which set
+/// all of their bytes to `0xff`. Like on a real NOR flash, writes only clear bits: the result is
+/// the logical AND of the previous data and the written data. Any capacity beyond the last full
+/// page is left unused.
+#[derive(Debug)]
+pub struct NvramNorFlash<T, const ERASE_SIZE: usize> {
+	nvram: T,
+}
+
+impl<T, const ERASE_SIZE: usize> NvramNorFlash<T, ERASE_SIZE>
+where
+	T: Nvram,
+{
+	const GEOMETRY: () = assert!(ERASE_SIZE > 0, "Erase size must not be zero");
+
+	/// Expose `nvram` as a NOR flash with pages of `ERASE_SIZE` bytes.
+	pub fn new(nvram: T) -> Self {
+		let () = Self::GEOMETRY;
+
+		Self { nvram }
+	}
+
+	/// Release the underlying memory.
+	pub fn into_inner(self) -> T {
+		self.nvram
+	}
+
+	fn check(
+		&self,
+		align: usize,
+		offset: u32,
+		length: usize,
+	) -> Result<(), NvramNorFlashError<T::Error>> {
+		let capacity = ReadNorFlash::capacity(self);
+		let offset = offset as usize;
+		if length > capacity || offset > capacity - length {
+			return Err(NvramNorFlashError::NorFlash(NorFlashErrorKind::OutOfBounds));
+		}
+		if offset % align != 0 || length % align != 0 {
+			return Err(NvramNorFlashError::NorFlash(NorFlashErrorKind::NotAligned));
+		}
+		Ok(())
+	}
+}
+
+impl<T, const ERASE_SIZE: usize> ErrorType for NvramNorFlash<T, ERASE_SIZE>
+where
+	T: Nvram,
+{
+	type Error = NvramNorFlashError<T::Error>;
+}
+
+impl<T, const ERASE_SIZE: usize> ReadNorFlash for NvramNorFlash<T, ERASE_SIZE>
+where
+	T: Nvram,
+{
+	const READ_SIZE: usize = 1;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.check(1, offset, bytes.len())?;
+		ReadNvram::read(&mut self.nvram, offset, bytes)
+			.await
+			.map_err(NvramNorFlashError::Nvram)
+	}
+
+	fn capacity(&self) -> usize {
+		ReadNvram::capacity(&self.nvram) / ERASE_SIZE * ERASE_SIZE
+	}
+}
+
+impl<T, const ERASE_SIZE: usize> NorFlash for NvramNorFlash<T, ERASE_SIZE>
+where
+	T: Nvram,
+{
+	const WRITE_SIZE: usize = 1;
+	const ERASE_SIZE: usize = ERASE_SIZE;
+
+	async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		if from > to {
+			return Err(NvramNorFlashError::NorFlash(NorFlashErrorKind::OutOfBounds));
+		}
+		self.check(ERASE_SIZE, from, (to - from) as usize)?;
+		let erased = [Self::ERASE_VALUE; CHUNK_SIZE];
+		let mut offset = from;
+		while offset < to {
+			let length = core::cmp::min(CHUNK_SIZE, (to - offset) as usize);
+			Nvram::write(&mut self.nvram, offset, &erased[..length])
+				.await
+				.map_err(NvramNorFlashError::Nvram)?;
+			offset += length as u32;
+		}
+		Ok(())
+	}
+
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.check(1, offset, bytes.len())?;
+		let mut merged = [0; CHUNK_SIZE];
+		for (offset, chunk) in (offset..).step_by(CHUNK_SIZE).zip(bytes.chunks(CHUNK_SIZE)) {
+			let merged = &mut merged[..chunk.len()];
+			ReadNvram::read(&mut self.nvram, offset, merged)
+				.await
+				.map_err(NvramNorFlashError::Nvram)?;
+			for (merged, byte) in merged.iter_mut().zip(chunk) {
+				*merged &= byte;
+			}
+			Nvram::write(&mut self.nvram, offset, merged)
+				.await
+				.map_err(NvramNorFlashError::Nvram)?;
+		}
+		Ok(())
+	}
+}
+
+impl<T, const ERASE_SIZE: usize> MultiwriteNorFlash for NvramNorFlash<T, ERASE_SIZE> where T: Nvram {}
+
+#[cfg(test)]
+mod tests {
+	use embedded_storage::StorageErrorKind;
+
+	use super::*;
+	use crate::adapter::{BlockOn, SpinBlockOn};
+
+	/// A memory of `N` bytes, recording the length of the writes it receives.
+	struct RamNvram<const N: usize> {
+		data: [u8; N],
+		writes: [usize; 8],
+		count: usize,
+	}
+
+	impl<const N: usize> RamNvram<N> {
+		fn new() -> Self {
+			Self {
+				data: [0; N],
+				writes: [0; 8],
+				count: 0,
+			}
+		}
+
+		fn writes(&self) -> &[usize] {
+			&self.writes[..self.count]
+		}
+	}
+
+	impl<const N: usize> crate::nvram::ErrorType for RamNvram<N> {
+		type Error = StorageErrorKind;
+	}
+
+	impl<const N: usize> ReadNvram for RamNvram<N> {
+		async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			let offset = offset as usize;
+			let data = self.data.get(offset..offset + bytes.len());
+			bytes.copy_from_slice(data.ok_or(StorageErrorKind::OutOfBounds)?);
+			Ok(())
+		}
+
+		fn capacity(&self) -> usize {
+			N
+		}
+	}
+
+	impl<const N: usize> Nvram for RamNvram<N> {
+		async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+			let offset = offset as usize;
+			let data = self.data.get_mut(offset..offset + bytes.len());
+			data.ok_or(StorageErrorKind::OutOfBounds)?
+				.copy_from_slice(bytes);
+			self.writes[self.count] = bytes.len();
+			self.count += 1;
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn capacity_covers_full_pages_only() {
+		SpinBlockOn.block_on(async {
+			let mut flash = NvramNorFlash::<_, 16>::new(RamNvram::<40>::new());
+			assert_eq!(flash.capacity(), 32);
+			assert_eq!(
+				flash.read(32, &mut [0; 1]).await,
+				Err(NvramNorFlashError::NorFlash(NorFlashErrorKind::OutOfBounds))
+			);
+			assert_eq!(
+				flash.write(30, &[0; 4]).await,
+				Err(NvramNorFlashError::NorFlash(NorFlashErrorKind::OutOfBounds))
+			);
+			assert_eq!(flash.into_inner().writes(), []);
+		});
+	}
+
+	#[test]
+	fn writes_merge_chunk_by_chunk() {
+		SpinBlockOn.block_on(async {
+			let mut flash = NvramNorFlash::<_, 64>::new(RamNvram::<64>::new());
+			flash.erase(0, 64).await.unwrap();
+			flash.write(4, &[0b1100; 40]).await.unwrap();
+			flash.write(20, &[0b1010; 8]).await.unwrap();
+
+			let mut data = [0; 64];
+			flash.read(0, &mut data).await.unwrap();
+			assert_eq!(data[..4], [0xff; 4]);
+			assert_eq!(data[4..20], [0b1100; 16]);
+			assert_eq!(data[20..28], [0b1000; 8]);
+			assert_eq!(data[28..44], [0b1100; 16]);
+			assert_eq!(data[44..], [0xff; 20]);
+
+			// Erases and writes go through the memory 32 bytes at most at a time
+			assert_eq!(flash.into_inner().writes(), [32, 32, 32, 8, 8]);
+		});
+	}
+
+	#[test]
+	fn erases_set_bits_back() {
+		SpinBlockOn.block_on(async {
+			let mut flash = NvramNorFlash::<_, 16>::new(RamNvram::<64>::new());
+			flash.erase(16, 48).await.unwrap();
+			flash.write(16, &[0; 32]).await.unwrap();
+			flash.write(16, &[0xff; 32]).await.unwrap();
+
+			let mut data = [0xff; 32];
+			flash.read(16, &mut data).await.unwrap();
+			assert_eq!(data, [0; 32]);
+
+			flash.erase(16, 32).await.unwrap();
+			flash.read(16, &mut data).await.unwrap();
+			assert_eq!(data[..16], [0xff; 16]);
+			assert_eq!(data[16..], [0; 16]);
+
+			assert_eq!(
+				flash.erase(8, 16).await,
+				Err(NvramNorFlashError::NorFlash(NorFlashErrorKind::NotAligned))
+			);
+			assert_eq!(
+				flash.erase(48, 80).await,
+				Err(NvramNorFlashError::NorFlash(NorFlashErrorKind::OutOfBounds))
+			);
+		});
+	}
+}
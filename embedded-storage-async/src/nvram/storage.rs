@@ -0,0 +1,121 @@
+use crate::{
+	nvram::{Nvram, ReadNvram},
+	ReadStorage, Storage,
+};
+
+/// A [`Storage`] implementation on top of a [`Nvram`], reading and writing in place.
+///
+/// As non-volatile RAMs need no erase, no read/modify/write cycle and no buffer is needed.
+#[derive(Debug)]
+pub struct NvramStorage<T> {
+	nvram: T,
+}
+
+impl<T> NvramStorage<T>
+where
+	T: ReadNvram,
+{
+	/// Instantiate a new generic `Storage` from a `Nvram`
+	pub fn new(nvram: T) -> Self {
+		Self { nvram }
+	}
+
+	/// Release the underlying memory.
+	pub fn into_inner(self) -> T {
+		self.nvram
+	}
+}
+
+impl<T> ReadStorage for NvramStorage<T>
+where
+	T: ReadNvram,
+{
+	type Error = T::Error;
+
+	async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.nvram.read(offset, bytes).await
+	}
+
+	fn capacity(&self) -> usize {
+		self.nvram.capacity()
+	}
+}
+
+impl<T> Storage for NvramStorage<T>
+where
+	T: Nvram,
+{
+	async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.nvram.write(offset, bytes).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use embedded_storage::StorageErrorKind;
+
+	use super::*;
+	use crate::adapter::{BlockOn, SpinBlockOn};
+
+	/// A memory of 16 bytes, counting the writes it receives.
+	#[derive(Default)]
+	struct RamNvram([u8; 16], usize);
+
+	impl crate::nvram::ErrorType for RamNvram {
+		type Error = StorageErrorKind;
+	}
+
+	impl ReadNvram for RamNvram {
+		async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			let offset = offset as usize;
+			let data = self.0.get(offset..offset + bytes.len());
+			bytes.copy_from_slice(data.ok_or(StorageErrorKind::OutOfBounds)?);
+			Ok(())
+		}
+
+		fn capacity(&self) -> usize {
+			self.0.len()
+		}
+	}
+
+	impl Nvram for RamNvram {
+		async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+			let offset = offset as usize;
+			let data = self.0.get_mut(offset..offset + bytes.len());
+			data.ok_or(StorageErrorKind::OutOfBounds)?
+				.copy_from_slice(bytes);
+			self.1 += 1;
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn writes_in_place() {
+		SpinBlockOn.block_on(async {
+			let mut storage = NvramStorage::new(RamNvram::default());
+			assert_eq!(storage.capacity(), 16);
+			storage.write(3, &[1, 2, 3]).await.unwrap();
+			storage.write(4, &[0xff]).await.unwrap();
+
+			let mut data = [0; 6];
+			storage.read(2, &mut data).await.unwrap();
+			assert_eq!(data, [0, 1, 0xff, 3, 0, 0]);
+			assert_eq!(storage.into_inner().1, 2);
+		});
+	}
+
+	#[test]
+	fn forwards_errors() {
+		SpinBlockOn.block_on(async {
+			let mut storage = NvramStorage::new(RamNvram::default());
+			assert_eq!(
+				storage.write(14, &[0; 3]).await,
+				Err(StorageErrorKind::OutOfBounds)
+			);
+			assert_eq!(
+				storage.read(17, &mut []).await,
+				Err(StorageErrorKind::OutOfBounds)
+			);
+		});
+	}
+}
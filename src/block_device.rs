@@ -0,0 +1,136 @@
+use crate::{StorageError, StorageErrorKind};
+
+mod storage;
+
+pub use self::storage::{BlockDeviceStorage, BlockDeviceStorageError};
+
+/// A trait that BlockDevice implementations can use to share an error type.
+pub trait ErrorType {
+	/// Errors returned by this block device.
+	type Error: StorageError;
+}
+
+/// Read only block device trait.
+///
+/// Block devices, like SD cards or eMMC, are addressed by block index, and read and write whole
+/// blocks of `BLOCK_SIZE` bytes. Blocks can be overwritten without being erased first.
+pub trait ReadBlockDevice: ErrorType {
+	/// The number of bytes in a block
+	const BLOCK_SIZE: usize;
+
+	/// Read consecutive blocks, starting at `block`, into `data`, whose length must be a multiple
+	/// of `BLOCK_SIZE`.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the length of `data` is not a multiple of `BLOCK_SIZE`, or if the
+	/// blocks are out of bounds. The implementation can use the [`check_read`] helper function.
+	fn read_blocks(&mut self, block: u32, data: &mut [u8]) -> Result<(), Self::Error>;
+
+	/// The number of blocks of the device.
+	fn block_count(&self) -> usize;
+
+	/// The capacity of the device in bytes, saturating at `usize::MAX`.
+	fn capacity(&self) -> usize {
+		self.block_count().saturating_mul(Self::BLOCK_SIZE)
+	}
+}
+
+/// Return whether a read operation is aligned and within bounds.
+pub fn check_read<T: ReadBlockDevice>(
+	device: &T,
+	block: u32,
+	length: usize,
+) -> Result<(), StorageErrorKind> {
+	check_blocks(device, block, length)
+}
+
+/// Block device trait.
+pub trait BlockDevice: ReadBlockDevice {
+	/// Write `data`, whose length must be a multiple of `BLOCK_SIZE`, to consecutive blocks
+	/// starting at `block`.
+	///
+	/// If power is lost during write, the contents of the blocks being written are undefined.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the length of `data` is not a multiple of `BLOCK_SIZE`, or if the
+	/// blocks are out of bounds. The implementation can use the [`check_write`] helper function.
+	fn write_blocks(&mut self, block: u32, data: &[u8]) -> Result<(), Self::Error>;
+
+	/// Tell the device that the blocks `from..to` are no longer in use, so that it can discard
+	/// their contents. Trimmed blocks read back undefined data until they are written again.
+	///
+	/// The default implementation does nothing, for devices without trim support.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the blocks are out of bounds (the case where `from > to` is considered
+	/// out of bounds). The implementation can use the [`check_trim`] helper function.
+	fn trim(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		let _ = (from, to);
+		Ok(())
+	}
+}
+
+/// Return whether a write operation is aligned and within bounds.
+pub fn check_write<T: BlockDevice>(
+	device: &T,
+	block: u32,
+	length: usize,
+) -> Result<(), StorageErrorKind> {
+	check_blocks(device, block, length)
+}
+
+/// Return whether a trim operation is within bounds.
+pub fn check_trim<T: BlockDevice>(device: &T, from: u32, to: u32) -> Result<(), StorageErrorKind> {
+	if from > to || to as usize > device.block_count() {
+		return Err(StorageErrorKind::OutOfBounds);
+	}
+	Ok(())
+}
+
+fn check_blocks<T: ReadBlockDevice>(
+	device: &T,
+	block: u32,
+	length: usize,
+) -> Result<(), StorageErrorKind> {
+	if length % T::BLOCK_SIZE != 0 {
+		return Err(StorageErrorKind::NotAligned);
+	}
+	let blocks = length / T::BLOCK_SIZE;
+	if blocks > device.block_count() || block as usize > device.block_count() - blocks {
+		return Err(StorageErrorKind::OutOfBounds);
+	}
+	Ok(())
+}
+
+impl<T: ErrorType> ErrorType for &mut T {
+	type Error = T::Error;
+}
+
+impl<T: ReadBlockDevice> ReadBlockDevice for &mut T {
+	const BLOCK_SIZE: usize = T::BLOCK_SIZE;
+
+	fn read_blocks(&mut self, block: u32, data: &mut [u8]) -> Result<(), Self::Error> {
+		T::read_blocks(self, block, data)
+	}
+
+	fn block_count(&self) -> usize {
+		T::block_count(self)
+	}
+
+	fn capacity(&self) -> usize {
+		T::capacity(self)
+	}
+}
+
+impl<T: BlockDevice> BlockDevice for &mut T {
+	fn write_blocks(&mut self, block: u32, data: &[u8]) -> Result<(), Self::Error> {
+		T::write_blocks(self, block, data)
+	}
+
+	fn trim(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		T::trim(self, from, to)
+	}
+}
@@ -0,0 +1,335 @@
+use crate::{
+	block_device::{BlockDevice, ReadBlockDevice},
+	ReadStorage, Storage, StorageError, StorageErrorKind,
+};
+
+/// Errors returned by [`BlockDeviceStorage`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum BlockDeviceStorageError<E> {
+	/// The arguments are out of the bounds of the device.
+	OutOfBounds,
+
+	/// The underlying block device returned an error.
+	Device(E),
+}
+
+impl<E: StorageError> StorageError for BlockDeviceStorageError<E> {
+	fn kind(&self) -> StorageErrorKind {
+		match self {
+			Self::OutOfBounds => StorageErrorKind::OutOfBounds,
+			Self::Device(e) => e.kind(),
+		}
+	}
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for BlockDeviceStorageError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::OutOfBounds => write!(f, "Arguments are out of bounds"),
+			Self::Device(e) => e.fmt(f),
+		}
+	}
+}
+
+/// A [`Storage`] implementation on top of a [`BlockDevice`], doing read/modify/write operations
+/// on partially written blocks through a block buffer.
+///
+/// Whole blocks are read and written straight from and to the caller's slice, only the partial
+/// blocks at the start and the end of an access go through the buffer. Partial blocks already
+/// holding the data are not written again.
+///
+/// As [`Storage`] offsets are `u32`, the storage is limited to the first 4 GiB of larger devices.
+#[derive(Debug)]
+pub struct BlockDeviceStorage<'a, D> {
+	device: D,
+	buffer: &'a mut [u8],
+}
+
+impl<'a, D> BlockDeviceStorage<'a, D>
+where
+	D: ReadBlockDevice,
+{
+	/// Instantiate a new generic `Storage` from a `BlockDevice`
+	///
+	/// **NOTE** This will panic if the provided buffer,
+	/// is smaller than the block size of the device
+	pub fn new(device: D, buffer: &'a mut [u8]) -> Self {
+		if buffer.len() < D::BLOCK_SIZE {
+			panic!("Block buffer is too small");
+		}
+
+		Self { device, buffer }
+	}
+
+	/// Release the underlying block device.
+	pub fn into_inner(self) -> D {
+		self.device
+	}
+
+	/// The capacity of the device, limited to the 4 GiB reachable with `u32` offsets.
+	fn limited_capacity(&self) -> usize {
+		let capacity = self.device.block_count() as u64 * D::BLOCK_SIZE as u64;
+		capacity.min(1 << 32).min(usize::MAX as u64) as usize
+	}
+
+	fn check(&self, offset: u32, length: usize) -> Result<(), BlockDeviceStorageError<D::Error>> {
+		let capacity = self.limited_capacity();
+		if length > capacity || offset as usize > capacity - length {
+			return Err(BlockDeviceStorageError::OutOfBounds);
+		}
+		Ok(())
+	}
+}
+
+impl<'a, D> ReadStorage for BlockDeviceStorage<'a, D>
+where
+	D: ReadBlockDevice,
+{
+	type Error = BlockDeviceStorageError<D::Error>;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.check(offset, bytes.len())?;
+		let buffer = &mut self.buffer[..D::BLOCK_SIZE];
+		let mut block = offset / D::BLOCK_SIZE as u32;
+		let mut bytes = bytes;
+
+		let head = offset as usize % D::BLOCK_SIZE;
+		if head != 0 && !bytes.is_empty() {
+			self.device
+				.read_blocks(block, buffer)
+				.map_err(BlockDeviceStorageError::Device)?;
+			let len = bytes.len().min(D::BLOCK_SIZE - head);
+			bytes[..len].copy_from_slice(&buffer[head..head + len]);
+			block += 1;
+			bytes = &mut bytes[len..];
+		}
+
+		let aligned = bytes.len() - bytes.len() % D::BLOCK_SIZE;
+		if aligned != 0 {
+			self.device
+				.read_blocks(block, &mut bytes[..aligned])
+				.map_err(BlockDeviceStorageError::Device)?;
+			block += (aligned / D::BLOCK_SIZE) as u32;
+		}
+
+		let tail = &mut bytes[aligned..];
+		if !tail.is_empty() {
+			self.device
+				.read_blocks(block, buffer)
+				.map_err(BlockDeviceStorageError::Device)?;
+			let len = tail.len();
+			tail.copy_from_slice(&buffer[..len]);
+		}
+		Ok(())
+	}
+
+	fn capacity(&self) -> usize {
+		self.limited_capacity()
+	}
+}
+
+impl<'a, D> Storage for BlockDeviceStorage<'a, D>
+where
+	D: BlockDevice,
+{
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.check(offset, bytes.len())?;
+		let mut block = offset / D::BLOCK_SIZE as u32;
+		let mut bytes = bytes;
+
+		let head = offset as usize % D::BLOCK_SIZE;
+		if head != 0 && !bytes.is_empty() {
+			let len = bytes.len().min(D::BLOCK_SIZE - head);
+			self.merge(block, head, &bytes[..len])?;
+			block += 1;
+			bytes = &bytes[len..];
+		}
+
+		let aligned = bytes.len() - bytes.len() % D::BLOCK_SIZE;
+		if aligned != 0 {
+			self.device
+				.write_blocks(block, &bytes[..aligned])
+				.map_err(BlockDeviceStorageError::Device)?;
+			block += (aligned / D::BLOCK_SIZE) as u32;
+		}
+
+		let tail = &bytes[aligned..];
+		if !tail.is_empty() {
+			self.merge(block, 0, tail)?;
+		}
+		Ok(())
+	}
+}
+
+impl<'a, D> BlockDeviceStorage<'a, D>
+where
+	D: BlockDevice,
+{
+	/// Write `data` at `offset` into `block`, keeping the rest of the block.
+	fn merge(
+		&mut self,
+		block: u32,
+		offset: usize,
+		data: &[u8],
+	) -> Result<(), BlockDeviceStorageError<D::Error>> {
+		let buffer = &mut self.buffer[..D::BLOCK_SIZE];
+		self.device
+			.read_blocks(block, buffer)
+			.map_err(BlockDeviceStorageError::Device)?;
+
+		let range = offset..offset + data.len();
+		// Nothing to do if the data is already there
+		if buffer[range.clone()] == *data {
+			return Ok(());
+		}
+
+		buffer[range].copy_from_slice(data);
+		self.device
+			.write_blocks(block, buffer)
+			.map_err(BlockDeviceStorageError::Device)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::block_device::ErrorType;
+
+	/// A device of `BLOCKS` blocks of 512 bytes, reading zeros.
+	struct LargeDevice<const BLOCKS: usize>;
+
+	impl<const BLOCKS: usize> ErrorType for LargeDevice<BLOCKS> {
+		type Error = StorageErrorKind;
+	}
+
+	impl<const BLOCKS: usize> ReadBlockDevice for LargeDevice<BLOCKS> {
+		const BLOCK_SIZE: usize = 512;
+
+		fn read_blocks(&mut self, _block: u32, data: &mut [u8]) -> Result<(), Self::Error> {
+			data.fill(0);
+			Ok(())
+		}
+
+		fn block_count(&self) -> usize {
+			BLOCKS
+		}
+	}
+
+	#[test]
+	fn device_capacities_saturate() {
+		assert_eq!(LargeDevice::<{ usize::MAX }>.capacity(), usize::MAX);
+	}
+
+	#[test]
+	#[cfg(target_pointer_width = "64")]
+	fn storage_is_limited_to_4_gib() {
+		let mut buffer = [0; 512];
+		let mut storage = BlockDeviceStorage::new(LargeDevice::<{ 1 << 24 }>, &mut buffer);
+		assert_eq!(storage.capacity(), 1 << 32);
+
+		let mut bytes = [0xff; 4];
+		storage.read(u32::MAX - 3, &mut bytes).unwrap();
+		assert_eq!(bytes, [0; 4]);
+		assert_eq!(
+			storage.read(u32::MAX - 2, &mut bytes),
+			Err(BlockDeviceStorageError::OutOfBounds)
+		);
+	}
+
+	/// A device of 8 blocks of 4 bytes, recording the block writes it receives.
+	#[derive(Default)]
+	struct RecordingDevice {
+		data: [u8; 32],
+		writes: [(u32, usize); 8],
+		count: usize,
+	}
+
+	impl RecordingDevice {
+		fn writes(&self) -> &[(u32, usize)] {
+			&self.writes[..self.count]
+		}
+	}
+
+	impl ErrorType for RecordingDevice {
+		type Error = StorageErrorKind;
+	}
+
+	impl ReadBlockDevice for RecordingDevice {
+		const BLOCK_SIZE: usize = 4;
+
+		fn read_blocks(&mut self, block: u32, data: &mut [u8]) -> Result<(), Self::Error> {
+			crate::block_device::check_read(self, block, data.len())?;
+			let offset = block as usize * 4;
+			data.copy_from_slice(&self.data[offset..offset + data.len()]);
+			Ok(())
+		}
+
+		fn block_count(&self) -> usize {
+			8
+		}
+	}
+
+	impl BlockDevice for RecordingDevice {
+		fn write_blocks(&mut self, block: u32, data: &[u8]) -> Result<(), Self::Error> {
+			crate::block_device::check_write(self, block, data.len())?;
+			let offset = block as usize * 4;
+			self.data[offset..offset + data.len()].copy_from_slice(data);
+			self.writes[self.count] = (block, data.len() / 4);
+			self.count += 1;
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn unaligned_writes_merge_into_one_block() {
+		let mut buffer = [0; 4];
+		let mut storage = BlockDeviceStorage::new(RecordingDevice::default(), &mut buffer);
+		storage.write(5, &[1, 2]).unwrap();
+
+		let device = storage.into_inner();
+		assert_eq!(device.writes(), [(1, 1)]);
+		assert_eq!(device.data[..8], [0, 0, 0, 0, 0, 1, 2, 0]);
+	}
+
+	#[test]
+	fn writes_split_into_head_middle_and_tail() {
+		let mut buffer = [0; 4];
+		let mut storage = BlockDeviceStorage::new(RecordingDevice::default(), &mut buffer);
+		let bytes = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+		storage.write(2, &bytes).unwrap();
+
+		let mut data = [0xff; 15];
+		storage.read(1, &mut data).unwrap();
+		assert_eq!(data[0], 0);
+		assert_eq!(data[1..12], bytes);
+		assert_eq!(data[12..], [0; 3]);
+
+		// The partial blocks at both ends are merged, the full blocks are written at once
+		assert_eq!(storage.into_inner().writes(), [(0, 1), (1, 2), (3, 1)]);
+	}
+
+	#[test]
+	fn unchanged_partial_blocks_are_not_written() {
+		let mut buffer = [0; 4];
+		let mut storage = BlockDeviceStorage::new(RecordingDevice::default(), &mut buffer);
+		storage.write(1, &[0; 2]).unwrap();
+		storage.write(6, &[0; 4]).unwrap();
+		storage.write(9, &[]).unwrap();
+		assert_eq!(storage.into_inner().writes(), []);
+	}
+
+	#[test]
+	fn accesses_beyond_the_device_fail() {
+		let mut buffer = [0; 4];
+		let mut storage = BlockDeviceStorage::new(RecordingDevice::default(), &mut buffer);
+		assert_eq!(
+			storage.write(30, &[0; 3]),
+			Err(BlockDeviceStorageError::OutOfBounds)
+		);
+		assert_eq!(
+			storage.read(33, &mut []),
+			Err(BlockDeviceStorageError::OutOfBounds)
+		);
+		assert_eq!(storage.into_inner().writes(), []);
+	}
+}
@@ -0,0 +1,21 @@
+/// Bitwise CRC-32 (IEEE), trading speed for not needing a lookup table.
+pub(crate) struct Crc32(u32);
+
+impl Crc32 {
+	pub(crate) fn new() -> Self {
+		Self(!0)
+	}
+
+	pub(crate) fn update(&mut self, data: &[u8]) {
+		for byte in data {
+			self.0 ^= *byte as u32;
+			for _ in 0..8 {
+				self.0 = (self.0 >> 1) ^ (0xedb8_8320 & (self.0 & 1).wrapping_neg());
+			}
+		}
+	}
+
+	pub(crate) fn finish(&self) -> u32 {
+		!self.0
+	}
+}
@@ -0,0 +1,220 @@
+use crate::{
+	iter::{IterableByOverlaps, OverlapIterator},
+	nor_flash::{Page, Pages},
+	StorageError, StorageErrorKind,
+};
+
+/// A trait that Eeprom implementations can use to share an error type.
+pub trait ErrorType {
+	/// Errors returned by this EEPROM.
+	type Error: StorageError;
+}
+
+/// Read only EEPROM trait.
+///
+/// EEPROMs are byte addressable and need no erase: any byte can be read and overwritten on its
+/// own.
+pub trait ReadEeprom: ErrorType {
+	/// Read a slice of data from the EEPROM, starting at `offset` and reading `bytes.len()`
+	/// bytes. Reads can cross page boundaries.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds. The implementation can use the
+	/// [`check_read`] helper function.
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error>;
+
+	/// The capacity of the peripheral in bytes.
+	fn capacity(&self) -> usize;
+}
+
+/// Return whether a read operation is within bounds.
+pub fn check_read<T: ReadEeprom>(
+	eeprom: &T,
+	offset: u32,
+	length: usize,
+) -> Result<(), StorageErrorKind> {
+	check_slice(eeprom, offset, length)
+}
+
+/// EEPROM trait.
+pub trait Eeprom: ReadEeprom {
+	/// The number of bytes a single write command of the peripheral can cover. Pages start at
+	/// multiples of `PAGE_SIZE`.
+	const PAGE_SIZE: usize;
+
+	/// Write a slice of data to the EEPROM, starting at `offset`. Bytes outside of the slice are
+	/// left unchanged.
+	///
+	/// Most chips wrap writes around to the start of the page when they reach its end, so unlike
+	/// their page write command, this function must split writes crossing page boundaries. The
+	/// implementation can use the [`page_writes`] helper function.
+	///
+	/// If power is lost during write, the contents of the page being written are undefined, but
+	/// the pages already written are guaranteed to hold the new data.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds. The implementation can use the
+	/// [`check_write`] helper function.
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Return whether a write operation is within bounds.
+pub fn check_write<T: Eeprom>(
+	eeprom: &T,
+	offset: u32,
+	length: usize,
+) -> Result<(), StorageErrorKind> {
+	check_slice(eeprom, offset, length)
+}
+
+fn check_slice<T: ReadEeprom>(
+	eeprom: &T,
+	offset: u32,
+	length: usize,
+) -> Result<(), StorageErrorKind> {
+	let offset = offset as usize;
+	if length > eeprom.capacity() || offset > eeprom.capacity() - length {
+		return Err(StorageErrorKind::OutOfBounds);
+	}
+	Ok(())
+}
+
+/// Iterator splitting a write at page boundaries, producing slice-address pairs which each fit
+/// in a single page.
+pub struct PageWrites<'a> {
+	overlaps: OverlapIterator<'a, Page, Pages>,
+}
+
+/// Split the write of `bytes` at `offset` into writes that each stay within a page of
+/// `page_size` bytes.
+///
+/// **NOTE** This will panic if `page_size` is zero.
+pub fn page_writes(bytes: &[u8], offset: u32, page_size: usize) -> PageWrites<'_> {
+	assert!(page_size != 0, "Page size must not be zero");
+	let first = offset / page_size as u32;
+	// An empty write covers no page at all
+	let last = match bytes.len() {
+		0 => first,
+		len => ((offset as usize + len + page_size - 1) / page_size) as u32,
+	};
+	let pages = Pages {
+		indices: first..last,
+		size: page_size,
+	};
+	PageWrites {
+		overlaps: pages.overlaps(bytes, offset),
+	}
+}
+
+impl<'a> Iterator for PageWrites<'a> {
+	type Item = (&'a [u8], u32);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.overlaps.next().map(|(data, _, addr)| (data, addr))
+	}
+}
+
+impl<T: ErrorType> ErrorType for &mut T {
+	type Error = T::Error;
+}
+
+impl<T: ReadEeprom> ReadEeprom for &mut T {
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		T::read(self, offset, bytes)
+	}
+
+	fn capacity(&self) -> usize {
+		T::capacity(self)
+	}
+}
+
+impl<T: Eeprom> Eeprom for &mut T {
+	const PAGE_SIZE: usize = T::PAGE_SIZE;
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		T::write(self, offset, bytes)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// An EEPROM of 16 bytes in pages of 4 bytes.
+	struct Eeprom16;
+
+	impl ErrorType for Eeprom16 {
+		type Error = StorageErrorKind;
+	}
+
+	impl ReadEeprom for Eeprom16 {
+		fn read(&mut self, _offset: u32, _bytes: &mut [u8]) -> Result<(), Self::Error> {
+			Ok(())
+		}
+
+		fn capacity(&self) -> usize {
+			16
+		}
+	}
+
+	impl Eeprom for Eeprom16 {
+		const PAGE_SIZE: usize = 4;
+
+		fn write(&mut self, _offset: u32, _bytes: &[u8]) -> Result<(), Self::Error> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn checks_bounds() {
+		for check in [check_read::<Eeprom16>, check_write::<Eeprom16>] {
+			assert_eq!(check(&Eeprom16, 0, 16), Ok(()));
+			assert_eq!(check(&Eeprom16, 13, 3), Ok(()));
+			assert_eq!(check(&Eeprom16, 16, 0), Ok(()));
+			assert_eq!(check(&Eeprom16, 14, 3), Err(StorageErrorKind::OutOfBounds));
+			assert_eq!(check(&Eeprom16, 17, 0), Err(StorageErrorKind::OutOfBounds));
+			assert_eq!(check(&Eeprom16, 0, 17), Err(StorageErrorKind::OutOfBounds));
+			assert_eq!(
+				check(&Eeprom16, u32::MAX, 2),
+				Err(StorageErrorKind::OutOfBounds)
+			);
+		}
+	}
+
+	#[test]
+	fn aligned_writes_cover_whole_pages() {
+		let bytes = [1, 2, 3, 4, 5, 6, 7, 8];
+		let mut writes = page_writes(&bytes, 4, 4);
+		assert_eq!(writes.next(), Some((&bytes[..4], 4)));
+		assert_eq!(writes.next(), Some((&bytes[4..], 8)));
+		assert_eq!(writes.next(), None);
+	}
+
+	#[test]
+	fn unaligned_writes_split_at_page_boundaries() {
+		let bytes = [1, 2, 3, 4, 5, 6, 7, 8];
+		let mut writes = page_writes(&bytes, 3, 4);
+		assert_eq!(writes.next(), Some((&bytes[..1], 3)));
+		assert_eq!(writes.next(), Some((&bytes[1..5], 4)));
+		assert_eq!(writes.next(), Some((&bytes[5..], 8)));
+		assert_eq!(writes.next(), None);
+
+		let mut writes = page_writes(&bytes[..2], 5, 4);
+		assert_eq!(writes.next(), Some((&bytes[..2], 5)));
+		assert_eq!(writes.next(), None);
+	}
+
+	#[test]
+	fn empty_writes_cover_no_page() {
+		assert_eq!(page_writes(&[], 0, 4).next(), None);
+		assert_eq!(page_writes(&[], 6, 4).next(), None);
+	}
+
+	#[test]
+	#[should_panic(expected = "Page size must not be zero")]
+	fn page_size_must_not_be_zero() {
+		page_writes(&[0], 0, 0);
+	}
+}
@@ -19,7 +19,7 @@ where
 	I: Iterator<Item = R>,
 {
 	/// Obtain an [`OverlapIterator`] over a subslice of `memory` that overlaps with the region in `self`
-	fn overlaps(self, memory: &'a [u8], base_address: u32) -> OverlapIterator<R, I>;
+	fn overlaps(self, memory: &'a [u8], base_address: u32) -> OverlapIterator<'a, R, I>;
 }
 
 impl<'a, R, I> Iterator for OverlapIterator<'a, R, I>
@@ -32,7 +32,7 @@ where
 	fn next(&mut self) -> Option<Self::Item> {
 		let mem_start = self.base_address;
 		let mem_end = self.base_address + self.memory.len() as u32;
-		while let Some(region) = self.regions.next() {
+		for region in self.regions.by_ref() {
 			if mem_start < region.end() && mem_end >= region.start() {
 				let addr_start = core::cmp::max(mem_start, region.start());
 				let addr_end = core::cmp::min(mem_end, region.end());
@@ -51,7 +51,7 @@ where
 	R: Region,
 	I: Iterator<Item = R>,
 {
-	fn overlaps(self, memory: &'a [u8], base_address: u32) -> OverlapIterator<R, I> {
+	fn overlaps(self, memory: &'a [u8], base_address: u32) -> OverlapIterator<'a, R, I> {
 		OverlapIterator {
 			memory,
 			regions: self,
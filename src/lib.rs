@@ -8,10 +8,26 @@
 #![deny(missing_docs)]
 #![deny(unsafe_code)]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+mod crc;
+
+/// Technology specific traits for block devices
+pub mod block_device;
+/// Technology specific traits for EEPROMs
+pub mod eeprom;
 /// Currently contains [`OverlapIterator`]
 pub mod iter;
+/// Technology specific traits for NAND Flashes
+pub mod nand_flash;
 /// Technology specific traits for NOR Flashes
 pub mod nor_flash;
+/// Technology specific traits for non-volatile RAMs
+pub mod nvram;
+
+use nand_flash::NandFlashErrorKind;
+use nor_flash::{NorFlashError, NorFlashErrorKind};
 
 /// A region denotes a contiguous piece of memory between two addresses.
 pub trait Region {
@@ -27,10 +43,119 @@ pub trait Region {
 	}
 }
 
+/// Storage errors.
+///
+/// Storage implementations must use an error type implementing this trait. This permits generic
+/// code to extract a generic error kind. It is implemented for every [`NorFlashError`], and for
+/// the NAND flash errors of this crate.
+pub trait StorageError: core::fmt::Debug {
+	/// Convert a specific storage error into a generic error kind.
+	fn kind(&self) -> StorageErrorKind;
+}
+
+impl<E: NorFlashError> StorageError for E {
+	fn kind(&self) -> StorageErrorKind {
+		NorFlashError::kind(self).into()
+	}
+}
+
+/// Storage error kinds.
+///
+/// Storage implementations must map their error to those generic error kinds through the
+/// [`StorageError`] trait.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum StorageErrorKind {
+	/// The arguments are not properly aligned.
+	NotAligned,
+
+	/// The arguments are out of bounds.
+	OutOfBounds,
+
+	/// Error specific to the implementation.
+	Other,
+
+	/// The targeted region is write protected.
+	WriteProtected,
+
+	/// Programming failed, or the data read back does not match the data written.
+	ProgramFailed,
+
+	/// Erasing failed, or the region read back is not erased.
+	EraseFailed,
+
+	/// The operation did not complete in time.
+	Timeout,
+
+	/// The data read has more bit errors than the ECC can correct.
+	UncorrectableEcc,
+
+	/// The device is busy with another operation.
+	Busy,
+}
+
+impl StorageError for StorageErrorKind {
+	fn kind(&self) -> StorageErrorKind {
+		*self
+	}
+}
+
+impl From<NorFlashErrorKind> for StorageErrorKind {
+	fn from(kind: NorFlashErrorKind) -> Self {
+		match kind {
+			NorFlashErrorKind::NotAligned => Self::NotAligned,
+			NorFlashErrorKind::OutOfBounds => Self::OutOfBounds,
+			NorFlashErrorKind::Other => Self::Other,
+			NorFlashErrorKind::WriteProtected => Self::WriteProtected,
+			NorFlashErrorKind::ProgramFailed => Self::ProgramFailed,
+			NorFlashErrorKind::EraseFailed => Self::EraseFailed,
+			NorFlashErrorKind::Timeout => Self::Timeout,
+			NorFlashErrorKind::UncorrectableEcc => Self::UncorrectableEcc,
+			NorFlashErrorKind::Busy => Self::Busy,
+		}
+	}
+}
+
+impl From<NandFlashErrorKind> for StorageErrorKind {
+	fn from(kind: NandFlashErrorKind) -> Self {
+		match kind {
+			NandFlashErrorKind::OutOfBounds => Self::OutOfBounds,
+			NandFlashErrorKind::ProgramFailed => Self::ProgramFailed,
+			NandFlashErrorKind::EraseFailed => Self::EraseFailed,
+			NandFlashErrorKind::UncorrectableEcc => Self::UncorrectableEcc,
+			NandFlashErrorKind::WriteProtected => Self::WriteProtected,
+			NandFlashErrorKind::Timeout => Self::Timeout,
+			NandFlashErrorKind::BadBlock | NandFlashErrorKind::Other => Self::Other,
+		}
+	}
+}
+
+impl StorageError for NandFlashErrorKind {
+	fn kind(&self) -> StorageErrorKind {
+		(*self).into()
+	}
+}
+
+impl core::fmt::Display for StorageErrorKind {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::NotAligned => write!(f, "Arguments are not properly aligned"),
+			Self::OutOfBounds => write!(f, "Arguments are out of bounds"),
+			Self::Other => write!(f, "An implementation specific error occurred"),
+			Self::WriteProtected => write!(f, "Region is write protected"),
+			Self::ProgramFailed => write!(f, "Programming failed"),
+			Self::EraseFailed => write!(f, "Erasing failed"),
+			Self::Timeout => write!(f, "Operation timed out"),
+			Self::UncorrectableEcc => write!(f, "Data has uncorrectable ECC errors"),
+			Self::Busy => write!(f, "Device is busy"),
+		}
+	}
+}
+
 /// Transparent read only storage trait
 pub trait ReadStorage {
 	/// An enumeration of storage errors
-	type Error;
+	type Error: StorageError;
 
 	/// Read a slice of data from the storage peripheral, starting the read
 	/// operation at the given address offset, and reading `bytes.len()` bytes.
@@ -0,0 +1,245 @@
+mod bbm;
+mod ftl;
+mod mock;
+
+pub use self::bbm::{BadBlockManager, BadBlockManagerError};
+pub use self::ftl::{Ftl, FtlError};
+pub use self::mock::{MockNandFlash, MockNandFlashError};
+
+/// NAND flash errors.
+///
+/// NAND flash implementations must use an error type implementing this trait. This permits generic
+/// code to extract a generic error kind.
+pub trait NandFlashError: core::fmt::Debug {
+	/// Convert a specific NAND flash error into a generic error kind.
+	fn kind(&self) -> NandFlashErrorKind;
+}
+
+impl NandFlashError for core::convert::Infallible {
+	fn kind(&self) -> NandFlashErrorKind {
+		match *self {}
+	}
+}
+
+/// A trait that NandFlash implementations can use to share an error type.
+pub trait ErrorType {
+	/// Errors returned by this NAND flash.
+	type Error: NandFlashError;
+}
+
+/// NAND flash error kinds.
+///
+/// NAND flash implementations must map their error to those generic error kinds through the
+/// [`NandFlashError`] trait.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum NandFlashErrorKind {
+	/// The arguments are out of bounds.
+	OutOfBounds,
+
+	/// The block is marked as bad.
+	BadBlock,
+
+	/// Programming failed, the block should be considered bad.
+	ProgramFailed,
+
+	/// Erasing failed, the block should be considered bad.
+	EraseFailed,
+
+	/// The data read has more bit errors than the ECC can correct.
+	UncorrectableEcc,
+
+	/// The targeted block is write protected.
+	WriteProtected,
+
+	/// The operation did not complete in time.
+	Timeout,
+
+	/// Error specific to the implementation.
+	Other,
+}
+
+impl NandFlashError for NandFlashErrorKind {
+	fn kind(&self) -> NandFlashErrorKind {
+		*self
+	}
+}
+
+impl core::fmt::Display for NandFlashErrorKind {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::OutOfBounds => write!(f, "Arguments are out of bounds"),
+			Self::BadBlock => write!(f, "Block is marked as bad"),
+			Self::ProgramFailed => write!(f, "Programming failed"),
+			Self::EraseFailed => write!(f, "Erasing failed"),
+			Self::UncorrectableEcc => write!(f, "Data has uncorrectable ECC errors"),
+			Self::WriteProtected => write!(f, "Block is write protected"),
+			Self::Timeout => write!(f, "Operation timed out"),
+			Self::Other => write!(f, "An implementation specific error occurred"),
+		}
+	}
+}
+
+/// Outcome of the error correction of a successful page read.
+///
+/// Reads with uncorrectable errors fail with [`NandFlashErrorKind::UncorrectableEcc`] instead.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum EccStatus {
+	/// No bit errors were found.
+	Clean,
+
+	/// This many bit errors were found and corrected. The block is wearing out, and its data
+	/// should be moved before the errors become uncorrectable.
+	Corrected(u8),
+}
+
+/// Read only NAND flash trait.
+///
+/// NAND flashes are addressed by page index, pages `n * PAGES_PER_BLOCK..(n + 1) * PAGES_PER_BLOCK`
+/// forming block `n`, where `PAGES_PER_BLOCK` is `BLOCK_SIZE / PAGE_SIZE`. Every page has a data
+/// area of `PAGE_SIZE` bytes and a spare (OOB) area of `SPARE_SIZE` bytes.
+pub trait ReadNandFlash: ErrorType {
+	/// The number of data bytes in a page
+	const PAGE_SIZE: usize;
+
+	/// The number of spare bytes in a page
+	const SPARE_SIZE: usize;
+
+	/// The number of data bytes in a block, a multiple of `PAGE_SIZE`
+	const BLOCK_SIZE: usize;
+
+	/// Read the start of the data area of `page` into `data`, and the start of its spare area
+	/// into `spare`. Either slice can be empty.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds, or if the data has uncorrectable
+	/// errors. The implementation can use the [`check_page`] helper function.
+	fn read_page(
+		&mut self,
+		page: u32,
+		data: &mut [u8],
+		spare: &mut [u8],
+	) -> Result<EccStatus, Self::Error>;
+
+	/// Check whether `block` is marked as bad, either by the factory or by
+	/// [`NandFlash::mark_bad_block`].
+	///
+	/// # Errors
+	///
+	/// Returns an error if the block is out of bounds. The implementation can use the
+	/// [`check_block`] helper function.
+	fn is_bad_block(&mut self, block: u32) -> Result<bool, Self::Error>;
+
+	/// The number of blocks of the peripheral, including bad blocks.
+	fn block_count(&self) -> usize;
+
+	/// The capacity of the data areas of the peripheral in bytes, including bad blocks, saturating
+	/// at `usize::MAX`.
+	fn capacity(&self) -> usize {
+		self.block_count().saturating_mul(Self::BLOCK_SIZE)
+	}
+}
+
+/// NAND flash trait.
+pub trait NandFlash: ReadNandFlash {
+	/// Erase `block`, setting all data and spare bytes of its pages to 0xff.
+	///
+	/// If power is lost during erase, contents of the block are undefined.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the block is out of bounds, or if erasing failed. The implementation
+	/// can use the [`check_block`] helper function.
+	fn erase_block(&mut self, block: u32) -> Result<(), Self::Error>;
+
+	/// Program the start of the data area of `page` with `data`, and the start of its spare area
+	/// with `spare`. Bytes not covered by either slice are left erased.
+	///
+	/// Each page can only be programmed once between erases, and pages of a block have to be
+	/// programmed in ascending order. If power is lost during program, the contents of the page
+	/// are undefined.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds, or if programming failed. The
+	/// implementation can use the [`check_page`] helper function.
+	fn program_page(&mut self, page: u32, data: &[u8], spare: &[u8]) -> Result<(), Self::Error>;
+
+	/// Mark `block` as bad, so that [`ReadNandFlash::is_bad_block`] reports it from now on.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the block is out of bounds. The implementation can use the
+	/// [`check_block`] helper function.
+	fn mark_bad_block(&mut self, block: u32) -> Result<(), Self::Error>;
+}
+
+/// Return whether a page read or program is within bounds.
+pub fn check_page<T: ReadNandFlash>(
+	flash: &T,
+	page: u32,
+	data_length: usize,
+	spare_length: usize,
+) -> Result<(), NandFlashErrorKind> {
+	let pages = flash
+		.block_count()
+		.saturating_mul(T::BLOCK_SIZE / T::PAGE_SIZE);
+	if page as usize >= pages || data_length > T::PAGE_SIZE || spare_length > T::SPARE_SIZE {
+		return Err(NandFlashErrorKind::OutOfBounds);
+	}
+	Ok(())
+}
+
+/// Return whether a block operation is within bounds.
+pub fn check_block<T: ReadNandFlash>(flash: &T, block: u32) -> Result<(), NandFlashErrorKind> {
+	if block as usize >= flash.block_count() {
+		return Err(NandFlashErrorKind::OutOfBounds);
+	}
+	Ok(())
+}
+
+impl<T: ErrorType> ErrorType for &mut T {
+	type Error = T::Error;
+}
+
+impl<T: ReadNandFlash> ReadNandFlash for &mut T {
+	const PAGE_SIZE: usize = T::PAGE_SIZE;
+	const SPARE_SIZE: usize = T::SPARE_SIZE;
+	const BLOCK_SIZE: usize = T::BLOCK_SIZE;
+
+	fn read_page(
+		&mut self,
+		page: u32,
+		data: &mut [u8],
+		spare: &mut [u8],
+	) -> Result<EccStatus, Self::Error> {
+		T::read_page(self, page, data, spare)
+	}
+
+	fn is_bad_block(&mut self, block: u32) -> Result<bool, Self::Error> {
+		T::is_bad_block(self, block)
+	}
+
+	fn block_count(&self) -> usize {
+		T::block_count(self)
+	}
+
+	fn capacity(&self) -> usize {
+		T::capacity(self)
+	}
+}
+
+impl<T: NandFlash> NandFlash for &mut T {
+	fn erase_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		T::erase_block(self, block)
+	}
+
+	fn program_page(&mut self, page: u32, data: &[u8], spare: &[u8]) -> Result<(), Self::Error> {
+		T::program_page(self, page, data, spare)
+	}
+
+	fn mark_bad_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		T::mark_bad_block(self, block)
+	}
+}
@@ -0,0 +1,755 @@
+use crate::{
+	crc::Crc32,
+	nand_flash::{
+		check_block, check_page, EccStatus, ErrorType, NandFlash, NandFlashError,
+		NandFlashErrorKind, ReadNandFlash,
+	},
+	StorageError, StorageErrorKind,
+};
+
+/// Marks a page holding the header of a bad block table, "BBT0" in little endian.
+const MAGIC: u32 = 0x3054_4242;
+
+/// Magic, version and checksum.
+const HEADER_SIZE: usize = 12;
+
+/// Number of table entries stored in each page of a table block.
+const ENTRIES_PER_PAGE: usize = 8;
+
+/// Number of blocks holding a copy of the table.
+const TABLE_COPIES: usize = 2;
+
+/// Table entry of a spare block which is not in use.
+const FREE: u32 = u32::MAX;
+
+/// Table entry of a spare block which went bad.
+const BAD: u32 = u32::MAX - 1;
+
+/// Table entry of a spare block holding a copy of the table, in place of a bad table block.
+const TABLE: u32 = u32::MAX - 2;
+
+/// Errors returned by [`BadBlockManager`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum BadBlockManagerError<E> {
+	/// The arguments are out of bounds, or a bad block could not be replaced because all spare
+	/// blocks are used up ([`NandFlashErrorKind::BadBlock`]).
+	Manager(NandFlashErrorKind),
+
+	/// The underlying flash returned an error.
+	Flash(E),
+}
+
+impl<E: NandFlashError> NandFlashError for BadBlockManagerError<E> {
+	fn kind(&self) -> NandFlashErrorKind {
+		match self {
+			Self::Manager(kind) => *kind,
+			Self::Flash(e) => e.kind(),
+		}
+	}
+}
+
+impl<E: NandFlashError> StorageError for BadBlockManagerError<E> {
+	fn kind(&self) -> StorageErrorKind {
+		NandFlashError::kind(self).into()
+	}
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for BadBlockManagerError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::Manager(kind) => kind.fmt(f),
+			Self::Flash(e) => e.fmt(f),
+		}
+	}
+}
+
+/// Bad block management layer, exposing the good blocks of a NAND flash as a NAND flash without
+/// bad blocks.
+///
+/// The last two blocks of the flash each hold a copy of the remap table, and the `SPARES` blocks
+/// before them are set aside to replace bad blocks. All other blocks are exposed, with accesses to
+/// bad ones transparently redirected to their replacement.
+///
+/// When no valid table is found, the factory bad block markers are scanned and bad blocks are
+/// replaced right away. Afterwards:
+///
+/// - A block failing to erase is replaced, and the erase is retried on its replacement.
+/// - A block failing to program is replaced, the pages programmed before the failing one are
+///   copied to the replacement, and the program is retried on it.
+/// - [`NandFlash::mark_bad_block`] replaces a block, for instance once it reports too many
+///   corrected bit errors, and copies its contents to the replacement.
+///
+/// Replaced blocks are marked bad in the underlying flash, and every update of the table is
+/// written to the block holding the older copy, so one of them survives a power loss. A spare
+/// block takes over the copy of a table block which is bad.
+#[derive(Debug)]
+pub struct BadBlockManager<'a, F, const SPARES: usize> {
+	flash: F,
+	buffer: &'a mut [u8],
+	/// Block replaced by each spare block, or [`FREE`], [`BAD`] or [`TABLE`].
+	spares: [u32; SPARES],
+	version: u32,
+	/// Block holding the most recent copy of the table.
+	table: Option<u32>,
+}
+
+impl<'a, F, const SPARES: usize> BadBlockManager<'a, F, SPARES>
+where
+	F: NandFlash,
+{
+	const GEOMETRY: () = assert!(
+		F::PAGE_SIZE >= 4 * ENTRIES_PER_PAGE
+			&& (SPARES + ENTRIES_PER_PAGE - 1) / ENTRIES_PER_PAGE < F::BLOCK_SIZE / F::PAGE_SIZE,
+		"Bad block table does not fit in a block"
+	);
+
+	/// Load the bad block table of `flash`, or create it if there is none. `buffer` is used to
+	/// copy pages to replacement blocks.
+	///
+	/// **NOTE** This will panic if the provided buffer is smaller than the page size plus the
+	/// spare size of the flash
+	///
+	/// # Errors
+	///
+	/// Returns [`NandFlashErrorKind::OutOfBounds`] if `flash` has no blocks left after reserving
+	/// the table and spare blocks, and [`NandFlashErrorKind::BadBlock`] if there are not enough
+	/// good spare blocks to replace the factory bad blocks and the bad table blocks.
+	pub fn new(flash: F, buffer: &'a mut [u8]) -> Result<Self, BadBlockManagerError<F::Error>> {
+		let () = Self::GEOMETRY;
+		if buffer.len() < F::PAGE_SIZE + F::SPARE_SIZE {
+			panic!("Page buffer is too small");
+		}
+
+		if flash.block_count() <= TABLE_COPIES + SPARES {
+			return Err(BadBlockManagerError::Manager(
+				NandFlashErrorKind::OutOfBounds,
+			));
+		}
+
+		let mut manager = Self {
+			flash,
+			buffer,
+			spares: [FREE; SPARES],
+			version: 0,
+			table: None,
+		};
+		if !manager.load()? {
+			manager.format()?;
+		}
+		Ok(manager)
+	}
+
+	/// Number of spare blocks still available to replace bad blocks.
+	pub fn free_spares(&self) -> usize {
+		self.spares.iter().filter(|block| **block == FREE).count()
+	}
+
+	/// Release the underlying flash.
+	pub fn into_inner(self) -> F {
+		self.flash
+	}
+
+	fn pages_per_block() -> u32 {
+		(F::BLOCK_SIZE / F::PAGE_SIZE) as u32
+	}
+
+	fn first_spare(&self) -> u32 {
+		(self.flash.block_count() - TABLE_COPIES - SPARES) as u32
+	}
+
+	fn table_block(&self, copy: u32) -> u32 {
+		(self.flash.block_count() - TABLE_COPIES) as u32 + copy
+	}
+
+	/// The block of the underlying flash currently standing in for `block`.
+	fn physical(&self, block: u32) -> u32 {
+		match self.spares.iter().position(|replaced| *replaced == block) {
+			Some(spare) => self.first_spare() + spare as u32,
+			None => block,
+		}
+	}
+
+	fn flash_is_bad(&mut self, block: u32) -> Result<bool, BadBlockManagerError<F::Error>> {
+		self.flash
+			.is_bad_block(block)
+			.map_err(BadBlockManagerError::Flash)
+	}
+
+	/// Replace the factory bad blocks, and store the resulting table.
+	fn format(&mut self) -> Result<(), BadBlockManagerError<F::Error>> {
+		let first_spare = self.first_spare();
+		for spare in 0..SPARES {
+			if self.flash_is_bad(first_spare + spare as u32)? {
+				self.spares[spare] = BAD;
+			}
+		}
+		for block in 0..first_spare {
+			if self.flash_is_bad(block)? {
+				self.allocate(block)?;
+			}
+		}
+		self.store()
+	}
+
+	fn mark_bad(&mut self, block: u32) -> Result<(), BadBlockManagerError<F::Error>> {
+		self.flash
+			.mark_bad_block(block)
+			.map_err(BadBlockManagerError::Flash)
+	}
+
+	/// Replace the block standing in for `block`, copy its first `pages` pages to the replacement,
+	/// mark it as bad and store the updated table.
+	fn retire(&mut self, block: u32, pages: u32) -> Result<(), BadBlockManagerError<F::Error>> {
+		let failing = self.physical(block);
+		let allocated = loop {
+			if let Some(spare) = self.spares.iter_mut().find(|replaced| **replaced == block) {
+				*spare = BAD;
+			}
+			if let Err(e) = self.allocate(block) {
+				break Err(e);
+			}
+
+			let replacement = self.physical(block);
+			match self.copy_pages(failing, replacement, pages) {
+				Ok(()) => break Ok(()),
+				Err(e) if e.kind() == NandFlashErrorKind::ProgramFailed => {
+					self.mark_bad(replacement)?;
+				}
+				Err(e) => return Err(BadBlockManagerError::Flash(e)),
+			}
+		};
+		self.mark_bad(failing)?;
+
+		// Store the table even when running out of spares, to remember the bad spares
+		self.store()?;
+		allocated
+	}
+
+	/// Copy the first `pages` pages of block `from` to the erased block `to`, skipping erased and
+	/// unreadable pages.
+	fn copy_pages(&mut self, from: u32, to: u32, pages: u32) -> Result<(), F::Error> {
+		let (data, spare) = self.buffer.split_at_mut(F::PAGE_SIZE);
+		let spare = &mut spare[..F::SPARE_SIZE];
+		for page in 0..pages {
+			match self
+				.flash
+				.read_page(from * Self::pages_per_block() + page, data, spare)
+			{
+				Ok(_) => {}
+				// The contents of the page are lost already
+				Err(e) if e.kind() == NandFlashErrorKind::UncorrectableEcc => continue,
+				Err(e) => return Err(e),
+			}
+			// Erased pages are skipped, so that they can still be programmed
+			if data.iter().chain(spare.iter()).all(|byte| *byte == 0xff) {
+				continue;
+			}
+			self.flash
+				.program_page(to * Self::pages_per_block() + page, data, spare)?;
+		}
+		Ok(())
+	}
+
+	/// Assign an erased spare block to `block`, without storing the table.
+	fn allocate(&mut self, block: u32) -> Result<(), BadBlockManagerError<F::Error>> {
+		let first_spare = self.first_spare();
+		while let Some(spare) = self.spares.iter().position(|replaced| *replaced == FREE) {
+			match self.flash.erase_block(first_spare + spare as u32) {
+				Ok(()) => {
+					self.spares[spare] = block;
+					return Ok(());
+				}
+				Err(e) if e.kind() == NandFlashErrorKind::EraseFailed => {
+					self.flash
+						.mark_bad_block(first_spare + spare as u32)
+						.map_err(BadBlockManagerError::Flash)?;
+					self.spares[spare] = BAD;
+				}
+				Err(e) => return Err(BadBlockManagerError::Flash(e)),
+			}
+		}
+		Err(BadBlockManagerError::Manager(NandFlashErrorKind::BadBlock))
+	}
+
+	/// Write the table to the block not holding the most recent copy, replacing table blocks
+	/// which turn out to be bad.
+	fn store(&mut self) -> Result<(), BadBlockManagerError<F::Error>> {
+		let version = self.version + 1;
+		loop {
+			let block = self.next_table_block()?;
+			match self.write_table(block, version) {
+				Ok(()) => {
+					self.version = version;
+					self.table = Some(block);
+					return Ok(());
+				}
+				Err(e)
+					if e.kind() == NandFlashErrorKind::EraseFailed
+						|| e.kind() == NandFlashErrorKind::ProgramFailed =>
+				{
+					self.mark_bad(block)?;
+					let first_spare = self.first_spare();
+					if let Some(spare) = block.checked_sub(first_spare) {
+						if (spare as usize) < SPARES {
+							self.spares[spare as usize] = BAD;
+						}
+					}
+				}
+				Err(e) => return Err(BadBlockManagerError::Flash(e)),
+			}
+		}
+	}
+
+	/// The block the next copy of the table goes to: a good table block or a spare block
+	/// standing in for a bad one, which does not hold the most recent copy.
+	fn next_table_block(&mut self) -> Result<u32, BadBlockManagerError<F::Error>> {
+		for copy in 0..TABLE_COPIES as u32 {
+			let block = self.table_block(copy);
+			if Some(block) != self.table && !self.flash_is_bad(block)? {
+				return Ok(block);
+			}
+		}
+
+		let first_spare = self.first_spare();
+		let table = self.table;
+		let spare =
+			match self.spares.iter().enumerate().position(|(spare, entry)| {
+				*entry == TABLE && Some(first_spare + spare as u32) != table
+			}) {
+				Some(spare) => spare,
+				// The spare is erased when the table is written
+				None => {
+					let spare = self
+						.spares
+						.iter()
+						.position(|entry| *entry == FREE)
+						.ok_or(BadBlockManagerError::Manager(NandFlashErrorKind::BadBlock))?;
+					self.spares[spare] = TABLE;
+					spare
+				}
+			};
+		Ok(first_spare + spare as u32)
+	}
+
+	/// Write the table to `block`, entries first and header last.
+	fn write_table(&mut self, block: u32, version: u32) -> Result<(), F::Error> {
+		self.flash.erase_block(block)?;
+
+		let first_page = block * Self::pages_per_block();
+		let mut crc = Crc32::new();
+		crc.update(&version.to_le_bytes());
+		let chunks = self.spares.chunks(ENTRIES_PER_PAGE);
+		let header_page = first_page + chunks.len() as u32;
+		for (page, entries) in (first_page..).zip(chunks) {
+			let mut data = [0xff; 4 * ENTRIES_PER_PAGE];
+			for (bytes, entry) in data.chunks_mut(4).zip(entries) {
+				bytes.copy_from_slice(&entry.to_le_bytes());
+			}
+			let data = &data[..4 * entries.len()];
+			crc.update(data);
+			self.flash.program_page(page, data, &[])?;
+		}
+
+		let mut header = [0; HEADER_SIZE];
+		header[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+		header[4..8].copy_from_slice(&version.to_le_bytes());
+		header[8..12].copy_from_slice(&crc.finish().to_le_bytes());
+		self.flash.program_page(header_page, &header, &[])
+	}
+
+	/// Load the most recent valid copy of the table, returning whether there is one.
+	fn load(&mut self) -> Result<bool, BadBlockManagerError<F::Error>> {
+		let mut found = false;
+		let mut spares = [FREE; SPARES];
+		let first_spare = self.first_spare();
+		for block in first_spare..self.table_block(TABLE_COPIES as u32) {
+			if self.flash_is_bad(block)? {
+				continue;
+			}
+			if let Some(version) = self.read_table(block, &mut spares)? {
+				// Data in a spare block which happens to look like a table is not a copy
+				let copy =
+					block >= self.table_block(0) || spares[(block - first_spare) as usize] == TABLE;
+				if copy && (!found || version > self.version) {
+					self.spares = spares;
+					self.version = version;
+					self.table = Some(block);
+					found = true;
+				}
+			}
+		}
+		Ok(found)
+	}
+
+	/// Read the copy of the table in `block`, returning its version if it is valid.
+	fn read_table(
+		&mut self,
+		block: u32,
+		spares: &mut [u32; SPARES],
+	) -> Result<Option<u32>, BadBlockManagerError<F::Error>> {
+		let first_page = block * Self::pages_per_block();
+		let chunks = (SPARES + ENTRIES_PER_PAGE - 1) / ENTRIES_PER_PAGE;
+
+		let mut header = [0; HEADER_SIZE];
+		if !self.read_table_page(first_page + chunks as u32, &mut header)? {
+			return Ok(None);
+		}
+		let word =
+			|i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+		let (magic, version, crc) = (word(0), word(4), word(8));
+		if magic != MAGIC {
+			return Ok(None);
+		}
+
+		let mut check = Crc32::new();
+		check.update(&version.to_le_bytes());
+		for (page, entries) in (first_page..).zip(spares.chunks_mut(ENTRIES_PER_PAGE)) {
+			let mut data = [0; 4 * ENTRIES_PER_PAGE];
+			let data = &mut data[..4 * entries.len()];
+			if !self.read_table_page(page, data)? {
+				return Ok(None);
+			}
+			check.update(data);
+			for (entry, bytes) in entries.iter_mut().zip(data.chunks(4)) {
+				*entry = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+			}
+		}
+
+		Ok(if check.finish() == crc {
+			Some(version)
+		} else {
+			None
+		})
+	}
+
+	/// Read a page of the table, returning `false` if it is unreadable.
+	fn read_table_page(
+		&mut self,
+		page: u32,
+		data: &mut [u8],
+	) -> Result<bool, BadBlockManagerError<F::Error>> {
+		match self.flash.read_page(page, data, &mut []) {
+			Ok(_) => Ok(true),
+			Err(e) if e.kind() == NandFlashErrorKind::UncorrectableEcc => Ok(false),
+			Err(e) => Err(BadBlockManagerError::Flash(e)),
+		}
+	}
+
+	/// The page of the underlying flash currently standing in for `page`.
+	fn physical_page(&self, page: u32) -> u32 {
+		let block = page / Self::pages_per_block();
+		self.physical(block) * Self::pages_per_block() + page % Self::pages_per_block()
+	}
+}
+
+impl<'a, F, const SPARES: usize> ErrorType for BadBlockManager<'a, F, SPARES>
+where
+	F: ErrorType,
+{
+	type Error = BadBlockManagerError<F::Error>;
+}
+
+impl<'a, F, const SPARES: usize> ReadNandFlash for BadBlockManager<'a, F, SPARES>
+where
+	F: NandFlash,
+{
+	const PAGE_SIZE: usize = F::PAGE_SIZE;
+	const SPARE_SIZE: usize = F::SPARE_SIZE;
+	const BLOCK_SIZE: usize = F::BLOCK_SIZE;
+
+	fn read_page(
+		&mut self,
+		page: u32,
+		data: &mut [u8],
+		spare: &mut [u8],
+	) -> Result<EccStatus, Self::Error> {
+		check_page(self, page, data.len(), spare.len()).map_err(BadBlockManagerError::Manager)?;
+		self.flash
+			.read_page(self.physical_page(page), data, spare)
+			.map_err(BadBlockManagerError::Flash)
+	}
+
+	fn is_bad_block(&mut self, block: u32) -> Result<bool, Self::Error> {
+		check_block(self, block).map_err(BadBlockManagerError::Manager)?;
+		Ok(false)
+	}
+
+	fn block_count(&self) -> usize {
+		self.first_spare() as usize
+	}
+}
+
+impl<'a, F, const SPARES: usize> NandFlash for BadBlockManager<'a, F, SPARES>
+where
+	F: NandFlash,
+{
+	fn erase_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		check_block(self, block).map_err(BadBlockManagerError::Manager)?;
+		loop {
+			match self.flash.erase_block(self.physical(block)) {
+				Err(e) if e.kind() == NandFlashErrorKind::EraseFailed => self.retire(block, 0)?,
+				result => return result.map_err(BadBlockManagerError::Flash),
+			}
+		}
+	}
+
+	fn program_page(&mut self, page: u32, data: &[u8], spare: &[u8]) -> Result<(), Self::Error> {
+		check_page(self, page, data.len(), spare.len()).map_err(BadBlockManagerError::Manager)?;
+		let (block, index) = (
+			page / Self::pages_per_block(),
+			page % Self::pages_per_block(),
+		);
+		loop {
+			match self
+				.flash
+				.program_page(self.physical_page(page), data, spare)
+			{
+				Err(e) if e.kind() == NandFlashErrorKind::ProgramFailed => {
+					self.retire(block, index)?
+				}
+				result => return result.map_err(BadBlockManagerError::Flash),
+			}
+		}
+	}
+
+	fn mark_bad_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		check_block(self, block).map_err(BadBlockManagerError::Manager)?;
+		self.retire(block, Self::pages_per_block())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::nand_flash::MockNandFlash;
+
+	type Flash = MockNandFlash<32, 8, 4, 16>;
+	type Manager<'a, F> = BadBlockManager<'a, F, 4>;
+
+	/// Fails every erase and program once `budget` of them are used up, like a power loss.
+	struct PowerCut<F> {
+		flash: F,
+		budget: usize,
+	}
+
+	#[derive(Debug)]
+	enum PowerCutError<E> {
+		Flash(E),
+		PowerLoss,
+	}
+
+	impl<E: NandFlashError> NandFlashError for PowerCutError<E> {
+		fn kind(&self) -> NandFlashErrorKind {
+			match self {
+				Self::Flash(e) => e.kind(),
+				Self::PowerLoss => NandFlashErrorKind::Other,
+			}
+		}
+	}
+
+	impl<F: NandFlash> PowerCut<F> {
+		fn spend(&mut self) -> Result<(), PowerCutError<F::Error>> {
+			if self.budget == 0 {
+				return Err(PowerCutError::PowerLoss);
+			}
+			self.budget -= 1;
+			Ok(())
+		}
+	}
+
+	impl<F: NandFlash> ErrorType for PowerCut<F> {
+		type Error = PowerCutError<F::Error>;
+	}
+
+	impl<F: NandFlash> ReadNandFlash for PowerCut<F> {
+		const PAGE_SIZE: usize = F::PAGE_SIZE;
+		const SPARE_SIZE: usize = F::SPARE_SIZE;
+		const BLOCK_SIZE: usize = F::BLOCK_SIZE;
+
+		fn read_page(
+			&mut self,
+			page: u32,
+			data: &mut [u8],
+			spare: &mut [u8],
+		) -> Result<EccStatus, Self::Error> {
+			self.flash
+				.read_page(page, data, spare)
+				.map_err(PowerCutError::Flash)
+		}
+
+		fn is_bad_block(&mut self, block: u32) -> Result<bool, Self::Error> {
+			self.flash.is_bad_block(block).map_err(PowerCutError::Flash)
+		}
+
+		fn block_count(&self) -> usize {
+			self.flash.block_count()
+		}
+	}
+
+	impl<F: NandFlash> NandFlash for PowerCut<F> {
+		fn erase_block(&mut self, block: u32) -> Result<(), Self::Error> {
+			self.spend()?;
+			self.flash.erase_block(block).map_err(PowerCutError::Flash)
+		}
+
+		fn program_page(
+			&mut self,
+			page: u32,
+			data: &[u8],
+			spare: &[u8],
+		) -> Result<(), Self::Error> {
+			self.spend()?;
+			self.flash
+				.program_page(page, data, spare)
+				.map_err(PowerCutError::Flash)
+		}
+
+		fn mark_bad_block(&mut self, block: u32) -> Result<(), Self::Error> {
+			self.flash
+				.mark_bad_block(block)
+				.map_err(PowerCutError::Flash)
+		}
+	}
+
+	fn read<F: NandFlash>(manager: &mut Manager<'_, F>, page: u32) -> [u8; 32] {
+		let mut data = [0; 32];
+		manager.read_page(page, &mut data, &mut []).unwrap();
+		data
+	}
+
+	#[test]
+	fn replaces_factory_bad_blocks() {
+		let mut buffer = [0; 40];
+		let mut flash = Flash::new();
+		flash.set_factory_bad_block(3);
+
+		let mut manager = Manager::new(flash, &mut buffer).unwrap();
+		assert_eq!(manager.block_count(), 10);
+		assert_eq!(manager.free_spares(), 3);
+		assert!(!manager.is_bad_block(3).unwrap());
+		manager.program_page(12, &[1; 32], &[]).unwrap();
+
+		let mut manager = Manager::new(manager.into_inner(), &mut buffer).unwrap();
+		assert_eq!(manager.free_spares(), 3);
+		assert_eq!(read(&mut manager, 12), [1; 32]);
+	}
+
+	#[test]
+	fn retries_failed_erases() {
+		let mut buffer = [0; 40];
+		let mut flash = Flash::new();
+		flash.fail_erase(2);
+
+		let mut manager = Manager::new(flash, &mut buffer).unwrap();
+		manager.erase_block(2).unwrap();
+		assert_eq!(manager.free_spares(), 3);
+		manager.program_page(8, &[2; 32], &[]).unwrap();
+		assert_eq!(read(&mut manager, 8), [2; 32]);
+	}
+
+	#[test]
+	fn failed_programs_keep_the_pages_before() {
+		let mut buffer = [0; 40];
+		let mut manager = Manager::new(Flash::new(), &mut buffer).unwrap();
+		manager.program_page(4, &[1; 32], &[1; 8]).unwrap();
+		manager.program_page(5, &[2; 32], &[2; 8]).unwrap();
+
+		let mut flash = manager.into_inner();
+		flash.fail_program(1);
+		let mut manager = Manager::new(flash, &mut buffer).unwrap();
+		manager.program_page(6, &[3; 32], &[]).unwrap();
+		assert_eq!(manager.free_spares(), 3);
+
+		let mut manager = Manager::new(manager.into_inner(), &mut buffer).unwrap();
+		let mut spare = [0; 8];
+		manager.read_page(5, &mut [], &mut spare).unwrap();
+		assert_eq!(spare, [2; 8]);
+		assert_eq!(read(&mut manager, 4), [1; 32]);
+		assert_eq!(read(&mut manager, 5), [2; 32]);
+		assert_eq!(read(&mut manager, 6), [3; 32]);
+		manager.program_page(7, &[4; 32], &[]).unwrap();
+	}
+
+	#[test]
+	fn marking_a_block_bad_moves_its_contents() {
+		let mut buffer = [0; 40];
+		let mut manager = Manager::new(Flash::new(), &mut buffer).unwrap();
+		manager.program_page(0, &[1; 32], &[]).unwrap();
+		manager.program_page(2, &[2; 32], &[]).unwrap();
+
+		manager.mark_bad_block(0).unwrap();
+		assert_eq!(manager.free_spares(), 3);
+		assert!(manager.into_inner().is_bad_block(0).unwrap());
+
+		let mut manager = Manager::new(Flash::new(), &mut buffer).unwrap();
+		manager.program_page(0, &[1; 32], &[]).unwrap();
+		manager.mark_bad_block(0).unwrap();
+		assert_eq!(read(&mut manager, 0), [1; 32]);
+		assert_eq!(read(&mut manager, 1), [0xff; 32]);
+		manager.program_page(1, &[3; 32], &[]).unwrap();
+		assert_eq!(read(&mut manager, 1), [3; 32]);
+	}
+
+	#[test]
+	fn spare_blocks_stand_in_for_bad_table_blocks() {
+		let mut buffer = [0; 40];
+		let mut flash = Flash::new();
+		flash.set_factory_bad_block(14);
+		flash.set_factory_bad_block(15);
+
+		let mut manager = Manager::new(flash, &mut buffer).unwrap();
+		assert_eq!(manager.free_spares(), 3);
+		manager.mark_bad_block(5).unwrap();
+		assert_eq!(manager.free_spares(), 1);
+		manager.program_page(20, &[5; 32], &[]).unwrap();
+
+		let mut manager = Manager::new(manager.into_inner(), &mut buffer).unwrap();
+		assert_eq!(manager.free_spares(), 1);
+		assert_eq!(read(&mut manager, 20), [5; 32]);
+	}
+
+	#[test]
+	fn power_loss_while_storing_keeps_a_table() {
+		let mut buffer = [0; 40];
+		let mut flash = Flash::new();
+		flash.set_factory_bad_block(15);
+		let mut manager = Manager::new(flash, &mut buffer).unwrap();
+		manager.program_page(0, &[1; 32], &[]).unwrap();
+		let flash = manager.into_inner();
+
+		for budget in 0..16 {
+			let cut = PowerCut {
+				flash: flash.clone(),
+				budget,
+			};
+			let mut manager = Manager::new(cut, &mut buffer).unwrap();
+			let _ = manager.mark_bad_block(0);
+
+			let mut manager = Manager::new(manager.into_inner().flash, &mut buffer).unwrap();
+			assert_eq!(
+				read(&mut manager, 0),
+				[1; 32],
+				"power loss after {}",
+				budget
+			);
+		}
+	}
+
+	#[test]
+	fn errors_have_storage_error_kinds() {
+		use crate::nand_flash::MockNandFlashError;
+
+		let manager =
+			BadBlockManagerError::<MockNandFlashError>::Manager(NandFlashErrorKind::BadBlock);
+		assert_eq!(StorageError::kind(&manager), StorageErrorKind::Other);
+		let flash = BadBlockManagerError::Flash(MockNandFlashError::UncorrectableEcc);
+		assert_eq!(
+			StorageError::kind(&flash),
+			StorageErrorKind::UncorrectableEcc
+		);
+		assert_eq!(
+			StorageError::kind(&NandFlashErrorKind::Timeout),
+			StorageErrorKind::Timeout
+		);
+	}
+}
@@ -0,0 +1,804 @@
+use crate::{
+	crc::Crc32,
+	nand_flash::{NandFlash, NandFlashError, NandFlashErrorKind},
+	ReadStorage, Storage, StorageError, StorageErrorKind,
+};
+
+/// Spare bytes used by the metadata of a page: the bad block marker, sector, sequence number,
+/// erase count of the block and checksum.
+const METADATA_SIZE: usize = 17;
+
+/// Map entry of a sector which was never written, and sector of an erased page.
+const UNMAPPED: u32 = u32::MAX;
+
+/// Sector of the header page starting every block, which records its erase count.
+const HEADER: u32 = u32::MAX - 1;
+
+/// Errors returned by [`Ftl`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum FtlError<E> {
+	/// The arguments are out of bounds.
+	OutOfBounds,
+
+	/// There are not enough good blocks left to hold all sectors.
+	NoSpace,
+
+	/// The underlying flash returned an error.
+	Flash(E),
+}
+
+impl<E: NandFlashError> StorageError for FtlError<E> {
+	fn kind(&self) -> StorageErrorKind {
+		match self {
+			Self::OutOfBounds => StorageErrorKind::OutOfBounds,
+			Self::NoSpace => StorageErrorKind::Other,
+			Self::Flash(e) => e.kind().into(),
+		}
+	}
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for FtlError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::OutOfBounds => write!(f, "Arguments are out of bounds"),
+			Self::NoSpace => write!(f, "Not enough good blocks left"),
+			Self::Flash(e) => e.fmt(f),
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct BlockInfo {
+	erases: u32,
+	valid: u32,
+	bad: bool,
+	/// The block failed to program, and is marked bad once its sectors are moved away.
+	failed: bool,
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+	u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Checksum of the page data and the metadata following the bad block marker.
+fn checksum(data: &[u8], metadata: &[u8]) -> u32 {
+	let mut crc = Crc32::new();
+	crc.update(data);
+	crc.update(&metadata[1..METADATA_SIZE - 4]);
+	crc.finish()
+}
+
+/// The metadata of a valid page, as found while mounting.
+#[derive(Debug, Copy, Clone)]
+struct FtlPage {
+	/// The sector held by the page.
+	sector: u32,
+	/// The sequence number of the page.
+	sequence: u32,
+	/// The erase count of the block of the page.
+	erases: u32,
+}
+
+/// The bookkeeping of an [`Ftl`], doing no I/O.
+#[derive(Debug)]
+struct FtlCore<const SECTORS: usize, const BLOCKS: usize> {
+	map: [u32; SECTORS],
+	blocks: [BlockInfo; BLOCKS],
+	block_count: u32,
+	pages_per_block: u32,
+	active: Option<(u32, u32)>,
+	sequence: u32,
+}
+
+impl<const SECTORS: usize, const BLOCKS: usize> FtlCore<SECTORS, BLOCKS> {
+	/// Create the bookkeeping of a flash with `block_count` blocks of `pages_per_block` pages.
+	///
+	/// **NOTE** This will panic if the flash has more than `BLOCKS` blocks
+	fn new(block_count: usize, pages_per_block: usize) -> Self {
+		if block_count > BLOCKS {
+			panic!("Flash has too many blocks");
+		}
+
+		Self {
+			map: [UNMAPPED; SECTORS],
+			blocks: [BlockInfo::default(); BLOCKS],
+			block_count: block_count as u32,
+			pages_per_block: pages_per_block as u32,
+			active: None,
+			sequence: 0,
+		}
+	}
+
+	/// The number of pages in a block.
+	fn pages_per_block(&self) -> u32 {
+		self.pages_per_block
+	}
+
+	/// Return whether an access is within the `capacity` of the FTL.
+	fn check<E>(capacity: usize, offset: u32, length: usize) -> Result<(), FtlError<E>> {
+		if length > capacity || offset as usize > capacity - length {
+			return Err(FtlError::OutOfBounds);
+		}
+		Ok(())
+	}
+
+	/// The page holding `sector`, if it was written.
+	fn mapped(&self, sector: u32) -> Option<u32> {
+		match self.map.get(sector as usize) {
+			Some(page) if *page != UNMAPPED => Some(*page),
+			_ => None,
+		}
+	}
+
+	/// The sequence number in the `metadata` of a page.
+	fn sequence(metadata: &[u8]) -> u32 {
+		read_u32(&metadata[5..9])
+	}
+
+	/// Record that `block` is bad.
+	fn mark_bad(&mut self, block: u32) {
+		let info = &mut self.blocks[block as usize];
+		info.bad = true;
+		info.failed = false;
+	}
+
+	/// Decode the metadata of a page read while mounting, returning `None` if the page is erased
+	/// or corrupted.
+	fn decode(data: &[u8], metadata: &[u8]) -> Option<FtlPage> {
+		let sector = read_u32(&metadata[1..5]);
+		if sector == UNMAPPED {
+			return None;
+		}
+		// Header pages have no data
+		let data = if sector == HEADER { &[][..] } else { data };
+		if read_u32(&metadata[13..17]) != checksum(data, metadata) {
+			return None;
+		}
+		Some(FtlPage {
+			sector,
+			sequence: Self::sequence(metadata),
+			erases: read_u32(&metadata[9..13]),
+		})
+	}
+
+	/// Record a valid page found while mounting. `newer` tells whether it is more recent than
+	/// the page currently holding its sector.
+	fn mount_page(&mut self, page: u32, metadata: &FtlPage, newer: bool) {
+		let info = &mut self.blocks[(page / self.pages_per_block) as usize];
+		info.erases = info.erases.max(metadata.erases);
+		self.sequence = self.sequence.max(metadata.sequence.wrapping_add(1));
+		if (metadata.sector as usize) < SECTORS && newer {
+			self.map[metadata.sector as usize] = page;
+		}
+	}
+
+	/// Count the valid pages of every block once all pages are mounted.
+	///
+	/// # Errors
+	///
+	/// Returns [`FtlError::NoSpace`] if the good blocks cannot hold `SECTORS` sectors, with two
+	/// blocks to spare for garbage collection.
+	fn finish_mount<E>(&mut self) -> Result<(), FtlError<E>> {
+		for page in self.map.iter().filter(|page| **page != UNMAPPED) {
+			self.blocks[(page / self.pages_per_block) as usize].valid += 1;
+		}
+
+		let good = self.blocks[..self.block_count as usize]
+			.iter()
+			.filter(|block| !block.bad)
+			.count();
+		let data_pages = self.pages_per_block as usize - 1;
+		if good < (SECTORS + data_pages - 1) / data_pages + 2 {
+			return Err(FtlError::NoSpace);
+		}
+		Ok(())
+	}
+
+	fn is_free(&self, block: u32) -> bool {
+		let info = &self.blocks[block as usize];
+		!info.bad
+			&& !info.failed
+			&& info.valid == 0
+			&& self.active.map(|(active, _)| active) != Some(block)
+	}
+
+	/// Whether the last free block has to be kept for garbage collection, rather than opened.
+	fn must_collect(&self) -> bool {
+		(0..self.block_count)
+			.filter(|block| self.is_free(*block))
+			.count() <= 1
+	}
+
+	/// The free block with the lowest erase count.
+	fn free_block(&self) -> Option<u32> {
+		(0..self.block_count)
+			.filter(|block| self.is_free(*block))
+			.min_by_key(|block| self.blocks[*block as usize].erases)
+	}
+
+	/// Record that `block` was erased, returning the metadata of its header page.
+	fn erased(&mut self, block: u32) -> [u8; METADATA_SIZE] {
+		self.blocks[block as usize].erases += 1;
+		self.encode(HEADER, block, &[])
+	}
+
+	/// Make `block`, whose header page is programmed, the active block.
+	fn open(&mut self, block: u32) {
+		self.active = Some((block, 1));
+	}
+
+	/// Stop programming the active block.
+	fn close(&mut self) {
+		self.active = None;
+	}
+
+	/// Take the next page of the active block, if it has one left.
+	fn next_page(&mut self) -> Option<u32> {
+		let (block, next) = self.active?;
+		if next < self.pages_per_block {
+			self.active = Some((block, next + 1));
+			Some(block * self.pages_per_block + next)
+		} else {
+			self.active = None;
+			None
+		}
+	}
+
+	/// The block with the fewest valid sectors, if moving them frees up any page.
+	fn victim(&self) -> Option<u32> {
+		(0..self.block_count)
+			.filter(|block| {
+				let info = &self.blocks[*block as usize];
+				!info.bad && !info.failed && !self.is_free(*block)
+			})
+			.min_by_key(|block| self.blocks[*block as usize].valid)
+			.filter(|block| self.blocks[*block as usize].valid < self.pages_per_block - 1)
+	}
+
+	/// The first sector from `sector` on held by `block`, and its page.
+	fn next_sector_in(&self, block: u32, sector: u32) -> Option<(u32, u32)> {
+		(sector..SECTORS as u32)
+			.map(|sector| (sector, self.map[sector as usize]))
+			.find(|(_, page)| *page != UNMAPPED && page / self.pages_per_block == block)
+	}
+
+	/// The metadata to program along with `data` as the new contents of `sector` into `page`.
+	fn metadata(&self, sector: u32, page: u32, data: &[u8]) -> [u8; METADATA_SIZE] {
+		self.encode(sector, page / self.pages_per_block, data)
+	}
+
+	fn encode(&self, sector: u32, block: u32, data: &[u8]) -> [u8; METADATA_SIZE] {
+		// The first spare byte is left erased, as it usually holds the bad block marker
+		let mut metadata = [0xff; METADATA_SIZE];
+		metadata[1..5].copy_from_slice(&sector.to_le_bytes());
+		metadata[5..9].copy_from_slice(&self.sequence.to_le_bytes());
+		metadata[9..13].copy_from_slice(&self.blocks[block as usize].erases.to_le_bytes());
+		let crc = checksum(data, &metadata);
+		metadata[13..17].copy_from_slice(&crc.to_le_bytes());
+		metadata
+	}
+
+	/// Record that `page` now holds `sector`.
+	fn programmed(&mut self, sector: u32, page: u32) {
+		self.sequence = self.sequence.wrapping_add(1);
+
+		let old = self.map[sector as usize];
+		if old != UNMAPPED {
+			self.blocks[(old / self.pages_per_block) as usize].valid -= 1;
+		}
+		self.map[sector as usize] = page;
+		self.blocks[(page / self.pages_per_block) as usize].valid += 1;
+	}
+
+	/// Record that programming `page` failed. Its block is not programmed any more, and is
+	/// marked bad once its sectors are moved away.
+	fn program_failed(&mut self, page: u32) {
+		self.active = None;
+		self.blocks[(page / self.pages_per_block) as usize].failed = true;
+	}
+
+	/// A sector still held by a block which failed to program.
+	fn relocation(&self) -> Option<u32> {
+		if !self.blocks.iter().any(|info| info.failed) {
+			return None;
+		}
+		(0..SECTORS as u32).find(|sector| {
+			let page = self.map[*sector as usize];
+			page != UNMAPPED && self.blocks[(page / self.pages_per_block) as usize].failed
+		})
+	}
+
+	/// A block which failed to program and holds no sectors any more, to be marked bad.
+	fn retired(&self) -> Option<u32> {
+		(0..self.block_count).find(|block| {
+			let info = &self.blocks[*block as usize];
+			info.failed && info.valid == 0
+		})
+	}
+}
+
+/// Flash translation layer, exposing a NAND flash as a [`Storage`] of `SECTORS` sectors of
+/// [`ReadNandFlash::PAGE_SIZE`](super::ReadNandFlash::PAGE_SIZE) bytes.
+///
+/// Sectors are never overwritten in place: every write programs the next free page, and records
+/// the sector, a sequence number, the erase count of the block and a CRC in the spare area of
+/// the page. The map from sectors to pages is rebuilt from those on creation, so an interrupted
+/// write leaves the sector with either its old or its new contents. Sectors which were never
+/// written read as `0xff`. The first page of every block is a header recording its erase count,
+/// so that it is known even while the block holds no sector.
+///
+/// Once only one free block is left, garbage collection moves the sectors of the block with the
+/// fewest valid pages away, and erases it. Free blocks are handed out by lowest erase count to
+/// spread wear. Blocks failing to erase are marked bad and skipped. When a program fails, the
+/// write is retried on another block, and the sectors of the failing block are moved away
+/// before it is marked bad. The FTL is meant to run on top of a
+/// [`BadBlockManager`](super::BadBlockManager) or a flash doing its own bad block management.
+///
+/// `BLOCKS` is the maximum number of blocks of the flash, as the FTL keeps track of the valid
+/// pages and erase count of each.
+pub struct Ftl<'a, F, const SECTORS: usize, const BLOCKS: usize> {
+	flash: F,
+	buffer: &'a mut [u8],
+	core: FtlCore<SECTORS, BLOCKS>,
+}
+
+impl<'a, F, const SECTORS: usize, const BLOCKS: usize> Ftl<'a, F, SECTORS, BLOCKS>
+where
+	F: NandFlash,
+{
+	const GEOMETRY: () = assert!(
+		F::SPARE_SIZE >= METADATA_SIZE && F::BLOCK_SIZE / F::PAGE_SIZE >= 2,
+		"Spare area is too small for the FTL metadata, or blocks have a single page"
+	);
+
+	/// Mount the FTL on `flash`, rebuilding the sector map from the spare areas of all pages.
+	///
+	/// **NOTE** This will panic if the provided buffer is smaller than the page size of the
+	/// flash, or if the flash has more than `BLOCKS` blocks
+	///
+	/// # Errors
+	///
+	/// Returns [`FtlError::NoSpace`] if the good blocks of the flash cannot hold `SECTORS`
+	/// sectors, with two blocks to spare for garbage collection.
+	pub fn new(flash: F, buffer: &'a mut [u8]) -> Result<Self, FtlError<F::Error>> {
+		let () = Self::GEOMETRY;
+		if buffer.len() < F::PAGE_SIZE {
+			panic!("Page buffer is too small");
+		}
+
+		let core = FtlCore::new(flash.block_count(), F::BLOCK_SIZE / F::PAGE_SIZE);
+		let mut ftl = Self {
+			flash,
+			buffer,
+			core,
+		};
+		ftl.mount()?;
+		Ok(ftl)
+	}
+
+	/// Release the underlying flash.
+	pub fn into_inner(self) -> F {
+		self.flash
+	}
+
+	fn mount(&mut self) -> Result<(), FtlError<F::Error>> {
+		let pages_per_block = self.core.pages_per_block();
+		for block in 0..self.flash.block_count() as u32 {
+			if self.flash.is_bad_block(block).map_err(FtlError::Flash)? {
+				self.core.mark_bad(block);
+				continue;
+			}
+
+			let first_page = block * pages_per_block;
+			for page in first_page..first_page + pages_per_block {
+				let mut metadata = [0; METADATA_SIZE];
+				let data = &mut self.buffer[..F::PAGE_SIZE];
+				match self.flash.read_page(page, data, &mut metadata) {
+					Ok(_) => {}
+					// Most likely a program interrupted by a power loss
+					Err(e) if e.kind() == NandFlashErrorKind::UncorrectableEcc => continue,
+					Err(e) => return Err(FtlError::Flash(e)),
+				}
+
+				if let Some(metadata) = FtlCore::<SECTORS, BLOCKS>::decode(data, &metadata) {
+					let newer = match self.core.mapped(metadata.sector) {
+						Some(current) => self.sequence_of(current)? < metadata.sequence,
+						None => true,
+					};
+					self.core.mount_page(page, &metadata, newer);
+				}
+			}
+		}
+		self.core.finish_mount()
+	}
+
+	fn sequence_of(&mut self, page: u32) -> Result<u32, FtlError<F::Error>> {
+		let mut metadata = [0; METADATA_SIZE];
+		self.flash
+			.read_page(page, &mut [], &mut metadata)
+			.map_err(FtlError::Flash)?;
+		Ok(FtlCore::<SECTORS, BLOCKS>::sequence(&metadata))
+	}
+
+	/// Erase the free block with the lowest erase count, and make it the active block.
+	fn open_block(&mut self) -> Result<(), FtlError<F::Error>> {
+		loop {
+			let block = self.core.free_block().ok_or(FtlError::NoSpace)?;
+			let result = match self.flash.erase_block(block) {
+				Ok(()) => {
+					let metadata = self.core.erased(block);
+					let header = block * self.core.pages_per_block();
+					self.flash.program_page(header, &[], &metadata)
+				}
+				Err(e) => Err(e),
+			};
+
+			match result {
+				Ok(()) => {
+					self.core.open(block);
+					return Ok(());
+				}
+				Err(e)
+					if e.kind() == NandFlashErrorKind::EraseFailed
+						|| e.kind() == NandFlashErrorKind::ProgramFailed =>
+				{
+					self.flash.mark_bad_block(block).map_err(FtlError::Flash)?;
+					self.core.mark_bad(block);
+				}
+				Err(e) => return Err(FtlError::Flash(e)),
+			}
+		}
+	}
+
+	/// Take the next page of the active block, opening a new one if needed.
+	fn allocate_page(&mut self) -> Result<u32, FtlError<F::Error>> {
+		loop {
+			if let Some(page) = self.core.next_page() {
+				return Ok(page);
+			}
+
+			// The last free block is kept for garbage collection
+			if self.core.must_collect() {
+				self.collect()?;
+			} else {
+				self.open_block()?;
+			}
+		}
+	}
+
+	/// Move the valid sectors of the block with the fewest of them to a free block.
+	fn collect(&mut self) -> Result<(), FtlError<F::Error>> {
+		let victim = self.core.victim().ok_or(FtlError::NoSpace)?;
+
+		self.open_block()?;
+		let mut next = 0;
+		while let Some((sector, page)) = self.core.next_sector_in(victim, next) {
+			next = sector + 1;
+			self.flash
+				.read_page(page, &mut self.buffer[..F::PAGE_SIZE], &mut [])
+				.map_err(FtlError::Flash)?;
+			// The victim has fewer valid pages than fit in the fresh block
+			let target = self.core.next_page().ok_or(FtlError::NoSpace)?;
+			if !self.program(sector, target)? {
+				// The sectors left are moved once another block is opened
+				break;
+			}
+		}
+		Ok(())
+	}
+
+	/// Program the buffer to `page` as the new contents of `sector`, returning `false` if
+	/// programming failed and the write has to be retried on another page.
+	fn program(&mut self, sector: u32, page: u32) -> Result<bool, FtlError<F::Error>> {
+		let data = &self.buffer[..F::PAGE_SIZE];
+		let metadata = self.core.metadata(sector, page, data);
+		match self.flash.program_page(page, data, &metadata) {
+			Ok(()) => {
+				self.core.programmed(sector, page);
+				Ok(true)
+			}
+			Err(e) if e.kind() == NandFlashErrorKind::ProgramFailed => {
+				self.core.program_failed(page);
+				Ok(false)
+			}
+			Err(e) => {
+				// Do not program the rest of a failing block
+				self.core.close();
+				Err(FtlError::Flash(e))
+			}
+		}
+	}
+
+	/// Move the sectors of the blocks which failed to program away, and mark those bad.
+	fn relocate(&mut self) -> Result<(), FtlError<F::Error>> {
+		while let Some(sector) = self.core.relocation() {
+			// Allocate first, garbage collection uses the buffer
+			let page = self.allocate_page()?;
+			if let Some(old) = self.core.mapped(sector) {
+				self.flash
+					.read_page(old, &mut self.buffer[..F::PAGE_SIZE], &mut [])
+					.map_err(FtlError::Flash)?;
+				self.program(sector, page)?;
+			}
+		}
+
+		while let Some(block) = self.core.retired() {
+			self.flash.mark_bad_block(block).map_err(FtlError::Flash)?;
+			self.core.mark_bad(block);
+		}
+		Ok(())
+	}
+
+	/// Write `data` at `offset` into `sector`.
+	fn write_sector(
+		&mut self,
+		sector: u32,
+		offset: usize,
+		data: &[u8],
+	) -> Result<(), FtlError<F::Error>> {
+		loop {
+			// Allocate first, garbage collection may move the sector and uses the buffer
+			let page = self.allocate_page()?;
+
+			let buffer = &mut self.buffer[..F::PAGE_SIZE];
+			if data.len() < F::PAGE_SIZE {
+				match self.core.mapped(sector) {
+					None => buffer.fill(0xff),
+					Some(old) => {
+						self.flash
+							.read_page(old, buffer, &mut [])
+							.map_err(FtlError::Flash)?;
+					}
+				}
+			}
+			buffer[offset..offset + data.len()].copy_from_slice(data);
+
+			let written = self.program(sector, page)?;
+			self.relocate()?;
+			if written {
+				return Ok(());
+			}
+		}
+	}
+}
+
+impl<'a, F, const SECTORS: usize, const BLOCKS: usize> ReadStorage for Ftl<'a, F, SECTORS, BLOCKS>
+where
+	F: NandFlash,
+{
+	type Error = FtlError<F::Error>;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		FtlCore::<SECTORS, BLOCKS>::check(self.capacity(), offset, bytes.len())?;
+
+		let mut offset = offset as usize;
+		let mut bytes = bytes;
+		while !bytes.is_empty() {
+			let (sector, start) = (offset / F::PAGE_SIZE, offset % F::PAGE_SIZE);
+			let len = bytes.len().min(F::PAGE_SIZE - start);
+			let (chunk, rest) = core::mem::take(&mut bytes).split_at_mut(len);
+
+			match self.core.mapped(sector as u32) {
+				None => chunk.fill(0xff),
+				Some(page) if len == F::PAGE_SIZE => {
+					self.flash
+						.read_page(page, chunk, &mut [])
+						.map_err(FtlError::Flash)?;
+				}
+				Some(page) => {
+					let buffer = &mut self.buffer[..F::PAGE_SIZE];
+					self.flash
+						.read_page(page, buffer, &mut [])
+						.map_err(FtlError::Flash)?;
+					chunk.copy_from_slice(&buffer[start..start + len]);
+				}
+			}
+
+			offset += len;
+			bytes = rest;
+		}
+		Ok(())
+	}
+
+	fn capacity(&self) -> usize {
+		SECTORS * F::PAGE_SIZE
+	}
+}
+
+impl<'a, F, const SECTORS: usize, const BLOCKS: usize> Storage for Ftl<'a, F, SECTORS, BLOCKS>
+where
+	F: NandFlash,
+{
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		FtlCore::<SECTORS, BLOCKS>::check(self.capacity(), offset, bytes.len())?;
+
+		let mut offset = offset as usize;
+		let mut bytes = bytes;
+		while !bytes.is_empty() {
+			let (sector, start) = (offset / F::PAGE_SIZE, offset % F::PAGE_SIZE);
+			let len = bytes.len().min(F::PAGE_SIZE - start);
+			let (chunk, rest) = bytes.split_at(len);
+			self.write_sector(sector as u32, start, chunk)?;
+
+			offset += len;
+			bytes = rest;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::nand_flash::{
+		BadBlockManager, EccStatus, ErrorType, MockNandFlash, MockNandFlashError, ReadNandFlash,
+	};
+
+	type Flash = MockNandFlash<32, 24, 4, 8>;
+
+	/// Counts the erases of every block, and lets the block of the `fail_at`th program fail.
+	#[derive(Default)]
+	struct Probe {
+		flash: Flash,
+		erases: [u32; 8],
+		fail_at: Option<usize>,
+	}
+
+	impl ErrorType for Probe {
+		type Error = MockNandFlashError;
+	}
+
+	impl ReadNandFlash for Probe {
+		const PAGE_SIZE: usize = 32;
+		const SPARE_SIZE: usize = 24;
+		const BLOCK_SIZE: usize = 128;
+
+		fn read_page(
+			&mut self,
+			page: u32,
+			data: &mut [u8],
+			spare: &mut [u8],
+		) -> Result<EccStatus, Self::Error> {
+			self.flash.read_page(page, data, spare)
+		}
+
+		fn is_bad_block(&mut self, block: u32) -> Result<bool, Self::Error> {
+			self.flash.is_bad_block(block)
+		}
+
+		fn block_count(&self) -> usize {
+			self.flash.block_count()
+		}
+	}
+
+	impl NandFlash for Probe {
+		fn erase_block(&mut self, block: u32) -> Result<(), Self::Error> {
+			self.erases[block as usize] += 1;
+			self.flash.erase_block(block)
+		}
+
+		fn program_page(
+			&mut self,
+			page: u32,
+			data: &[u8],
+			spare: &[u8],
+		) -> Result<(), Self::Error> {
+			match self.fail_at {
+				Some(0) => {
+					self.flash.fail_program(page / 4);
+					self.fail_at = None;
+				}
+				Some(ref mut count) => *count -= 1,
+				None => {}
+			}
+			self.flash.program_page(page, data, spare)
+		}
+
+		fn mark_bad_block(&mut self, block: u32) -> Result<(), Self::Error> {
+			self.flash.mark_bad_block(block)
+		}
+	}
+
+	fn read<F: NandFlash, const BLOCKS: usize>(
+		ftl: &mut Ftl<'_, F, 8, BLOCKS>,
+		sector: u32,
+	) -> [u8; 32] {
+		let mut data = [0; 32];
+		ftl.read(sector * 32, &mut data).unwrap();
+		data
+	}
+
+	#[test]
+	fn keeps_sectors_across_remounts() {
+		let mut buffer = [0; 32];
+		let mut ftl = Ftl::<_, 8, 8>::new(Flash::new(), &mut buffer).unwrap();
+		assert_eq!(read(&mut ftl, 3), [0xff; 32]);
+		for round in 0..8 {
+			for sector in 0..8 {
+				ftl.write(sector * 32, &[round * 8 + sector as u8; 32])
+					.unwrap();
+			}
+		}
+		ftl.write(3 * 32 + 4, &[0xaa; 4]).unwrap();
+
+		let mut ftl = Ftl::<_, 8, 8>::new(ftl.into_inner(), &mut buffer).unwrap();
+		for sector in 0..8 {
+			let mut expected = [56 + sector as u8; 32];
+			if sector == 3 {
+				expected[4..8].fill(0xaa);
+			}
+			assert_eq!(read(&mut ftl, sector), expected);
+		}
+		assert_eq!(ftl.write(8 * 32, &[0]), Err(FtlError::OutOfBounds));
+	}
+
+	#[test]
+	fn program_failures_move_sectors_away() {
+		let mut buffer = [0; 32];
+		// The header of block 0 and two sectors are programmed before the failure
+		let probe = Probe {
+			fail_at: Some(3),
+			..Probe::default()
+		};
+		let mut ftl = Ftl::<_, 8, 8>::new(probe, &mut buffer).unwrap();
+		for sector in 0..3 {
+			ftl.write(sector * 32, &[sector as u8 + 1; 32]).unwrap();
+		}
+		for sector in 0..3 {
+			assert_eq!(read(&mut ftl, sector), [sector as u8 + 1; 32]);
+		}
+
+		let mut probe = ftl.into_inner();
+		assert!(probe.is_bad_block(0).unwrap());
+		let mut ftl = Ftl::<_, 8, 8>::new(probe, &mut buffer).unwrap();
+		for sector in 0..3 {
+			assert_eq!(read(&mut ftl, sector), [sector as u8 + 1; 32]);
+		}
+		ftl.write(3 * 32, &[4; 32]).unwrap();
+		assert_eq!(read(&mut ftl, 3), [4; 32]);
+	}
+
+	#[test]
+	fn remounts_keep_the_erase_counts_of_empty_blocks() {
+		let mut buffer = [0; 32];
+		let mut probe = Probe::default();
+		for round in 0..64 {
+			let mut ftl = Ftl::<_, 8, 8>::new(probe, &mut buffer).unwrap();
+			ftl.write(0, &[round; 32]).unwrap();
+			assert_eq!(read(&mut ftl, 0), [round; 32]);
+			probe = ftl.into_inner();
+		}
+
+		for block in 0..8 {
+			let mut metadata = [0; METADATA_SIZE];
+			probe.read_page(block * 4, &mut [], &mut metadata).unwrap();
+			let erases = probe.erases[block as usize];
+			assert!(erases > 0);
+			assert_eq!(read_u32(&metadata[9..13]), erases);
+		}
+		let most = probe.erases.iter().max().unwrap();
+		let least = probe.erases.iter().min().unwrap();
+		assert!(most - least <= 1);
+	}
+
+	#[test]
+	fn runs_on_a_bad_block_manager() {
+		let mut flash = MockNandFlash::<32, 24, 4, 16>::new();
+		flash.set_factory_bad_block(1);
+		let mut manager_buffer = [0; 56];
+		let manager = BadBlockManager::<_, 4>::new(flash, &mut manager_buffer).unwrap();
+
+		let mut buffer = [0; 32];
+		let mut ftl = Ftl::<_, 8, 16>::new(manager, &mut buffer).unwrap();
+		for round in 0..4 {
+			for sector in 0..8 {
+				ftl.write(sector * 32, &[round + sector as u8; 32]).unwrap();
+			}
+		}
+
+		let mut ftl = Ftl::<_, 8, 16>::new(ftl.into_inner(), &mut buffer).unwrap();
+		for sector in 0..8 {
+			assert_eq!(read(&mut ftl, sector), [3 + sector as u8; 32]);
+		}
+	}
+}
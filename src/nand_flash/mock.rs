@@ -0,0 +1,428 @@
+use crate::{
+	nand_flash::{
+		check_block, check_page, EccStatus, ErrorType, NandFlash, NandFlashError,
+		NandFlashErrorKind, ReadNandFlash,
+	},
+	StorageError, StorageErrorKind,
+};
+
+/// Number of bit errors per page the simulated ECC corrects.
+const CORRECTABLE_BIT_ERRORS: u8 = 4;
+
+/// Errors returned by [`MockNandFlash`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum MockNandFlashError {
+	/// The arguments are out of bounds.
+	OutOfBounds,
+
+	/// A page was programmed twice without being erased, or after a later page of its block.
+	OutOfOrder,
+
+	/// Programming failed, as injected with [`MockNandFlash::fail_program`].
+	ProgramFailed,
+
+	/// Erasing failed, as injected with [`MockNandFlash::fail_erase`].
+	EraseFailed,
+
+	/// The page has more bit errors than the ECC can correct, as injected with
+	/// [`MockNandFlash::inject_bit_errors`].
+	UncorrectableEcc,
+}
+
+impl MockNandFlashError {
+	fn from_check(_: NandFlashErrorKind) -> Self {
+		// The check helpers only report bounds errors
+		Self::OutOfBounds
+	}
+}
+
+impl NandFlashError for MockNandFlashError {
+	fn kind(&self) -> NandFlashErrorKind {
+		match self {
+			Self::OutOfBounds => NandFlashErrorKind::OutOfBounds,
+			Self::OutOfOrder => NandFlashErrorKind::Other,
+			Self::ProgramFailed => NandFlashErrorKind::ProgramFailed,
+			Self::EraseFailed => NandFlashErrorKind::EraseFailed,
+			Self::UncorrectableEcc => NandFlashErrorKind::UncorrectableEcc,
+		}
+	}
+}
+
+impl StorageError for MockNandFlashError {
+	fn kind(&self) -> StorageErrorKind {
+		NandFlashError::kind(self).into()
+	}
+}
+
+impl core::fmt::Display for MockNandFlashError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::OutOfBounds => write!(f, "Arguments are out of bounds"),
+			Self::OutOfOrder => write!(f, "Page was not programmed in order"),
+			Self::ProgramFailed => write!(f, "Programming failed"),
+			Self::EraseFailed => write!(f, "Erasing failed"),
+			Self::UncorrectableEcc => write!(f, "Data has uncorrectable ECC errors"),
+		}
+	}
+}
+
+/// In-memory NAND flash, for testing code built on top of [`NandFlash`] on the host.
+///
+/// The flash has `BLOCKS` blocks of `PAGES` pages, each holding `PAGE` data bytes and `SPARE`
+/// spare bytes, and starts out fully erased. Every operation is validated with [`check_page`]
+/// and [`check_block`], and pages have to be programmed once and in ascending order between
+/// erases, as required by [`NandFlash::program_page`].
+///
+/// Like on most real chips, a block is bad if the first spare byte of its first page is not
+/// `0xff`. Factory bad blocks, program and erase failures and bit errors can be injected to
+/// exercise error handling.
+#[derive(Debug, Clone)]
+pub struct MockNandFlash<
+	const PAGE: usize,
+	const SPARE: usize,
+	const PAGES: usize,
+	const BLOCKS: usize,
+> {
+	data: [[[u8; PAGE]; PAGES]; BLOCKS],
+	spare: [[[u8; SPARE]; PAGES]; BLOCKS],
+	next_page: [usize; BLOCKS],
+	failing_program: [bool; BLOCKS],
+	failing_erase: [bool; BLOCKS],
+	bit_errors: [[u8; PAGES]; BLOCKS],
+}
+
+impl<const PAGE: usize, const SPARE: usize, const PAGES: usize, const BLOCKS: usize>
+	MockNandFlash<PAGE, SPARE, PAGES, BLOCKS>
+{
+	const GEOMETRY: () = assert!(
+		PAGE > 0 && SPARE > 0 && PAGES > 0,
+		"Pages need data and spare bytes for the bad block marker"
+	);
+
+	/// Create a new, fully erased flash without any bad block.
+	pub fn new() -> Self {
+		let () = Self::GEOMETRY;
+
+		Self {
+			data: [[[0xff; PAGE]; PAGES]; BLOCKS],
+			spare: [[[0xff; SPARE]; PAGES]; BLOCKS],
+			next_page: [0; BLOCKS],
+			failing_program: [false; BLOCKS],
+			failing_erase: [false; BLOCKS],
+			bit_errors: [[0; PAGES]; BLOCKS],
+		}
+	}
+
+	/// Mark `block` as bad, as if it was shipped bad by the factory.
+	pub fn set_factory_bad_block(&mut self, block: u32) {
+		self.spare[block as usize][0][0] = 0;
+	}
+
+	/// Let every program of a page in `block` fail from now on.
+	pub fn fail_program(&mut self, block: u32) {
+		self.failing_program[block as usize] = true;
+	}
+
+	/// Let every erase of `block` fail from now on.
+	pub fn fail_erase(&mut self, block: u32) {
+		self.failing_erase[block as usize] = true;
+	}
+
+	/// Report `bits` bit errors when reading `page`, until its block is erased.
+	///
+	/// Up to 4 bit errors are corrected, more fail with [`MockNandFlashError::UncorrectableEcc`].
+	pub fn inject_bit_errors(&mut self, page: u32, bits: u8) {
+		let (block, page) = Self::locate(page);
+		self.bit_errors[block][page] = bits;
+	}
+
+	/// The block and the page within that block of `page`.
+	fn locate(page: u32) -> (usize, usize) {
+		(page as usize / PAGES, page as usize % PAGES)
+	}
+}
+
+impl<const PAGE: usize, const SPARE: usize, const PAGES: usize, const BLOCKS: usize> Default
+	for MockNandFlash<PAGE, SPARE, PAGES, BLOCKS>
+{
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<const PAGE: usize, const SPARE: usize, const PAGES: usize, const BLOCKS: usize> ErrorType
+	for MockNandFlash<PAGE, SPARE, PAGES, BLOCKS>
+{
+	type Error = MockNandFlashError;
+}
+
+impl<const PAGE: usize, const SPARE: usize, const PAGES: usize, const BLOCKS: usize> ReadNandFlash
+	for MockNandFlash<PAGE, SPARE, PAGES, BLOCKS>
+{
+	const PAGE_SIZE: usize = PAGE;
+	const SPARE_SIZE: usize = SPARE;
+	const BLOCK_SIZE: usize = PAGE * PAGES;
+
+	fn read_page(
+		&mut self,
+		page: u32,
+		data: &mut [u8],
+		spare: &mut [u8],
+	) -> Result<EccStatus, Self::Error> {
+		check_page(self, page, data.len(), spare.len()).map_err(MockNandFlashError::from_check)?;
+		let (block, page) = Self::locate(page);
+		let status = match self.bit_errors[block][page] {
+			0 => EccStatus::Clean,
+			bits if bits <= CORRECTABLE_BIT_ERRORS => EccStatus::Corrected(bits),
+			_ => return Err(MockNandFlashError::UncorrectableEcc),
+		};
+		data.copy_from_slice(&self.data[block][page][..data.len()]);
+		spare.copy_from_slice(&self.spare[block][page][..spare.len()]);
+		Ok(status)
+	}
+
+	fn is_bad_block(&mut self, block: u32) -> Result<bool, Self::Error> {
+		check_block(self, block).map_err(MockNandFlashError::from_check)?;
+		Ok(self.spare[block as usize][0][0] != 0xff)
+	}
+
+	fn block_count(&self) -> usize {
+		BLOCKS
+	}
+}
+
+impl<const PAGE: usize, const SPARE: usize, const PAGES: usize, const BLOCKS: usize> NandFlash
+	for MockNandFlash<PAGE, SPARE, PAGES, BLOCKS>
+{
+	fn erase_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		check_block(self, block).map_err(MockNandFlashError::from_check)?;
+		let block = block as usize;
+		if self.failing_erase[block] {
+			return Err(MockNandFlashError::EraseFailed);
+		}
+		self.data[block] = [[0xff; PAGE]; PAGES];
+		self.spare[block] = [[0xff; SPARE]; PAGES];
+		self.next_page[block] = 0;
+		self.bit_errors[block] = [0; PAGES];
+		Ok(())
+	}
+
+	fn program_page(&mut self, page: u32, data: &[u8], spare: &[u8]) -> Result<(), Self::Error> {
+		check_page(self, page, data.len(), spare.len()).map_err(MockNandFlashError::from_check)?;
+		let (block, page) = Self::locate(page);
+		if page < self.next_page[block] {
+			return Err(MockNandFlashError::OutOfOrder);
+		}
+		self.next_page[block] = page + 1;
+		if self.failing_program[block] {
+			return Err(MockNandFlashError::ProgramFailed);
+		}
+		self.data[block][page][..data.len()].copy_from_slice(data);
+		self.spare[block][page][..spare.len()].copy_from_slice(spare);
+		Ok(())
+	}
+
+	fn mark_bad_block(&mut self, block: u32) -> Result<(), Self::Error> {
+		check_block(self, block).map_err(MockNandFlashError::from_check)?;
+		self.set_factory_bad_block(block);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	type Flash = MockNandFlash<4, 2, 4, 4>;
+
+	/// A flash of `BLOCKS` blocks of 512 bytes, reading erased pages.
+	struct LargeFlash<const BLOCKS: usize>;
+
+	impl<const BLOCKS: usize> ErrorType for LargeFlash<BLOCKS> {
+		type Error = MockNandFlashError;
+	}
+
+	impl<const BLOCKS: usize> ReadNandFlash for LargeFlash<BLOCKS> {
+		const PAGE_SIZE: usize = 512;
+		const SPARE_SIZE: usize = 16;
+		const BLOCK_SIZE: usize = 512;
+
+		fn read_page(
+			&mut self,
+			page: u32,
+			data: &mut [u8],
+			spare: &mut [u8],
+		) -> Result<EccStatus, Self::Error> {
+			check_page(self, page, data.len(), spare.len())
+				.map_err(MockNandFlashError::from_check)?;
+			data.fill(0xff);
+			spare.fill(0xff);
+			Ok(EccStatus::Clean)
+		}
+
+		fn is_bad_block(&mut self, _block: u32) -> Result<bool, Self::Error> {
+			Ok(false)
+		}
+
+		fn block_count(&self) -> usize {
+			BLOCKS
+		}
+	}
+
+	#[test]
+	fn capacities_saturate() {
+		let mut flash = LargeFlash::<{ usize::MAX }>;
+		assert_eq!(flash.capacity(), usize::MAX);
+		assert_eq!(
+			flash.read_page(u32::MAX, &mut [], &mut []),
+			Ok(EccStatus::Clean)
+		);
+	}
+
+	#[test]
+	fn pages_round_trip() {
+		let mut flash = Flash::new();
+		assert_eq!(flash.capacity(), 64);
+		flash.program_page(5, &[1, 2, 3, 4], &[5]).unwrap();
+
+		let (mut data, mut spare) = ([0; 4], [0; 2]);
+		assert_eq!(
+			flash.read_page(5, &mut data, &mut spare),
+			Ok(EccStatus::Clean)
+		);
+		assert_eq!((data, spare), ([1, 2, 3, 4], [5, 0xff]));
+		assert_eq!(
+			flash.read_page(6, &mut data, &mut spare),
+			Ok(EccStatus::Clean)
+		);
+		assert_eq!((data, spare), ([0xff; 4], [0xff; 2]));
+
+		assert_eq!(
+			flash.read_page(16, &mut data, &mut spare),
+			Err(MockNandFlashError::OutOfBounds)
+		);
+		assert_eq!(
+			flash.program_page(6, &[0; 5], &[]),
+			Err(MockNandFlashError::OutOfBounds)
+		);
+		assert_eq!(
+			flash.program_page(6, &[], &[0; 3]),
+			Err(MockNandFlashError::OutOfBounds)
+		);
+	}
+
+	#[test]
+	fn pages_are_programmed_once_and_in_order() {
+		let mut flash = Flash::new();
+		flash.program_page(1, &[0], &[]).unwrap();
+		assert_eq!(
+			flash.program_page(1, &[0], &[]),
+			Err(MockNandFlashError::OutOfOrder)
+		);
+		assert_eq!(
+			flash.program_page(0, &[0], &[]),
+			Err(MockNandFlashError::OutOfOrder)
+		);
+		flash.program_page(3, &[0], &[]).unwrap();
+		// Other blocks keep their own order
+		flash.program_page(4, &[0], &[]).unwrap();
+
+		flash.erase_block(0).unwrap();
+		flash.program_page(0, &[0], &[]).unwrap();
+	}
+
+	#[test]
+	fn bad_blocks_are_reported() {
+		let mut flash = Flash::new();
+		flash.set_factory_bad_block(2);
+		assert_eq!(flash.is_bad_block(1), Ok(false));
+		assert_eq!(flash.is_bad_block(2), Ok(true));
+
+		flash.mark_bad_block(1).unwrap();
+		assert_eq!(flash.is_bad_block(1), Ok(true));
+		let mut spare = [0; 1];
+		flash.read_page(4, &mut [], &mut spare).unwrap();
+		assert_ne!(spare, [0xff]);
+
+		// Erasing clears the marker, as on a real chip
+		flash.erase_block(1).unwrap();
+		assert_eq!(flash.is_bad_block(1), Ok(false));
+		assert_eq!(flash.is_bad_block(4), Err(MockNandFlashError::OutOfBounds));
+		assert_eq!(
+			flash.mark_bad_block(4),
+			Err(MockNandFlashError::OutOfBounds)
+		);
+	}
+
+	#[test]
+	fn injected_bit_errors_last_until_erase() {
+		let mut flash = Flash::new();
+		flash.inject_bit_errors(1, CORRECTABLE_BIT_ERRORS);
+		flash.inject_bit_errors(2, CORRECTABLE_BIT_ERRORS + 1);
+
+		let mut data = [0; 4];
+		assert_eq!(
+			flash.read_page(1, &mut data, &mut []),
+			Ok(EccStatus::Corrected(CORRECTABLE_BIT_ERRORS))
+		);
+		assert_eq!(
+			flash.read_page(2, &mut data, &mut []),
+			Err(MockNandFlashError::UncorrectableEcc)
+		);
+		assert_eq!(flash.read_page(3, &mut data, &mut []), Ok(EccStatus::Clean));
+
+		flash.erase_block(0).unwrap();
+		assert_eq!(flash.read_page(2, &mut data, &mut []), Ok(EccStatus::Clean));
+	}
+
+	#[test]
+	fn injected_failures_leave_the_flash_unchanged() {
+		let mut flash = Flash::new();
+		flash.program_page(8, &[1; 4], &[]).unwrap();
+		flash.fail_program(1);
+		flash.fail_erase(2);
+
+		assert_eq!(
+			flash.program_page(4, &[0; 4], &[]),
+			Err(MockNandFlashError::ProgramFailed)
+		);
+		let mut data = [0; 4];
+		flash.read_page(4, &mut data, &mut []).unwrap();
+		assert_eq!(data, [0xff; 4]);
+
+		assert_eq!(flash.erase_block(2), Err(MockNandFlashError::EraseFailed));
+		flash.read_page(8, &mut data, &mut []).unwrap();
+		assert_eq!(data, [1; 4]);
+
+		// Only the failing blocks are affected
+		flash.program_page(12, &[0; 4], &[]).unwrap();
+		flash.erase_block(3).unwrap();
+	}
+
+	#[test]
+	fn errors_have_their_kind() {
+		let kinds = [
+			(
+				MockNandFlashError::OutOfBounds,
+				NandFlashErrorKind::OutOfBounds,
+			),
+			(
+				MockNandFlashError::ProgramFailed,
+				NandFlashErrorKind::ProgramFailed,
+			),
+			(
+				MockNandFlashError::EraseFailed,
+				NandFlashErrorKind::EraseFailed,
+			),
+			(
+				MockNandFlashError::UncorrectableEcc,
+				NandFlashErrorKind::UncorrectableEcc,
+			),
+		];
+		for (error, kind) in kinds {
+			assert_eq!(NandFlashError::kind(&error), kind);
+			assert_eq!(StorageError::kind(&error), StorageErrorKind::from(kind));
+		}
+	}
+}
@@ -1,4 +1,35 @@
-use crate::{iter::IterableByOverlaps, ReadStorage, Region, Storage};
+use crate::{
+	eeprom::{page_writes, PageWrites},
+	iter::IterableByOverlaps,
+	ReadStorage, Region, Storage,
+};
+
+mod concat;
+#[cfg(feature = "std")]
+mod file;
+mod mock;
+mod multi_erase;
+mod partition;
+mod power_loss;
+mod scratch;
+mod sector;
+mod shared;
+
+pub use self::concat::{Concat, ConcatError};
+#[cfg(feature = "std")]
+pub use self::file::{FileFlash, FileFlashError};
+pub use self::mock::{MockFlash, MockFlashError, MultiwriteMockFlash};
+pub use self::multi_erase::{erase_planned, plan_erase, EraseOps, MultiEraseNorFlash};
+pub use self::partition::{Partition, PartitionError};
+pub use self::power_loss::{simulate_power_loss, PowerLossError, PowerLossFlash};
+pub use self::scratch::{ScratchRmwError, ScratchRmwNorFlashStorage};
+pub use self::sector::{
+	check_sector_erase, check_sector_map, check_sector_write, Sector, SectorMapped, SectorNorFlash,
+	SectorRun, SectorStorageError, Sectors,
+};
+#[cfg(feature = "critical-section")]
+pub use self::shared::CriticalSectionPartition;
+pub use self::shared::{RefCellPartition, SharedPartitions};
 
 /// NOR flash errors.
 ///
@@ -36,6 +67,24 @@ pub enum NorFlashErrorKind {
 
 	/// Error specific to the implementation.
 	Other,
+
+	/// The targeted region is write protected.
+	WriteProtected,
+
+	/// Programming failed, or the data read back does not match the data written.
+	ProgramFailed,
+
+	/// Erasing failed, or the region read back is not erased.
+	EraseFailed,
+
+	/// The operation did not complete in time.
+	Timeout,
+
+	/// The data read has more bit errors than the ECC can correct.
+	UncorrectableEcc,
+
+	/// The device is busy with another operation.
+	Busy,
 }
 
 impl NorFlashError for NorFlashErrorKind {
@@ -50,6 +99,12 @@ impl core::fmt::Display for NorFlashErrorKind {
 			Self::NotAligned => write!(f, "Arguments are not properly aligned"),
 			Self::OutOfBounds => write!(f, "Arguments are out of bounds"),
 			Self::Other => write!(f, "An implementation specific error occurred"),
+			Self::WriteProtected => write!(f, "Region is write protected"),
+			Self::ProgramFailed => write!(f, "Programming failed"),
+			Self::EraseFailed => write!(f, "Erasing failed"),
+			Self::Timeout => write!(f, "Operation timed out"),
+			Self::UncorrectableEcc => write!(f, "Data has uncorrectable ECC errors"),
+			Self::Busy => write!(f, "Device is busy"),
 		}
 	}
 }
@@ -89,8 +144,22 @@ pub trait NorFlash: ReadNorFlash {
 	/// The minumum number of bytes the storage peripheral can erase
 	const ERASE_SIZE: usize;
 
+	/// The largest number of bytes the storage peripheral programs with a single command, like
+	/// the 256 byte program page of SPI NOR flashes. Program pages start at multiples of
+	/// `PROGRAM_PAGE_SIZE`, which is a multiple of `WRITE_SIZE` and divides `ERASE_SIZE`.
+	///
+	/// Writes are not restricted to a program page, the implementation can split them with the
+	/// [`program_pages`] helper function. It defaults to `ERASE_SIZE`.
+	const PROGRAM_PAGE_SIZE: usize = Self::ERASE_SIZE;
+
+	/// The value every byte reads as after an erase, either `0xff` for flashes erasing to all 1s,
+	/// or `0x00` for flashes erasing to all 0s. Writes can only move bits away from this value.
+	///
+	/// It defaults to `0xff`, the erased state of nearly all NOR flashes.
+	const ERASE_VALUE: u8 = 0xff;
+
 	/// Erase the given storage range, clearing all data within `[from..to]`.
-	/// The given range will contain all 1s afterwards.
+	/// Every byte of the given range will read as `ERASE_VALUE` afterwards.
 	///
 	/// If power is lost during erase, contents of the page are undefined.
 	///
@@ -149,6 +218,16 @@ fn check_slice<T: ReadNorFlash>(
 	Ok(())
 }
 
+/// Split the write of `bytes` at `offset` into writes that each stay within a program page of
+/// `T`.
+///
+/// Fails to compile if `T::PROGRAM_PAGE_SIZE` is not a multiple of `T::WRITE_SIZE` dividing
+/// `T::ERASE_SIZE`.
+pub fn program_pages<T: NorFlash>(bytes: &[u8], offset: u32) -> PageWrites<'_> {
+	let () = <T as EraseWrite>::GEOMETRY;
+	page_writes(bytes, offset, T::PROGRAM_PAGE_SIZE)
+}
+
 impl<T: ErrorType> ErrorType for &mut T {
 	type Error = T::Error;
 }
@@ -168,6 +247,8 @@ impl<T: ReadNorFlash> ReadNorFlash for &mut T {
 impl<T: NorFlash> NorFlash for &mut T {
 	const WRITE_SIZE: usize = T::WRITE_SIZE;
 	const ERASE_SIZE: usize = T::ERASE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = T::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = T::ERASE_VALUE;
 
 	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
 		T::erase(self, from, to)
@@ -182,22 +263,24 @@ impl<T: NorFlash> NorFlash for &mut T {
 ///
 /// Writes to the same word twice are now allowed. The result is the logical AND of the
 /// previous data and the written data. That is, it is only possible to change 1 bits to 0 bits.
+/// On flashes whose [`NorFlash::ERASE_VALUE`] is `0x00` the roles of the bits are swapped: the
+/// result is the logical OR, and it is only possible to change 0 bits to 1 bits.
 ///
-/// If power is lost during write:
-/// - Bits that were 1 on flash and are written to 1 are guaranteed to stay as 1
-/// - Bits that were 1 on flash and are written to 0 are undefined
-/// - Bits that were 0 on flash are guaranteed to stay as 0
+/// If power is lost during write, with bits in their erased state called erased below:
+/// - Bits that were erased on flash and are written as erased are guaranteed to stay erased
+/// - Bits that were erased on flash and are written as programmed are undefined
+/// - Bits that were programmed on flash are guaranteed to stay programmed
 /// - Rest of the bits in the page are guaranteed to be unchanged
 pub trait MultiwriteNorFlash: NorFlash {}
 impl<T: MultiwriteNorFlash> MultiwriteNorFlash for &mut T {}
 
-struct Page {
+pub(crate) struct Page {
 	pub start: u32,
 	pub size: usize,
 }
 
 impl Page {
-	fn new(index: u32, size: usize) -> Self {
+	pub(crate) fn new(index: u32, size: usize) -> Self {
 		Self {
 			start: index * size as u32,
 			size,
@@ -215,7 +298,218 @@ impl Region for Page {
 	}
 }
 
+/// Iterator over the pages of `size` bytes with the given `indices`.
+pub(crate) struct Pages {
+	pub indices: core::ops::Range<u32>,
+	pub size: usize,
+}
+
+impl Iterator for Pages {
+	type Item = Page;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.indices.next().map(|index| Page::new(index, self.size))
+	}
+}
+
+/// The erase and write operations of a NOR flash needed by `rmw_regions`, whose erase units
+/// are given separately.
+trait EraseWrite: ReadNorFlash {
+	const WRITE_SIZE: usize;
+	const PROGRAM_PAGE_SIZE: usize;
+	const ERASE_VALUE: u8;
+
+	/// Fails to compile if the program page size does not fit the write and erase sizes.
+	const GEOMETRY: ();
+
+	fn erase_range(&mut self, from: u32, to: u32) -> Result<(), Self::Error>;
+
+	/// Write `bytes`, which stay within a program page, at `offset`.
+	fn write_page(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+
+	fn write_words(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		let () = Self::GEOMETRY;
+		for (data, addr) in page_writes(bytes, offset, Self::PROGRAM_PAGE_SIZE) {
+			self.write_page(addr, data)?;
+		}
+		Ok(())
+	}
+}
+
+impl<T: NorFlash> EraseWrite for T {
+	const WRITE_SIZE: usize = T::WRITE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = T::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = T::ERASE_VALUE;
+
+	const GEOMETRY: () = assert!(
+		T::PROGRAM_PAGE_SIZE % T::WRITE_SIZE == 0 && T::ERASE_SIZE % T::PROGRAM_PAGE_SIZE == 0,
+		"Program page size must be a multiple of the write size dividing the erase size"
+	);
+
+	fn erase_range(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.erase(from, to)
+	}
+
+	fn write_page(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.write(offset, bytes)
+	}
+}
+
+/// Perform read/modify/write operations on the byte slice, page by page.
+///
+/// Pages already holding `bytes` are left alone, and words which are still erased are written
+/// without erasing the page first. With `multiwrite`, which is only allowed on a
+/// [`MultiwriteNorFlash`], data is also written on top of existing data whenever no bit has to be
+/// changed back to its erased state.
+fn rmw_write<S: NorFlash>(
+	storage: &mut S,
+	merge_buffer: &mut [u8],
+	offset: u32,
+	bytes: &[u8],
+	multiwrite: bool,
+) -> Result<(), S::Error> {
+	let last_page = storage.capacity() / S::ERASE_SIZE;
+	let pages = (0..last_page as u32).map(move |i| Page::new(i, S::ERASE_SIZE));
+	rmw_regions(storage, pages, merge_buffer, offset, bytes, multiwrite)
+}
+
+/// Perform read/modify/write operations on the byte slice, erase unit by erase unit, the erase
+/// units being the `regions` covering the flash in ascending order.
+///
+/// `merge_buffer` must hold the largest region.
+fn rmw_regions<S: EraseWrite, R: Region>(
+	storage: &mut S,
+	regions: impl Iterator<Item = R>,
+	merge_buffer: &mut [u8],
+	offset: u32,
+	bytes: &[u8],
+	multiwrite: bool,
+) -> Result<(), S::Error> {
+	// `data` is the part of `bytes` contained within `page`,
+	// and `addr` in the address offset of `page` + any offset into the page as requested by `address`
+	for (data, page, addr) in regions.overlaps(bytes, offset) {
+		let merge_buffer = &mut merge_buffer[..(page.end() - page.start()) as usize];
+		let offset_into_page = addr.saturating_sub(page.start()) as usize;
+		let range = offset_into_page..offset_into_page + data.len();
+
+		storage.read(page.start(), merge_buffer)?;
+
+		// Nothing to do if the data is already there
+		if merge_buffer[range.clone()] == *data {
+			continue;
+		}
+
+		// The words overlapping with `data`, which need to be written
+		let words = range.start - range.start % S::WRITE_SIZE
+			..(range.end + S::WRITE_SIZE - 1) / S::WRITE_SIZE * S::WRITE_SIZE;
+
+		// Check if we can write the data block directly, under the limitations imposed by NorFlash:
+		// - We can only change bits away from their erased state
+		// - Unless the flash supports multiple writes, we can only write erased words
+		let writable = if multiwrite {
+			// Flip the bits so that erased bits are 1, as on a flash erasing to 0xff
+			let flip = !S::ERASE_VALUE;
+			data.iter()
+				.zip(&merge_buffer[range.clone()])
+				.all(|(a, b)| (*a ^ flip) & (*b ^ flip) == *a ^ flip)
+		} else {
+			merge_buffer[words.clone()]
+				.iter()
+				.all(|byte| *byte == S::ERASE_VALUE)
+		};
+
+		// Words left erased are skipped, so that they remain writable
+		merge_buffer[range].copy_from_slice(data);
+		if writable {
+			write_non_erased(
+				storage,
+				page.start() + words.start as u32,
+				&merge_buffer[words],
+			)?;
+		} else {
+			storage.erase_range(page.start(), page.end())?;
+			write_non_erased(storage, page.start(), merge_buffer)?;
+		}
+	}
+	Ok(())
+}
+
+/// Write `data` at `offset`, skipping words which are still erased, so they remain writable.
+fn write_non_erased<S: EraseWrite>(
+	storage: &mut S,
+	offset: u32,
+	data: &[u8],
+) -> Result<(), S::Error> {
+	let mut start = None;
+	for (i, word) in data.chunks(S::WRITE_SIZE).enumerate() {
+		let erased = word.iter().all(|byte| *byte == S::ERASE_VALUE);
+		match (start, erased) {
+			(None, false) => start = Some(i * S::WRITE_SIZE),
+			(Some(from), true) => {
+				storage.write_words(offset + from as u32, &data[from..i * S::WRITE_SIZE])?;
+				start = None;
+			}
+			_ => {}
+		}
+	}
+	if let Some(from) = start {
+		storage.write_words(offset + from as u32, &data[from..])?;
+	}
+	Ok(())
+}
+
+/// Read `bytes` at any `offset`, bouncing the unaligned head and tail through `buffer`.
+///
+/// `buffer` must hold at least [`ReadNorFlash::READ_SIZE`] bytes.
+fn read_unaligned<S: ReadNorFlash>(
+	storage: &mut S,
+	buffer: &mut [u8],
+	offset: u32,
+	bytes: &mut [u8],
+) -> Result<(), S::Error> {
+	let buffer = &mut buffer[..S::READ_SIZE];
+	let mut offset = offset;
+	let mut bytes = bytes;
+
+	let head = offset as usize % S::READ_SIZE;
+	if head != 0 && !bytes.is_empty() {
+		storage.read(offset - head as u32, buffer)?;
+		let len = bytes.len().min(S::READ_SIZE - head);
+		bytes[..len].copy_from_slice(&buffer[head..head + len]);
+		offset += len as u32;
+		bytes = &mut bytes[len..];
+	}
+
+	let aligned = bytes.len() - bytes.len() % S::READ_SIZE;
+	if aligned != 0 {
+		storage.read(offset, &mut bytes[..aligned])?;
+	}
+
+	let tail = &mut bytes[aligned..];
+	if !tail.is_empty() {
+		storage.read(offset + aligned as u32, buffer)?;
+		let len = tail.len();
+		tail.copy_from_slice(&buffer[..len]);
+	}
+	Ok(())
+}
+
+/// Error returned when a merge buffer is smaller than the erase size of the flash.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct MergeBufferTooSmall;
+
+impl core::fmt::Display for MergeBufferTooSmall {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "Merge buffer is too small")
+	}
+}
+
+/// A [`Storage`] implementation on top of a [`NorFlash`], doing read/modify/write operations
+/// through a merge buffer.
 ///
+/// It also works on top of a [`SectorNorFlash`] created with
+/// [`with_sectors`](RmwNorFlashStorage::with_sectors), doing read/modify/write operations sector
+/// by sector.
 pub struct RmwNorFlashStorage<'a, S> {
 	storage: S,
 	merge_buffer: &'a mut [u8],
@@ -230,14 +524,23 @@ where
 	/// **NOTE** This will panic if the provided merge buffer,
 	/// is smaller than the erase size of the flash peripheral
 	pub fn new(nor_flash: S, merge_buffer: &'a mut [u8]) -> Self {
+		match Self::try_new(nor_flash, merge_buffer) {
+			Ok(storage) => storage,
+			Err(_) => panic!("Merge buffer is too small"),
+		}
+	}
+
+	/// Instantiate a new generic `Storage` from a `NorFlash` peripheral, failing if the provided
+	/// merge buffer is smaller than the erase size of the flash peripheral
+	pub fn try_new(nor_flash: S, merge_buffer: &'a mut [u8]) -> Result<Self, MergeBufferTooSmall> {
 		if merge_buffer.len() < S::ERASE_SIZE {
-			panic!("Merge buffer is too small");
+			return Err(MergeBufferTooSmall);
 		}
 
-		Self {
+		Ok(Self {
 			storage: nor_flash,
 			merge_buffer,
-		}
+		})
 	}
 }
 
@@ -248,8 +551,7 @@ where
 	type Error = S::Error;
 
 	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
-		// Nothing special to be done for reads
-		self.storage.read(offset, bytes)
+		read_unaligned(&mut self.storage, self.merge_buffer, offset, bytes)
 	}
 
 	fn capacity(&self) -> usize {
@@ -262,35 +564,12 @@ where
 	S: NorFlash,
 {
 	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-		// Perform read/modify/write operations on the byte slice.
-		let last_page = self.storage.capacity() / S::ERASE_SIZE;
-
-		// `data` is the part of `bytes` contained within `page`,
-		// and `addr` in the address offset of `page` + any offset into the page as requested by `address`
-		for (data, page, addr) in (0..last_page as u32)
-			.map(move |i| Page::new(i, S::ERASE_SIZE))
-			.overlaps(bytes, offset)
-		{
-			let offset_into_page = addr.saturating_sub(page.start) as usize;
-
-			self.storage
-				.read(page.start, &mut self.merge_buffer[..S::ERASE_SIZE])?;
-
-			// If we cannot write multiple times to the same page, we will have to erase it
-			self.storage.erase(page.start, page.end())?;
-			self.merge_buffer[..S::ERASE_SIZE]
-				.iter_mut()
-				.skip(offset_into_page)
-				.zip(data)
-				.for_each(|(byte, input)| *byte = *input);
-			self.storage
-				.write(page.start, &self.merge_buffer[..S::ERASE_SIZE])?;
-		}
-		Ok(())
+		rmw_write(&mut self.storage, self.merge_buffer, offset, bytes, false)
 	}
 }
 
-///
+/// A [`Storage`] implementation on top of a [`MultiwriteNorFlash`], only erasing pages when the
+/// new data cannot be written on top of the existing data.
 pub struct RmwMultiwriteNorFlashStorage<'a, S> {
 	storage: S,
 	merge_buffer: &'a mut [u8],
@@ -305,14 +584,23 @@ where
 	/// **NOTE** This will panic if the provided merge buffer,
 	/// is smaller than the erase size of the flash peripheral
 	pub fn new(nor_flash: S, merge_buffer: &'a mut [u8]) -> Self {
+		match Self::try_new(nor_flash, merge_buffer) {
+			Ok(storage) => storage,
+			Err(_) => panic!("Merge buffer is too small"),
+		}
+	}
+
+	/// Instantiate a new generic `Storage` from a `NorFlash` peripheral, failing if the provided
+	/// merge buffer is smaller than the erase size of the flash peripheral
+	pub fn try_new(nor_flash: S, merge_buffer: &'a mut [u8]) -> Result<Self, MergeBufferTooSmall> {
 		if merge_buffer.len() < S::ERASE_SIZE {
-			panic!("Merge buffer is too small");
+			return Err(MergeBufferTooSmall);
 		}
 
-		Self {
+		Ok(Self {
 			storage: nor_flash,
 			merge_buffer,
-		}
+		})
 	}
 }
 
@@ -323,8 +611,7 @@ where
 	type Error = S::Error;
 
 	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
-		// Nothing special to be done for reads
-		self.storage.read(offset, bytes)
+		read_unaligned(&mut self.storage, self.merge_buffer, offset, bytes)
 	}
 
 	fn capacity(&self) -> usize {
@@ -337,44 +624,305 @@ where
 	S: MultiwriteNorFlash,
 {
 	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-		// Perform read/modify/write operations on the byte slice.
-		let last_page = self.storage.capacity() / S::ERASE_SIZE;
-
-		// `data` is the part of `bytes` contained within `page`,
-		// and `addr` in the address offset of `page` + any offset into the page as requested by `address`
-		for (data, page, addr) in (0..last_page as u32)
-			.map(move |i| Page::new(i, S::ERASE_SIZE))
-			.overlaps(bytes, offset)
-		{
-			let offset_into_page = addr.saturating_sub(page.start) as usize;
-
-			self.storage
-				.read(page.start, &mut self.merge_buffer[..S::ERASE_SIZE])?;
-
-			let rhs = &self.merge_buffer[offset_into_page..S::ERASE_SIZE];
-			let is_subset = data.iter().zip(rhs.iter()).all(|(a, b)| *a & *b == *a);
-
-			// Check if we can write the data block directly, under the limitations imposed by NorFlash:
-			// - We can only change 1's to 0's
-			if is_subset {
-				// Use `merge_buffer` as allocation for padding `data` to `WRITE_SIZE`
-				let offset = addr as usize % S::WRITE_SIZE;
-				let aligned_end = data.len() % S::WRITE_SIZE + offset + data.len();
-				self.merge_buffer[..aligned_end].fill(0xff);
-				self.merge_buffer[offset..offset + data.len()].copy_from_slice(data);
-				self.storage
-					.write(addr - offset as u32, &self.merge_buffer[..aligned_end])?;
-			} else {
-				self.storage.erase(page.start, page.end())?;
-				self.merge_buffer[..S::ERASE_SIZE]
-					.iter_mut()
-					.skip(offset_into_page)
-					.zip(data)
-					.for_each(|(byte, input)| *byte = *input);
-				self.storage
-					.write(page.start, &self.merge_buffer[..S::ERASE_SIZE])?;
-			}
+		rmw_write(&mut self.storage, self.merge_buffer, offset, bytes, true)
+	}
+}
+
+/// A [`Storage`] implementation on top of a [`NorFlash`], doing read/modify/write operations
+/// through a merge buffer of `N` bytes held internally.
+///
+/// Unlike the borrowed variant, it has no lifetime and can be created in a `const` context, for
+/// instance to live in a `static`. Using it with a merge buffer smaller than the erase size of the
+/// flash fails to compile.
+pub struct OwnedRmwNorFlashStorage<S, const N: usize> {
+	storage: S,
+	merge_buffer: [u8; N],
+}
+
+impl<S, const N: usize> OwnedRmwNorFlashStorage<S, N> {
+	/// Instantiate a new generic `Storage` from a `NorFlash` peripheral
+	pub const fn new(nor_flash: S) -> Self {
+		Self {
+			storage: nor_flash,
+			merge_buffer: [0; N],
 		}
-		Ok(())
+	}
+}
+
+impl<S, const N: usize> OwnedRmwNorFlashStorage<S, N>
+where
+	S: NorFlash,
+{
+	const BUFFER: () = assert!(N >= S::ERASE_SIZE, "Merge buffer is too small");
+}
+
+impl<S, const N: usize> ReadStorage for OwnedRmwNorFlashStorage<S, N>
+where
+	S: NorFlash,
+{
+	type Error = S::Error;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		let () = Self::BUFFER;
+		read_unaligned(&mut self.storage, &mut self.merge_buffer, offset, bytes)
+	}
+
+	fn capacity(&self) -> usize {
+		self.storage.capacity()
+	}
+}
+
+impl<S, const N: usize> Storage for OwnedRmwNorFlashStorage<S, N>
+where
+	S: NorFlash,
+{
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		let () = Self::BUFFER;
+		rmw_write(
+			&mut self.storage,
+			&mut self.merge_buffer,
+			offset,
+			bytes,
+			false,
+		)
+	}
+}
+
+/// A [`Storage`] implementation on top of a [`MultiwriteNorFlash`], only erasing pages when the
+/// new data cannot be written on top of the existing data, with a merge buffer of `N` bytes held
+/// internally.
+///
+/// Unlike the borrowed variant, it has no lifetime and can be created in a `const` context, for
+/// instance to live in a `static`. Using it with a merge buffer smaller than the erase size of the
+/// flash fails to compile.
+pub struct OwnedRmwMultiwriteNorFlashStorage<S, const N: usize> {
+	storage: S,
+	merge_buffer: [u8; N],
+}
+
+impl<S, const N: usize> OwnedRmwMultiwriteNorFlashStorage<S, N> {
+	/// Instantiate a new generic `Storage` from a `NorFlash` peripheral
+	pub const fn new(nor_flash: S) -> Self {
+		Self {
+			storage: nor_flash,
+			merge_buffer: [0; N],
+		}
+	}
+}
+
+impl<S, const N: usize> OwnedRmwMultiwriteNorFlashStorage<S, N>
+where
+	S: NorFlash,
+{
+	const BUFFER: () = assert!(N >= S::ERASE_SIZE, "Merge buffer is too small");
+}
+
+impl<S, const N: usize> ReadStorage for OwnedRmwMultiwriteNorFlashStorage<S, N>
+where
+	S: NorFlash,
+{
+	type Error = S::Error;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		let () = Self::BUFFER;
+		read_unaligned(&mut self.storage, &mut self.merge_buffer, offset, bytes)
+	}
+
+	fn capacity(&self) -> usize {
+		self.storage.capacity()
+	}
+}
+
+impl<S, const N: usize> Storage for OwnedRmwMultiwriteNorFlashStorage<S, N>
+where
+	S: MultiwriteNorFlash,
+{
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		let () = Self::BUFFER;
+		rmw_write(
+			&mut self.storage,
+			&mut self.merge_buffer,
+			offset,
+			bytes,
+			true,
+		)
+	}
+}
+
+/// A [`ReadStorage`] implementation on top of a [`ReadNorFlash`], allowing reads of any length at
+/// any offset.
+///
+/// Reads not aligned to [`ReadNorFlash::READ_SIZE`] are bounced through a buffer, aligned reads go
+/// straight to the flash.
+pub struct ReadNorFlashStorage<'a, S> {
+	storage: S,
+	buffer: &'a mut [u8],
+}
+
+impl<'a, S> ReadNorFlashStorage<'a, S>
+where
+	S: ReadNorFlash,
+{
+	/// Instantiate a new generic `ReadStorage` from a `ReadNorFlash` peripheral
+	///
+	/// **NOTE** This will panic if the provided buffer,
+	/// is smaller than the read size of the flash peripheral
+	pub fn new(nor_flash: S, buffer: &'a mut [u8]) -> Self {
+		if buffer.len() < S::READ_SIZE {
+			panic!("Read buffer is too small");
+		}
+
+		Self {
+			storage: nor_flash,
+			buffer,
+		}
+	}
+}
+
+impl<'a, S> ReadStorage for ReadNorFlashStorage<'a, S>
+where
+	S: ReadNorFlash,
+{
+	type Error = S::Error;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		read_unaligned(&mut self.storage, self.buffer, offset, bytes)
+	}
+
+	fn capacity(&self) -> usize {
+		self.storage.capacity()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	type Flash = MockFlash<64, 1, 1, 16>;
+
+	/// Checks that no write crosses a program page of 8 bytes.
+	struct PagedFlash(MockFlash<64, 1, 2, 16>);
+
+	impl ErrorType for PagedFlash {
+		type Error = MockFlashError;
+	}
+
+	impl ReadNorFlash for PagedFlash {
+		const READ_SIZE: usize = 1;
+
+		fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			self.0.read(offset, bytes)
+		}
+
+		fn capacity(&self) -> usize {
+			self.0.capacity()
+		}
+	}
+
+	impl NorFlash for PagedFlash {
+		const WRITE_SIZE: usize = 2;
+		const ERASE_SIZE: usize = 16;
+		const PROGRAM_PAGE_SIZE: usize = 8;
+
+		fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+			self.0.erase(from, to)
+		}
+
+		fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+			assert_eq!(offset / 8, (offset + bytes.len() as u32 - 1) / 8);
+			self.0.write(offset, bytes)
+		}
+	}
+
+	#[test]
+	fn rmw_leaves_erased_bytes_writable() {
+		let mut flash = Flash::new();
+		let mut buffer = [0; 16];
+		let mut storage = RmwNorFlashStorage::new(&mut flash, &mut buffer);
+
+		storage.write(0, &[0xff, 0x00]).unwrap();
+		storage.write(0, &[0x12]).unwrap();
+
+		let mut data = [0; 2];
+		storage.read(0, &mut data).unwrap();
+		assert_eq!(data, [0x12, 0x00]);
+	}
+
+	#[test]
+	fn rmw_leaves_padding_words_writable() {
+		let mut flash = MockFlash::<64, 1, 4, 16>::new();
+		let mut buffer = [0; 16];
+		let mut storage = RmwNorFlashStorage::new(&mut flash, &mut buffer);
+
+		storage.write(2, &[0x00, 0x00, 0xff, 0xff]).unwrap();
+		storage.write(6, &[0x34, 0x56]).unwrap();
+
+		let mut data = [0; 8];
+		storage.read(0, &mut data).unwrap();
+		assert_eq!(data, [0xff, 0xff, 0x00, 0x00, 0xff, 0xff, 0x34, 0x56]);
+	}
+
+	#[test]
+	fn rmw_writes_stay_within_program_pages() {
+		let mut flash = PagedFlash(MockFlash::new());
+		let mut buffer = [0; 16];
+		let mut storage = RmwNorFlashStorage::new(&mut flash, &mut buffer);
+
+		storage.write(3, &[0x5a; 26]).unwrap();
+		storage.write(4, &[0xa5; 4]).unwrap();
+
+		let mut data = [0; 32];
+		storage.read(0, &mut data).unwrap();
+		assert_eq!(data[..3], [0xff; 3]);
+		assert_eq!(data[3..8], [0x5a, 0xa5, 0xa5, 0xa5, 0xa5]);
+		assert_eq!(data[8..29], [0x5a; 21]);
+		assert_eq!(data[29..], [0xff; 3]);
+	}
+
+	#[test]
+	fn program_pages_split_writes() {
+		let bytes = [0; 20];
+		let mut writes = program_pages::<PagedFlash>(&bytes, 6);
+		assert_eq!(writes.next(), Some((&bytes[..2], 6)));
+		assert_eq!(writes.next(), Some((&bytes[2..10], 8)));
+		assert_eq!(writes.next(), Some((&bytes[10..18], 16)));
+		assert_eq!(writes.next(), Some((&bytes[18..], 24)));
+		assert_eq!(writes.next(), None);
+		assert_eq!(program_pages::<PagedFlash>(&[], 6).next(), None);
+	}
+
+	#[test]
+	fn rmw_respects_flashes_erasing_to_zero() {
+		let mut flash = MockFlash::<64, 1, 1, 16, 0x00>::new();
+		let mut buffer = [0; 16];
+		let mut storage = RmwNorFlashStorage::new(&mut flash, &mut buffer);
+
+		let mut data = [0xff; 2];
+		storage.read(0, &mut data).unwrap();
+		assert_eq!(data, [0x00; 2]);
+
+		storage.write(0, &[0x00, 0x12]).unwrap();
+		storage.write(0, &[0x34]).unwrap();
+		storage.write(1, &[0x10]).unwrap();
+		storage.read(0, &mut data).unwrap();
+		assert_eq!(data, [0x34, 0x10]);
+	}
+
+	#[test]
+	fn multiwrite_rmw_respects_flashes_erasing_to_zero() {
+		let mut flash = MultiwriteMockFlash::<64, 1, 1, 16, 0x00>::new();
+		flash.set_strict_bit_transitions(true);
+		let mut buffer = [0; 16];
+		let mut storage = RmwMultiwriteNorFlashStorage::new(&mut flash, &mut buffer);
+
+		let mut data = [0; 4];
+		storage.write(0, &[0x01; 4]).unwrap();
+		storage.write(0, &[0x03; 4]).unwrap();
+		storage.read(0, &mut data).unwrap();
+		assert_eq!(data, [0x03; 4]);
+
+		storage.write(1, &[0x02; 2]).unwrap();
+		storage.read(0, &mut data).unwrap();
+		assert_eq!(data, [0x03, 0x02, 0x02, 0x03]);
 	}
 }
@@ -0,0 +1,390 @@
+use crate::{
+	iter::IterableByOverlaps,
+	nor_flash::{
+		check_erase, check_read, check_write, ErrorType, MultiwriteNorFlash, NorFlash,
+		NorFlashError, NorFlashErrorKind, ReadNorFlash,
+	},
+	Region,
+};
+
+/// Errors returned by [`Concat`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ConcatError<A, B> {
+	/// The arguments are not properly aligned, or out of bounds.
+	Concat(NorFlashErrorKind),
+
+	/// The first flash returned an error.
+	First(A),
+
+	/// The second flash returned an error.
+	Second(B),
+}
+
+impl<A: NorFlashError, B: NorFlashError> NorFlashError for ConcatError<A, B> {
+	fn kind(&self) -> NorFlashErrorKind {
+		match self {
+			Self::Concat(kind) => *kind,
+			Self::First(e) => e.kind(),
+			Self::Second(e) => e.kind(),
+		}
+	}
+}
+
+impl<A: core::fmt::Display, B: core::fmt::Display> core::fmt::Display for ConcatError<A, B> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::Concat(kind) => kind.fmt(f),
+			Self::First(e) => e.fmt(f),
+			Self::Second(e) => e.fmt(f),
+		}
+	}
+}
+
+/// Address range covered by one of the concatenated flashes.
+struct Device {
+	start: u32,
+	end: u32,
+	second: bool,
+}
+
+impl Region for Device {
+	fn start(&self) -> u32 {
+		self.start
+	}
+
+	fn end(&self) -> u32 {
+		self.end
+	}
+}
+
+/// Whether either size is a multiple of the other.
+const fn compatible(a: usize, b: usize) -> bool {
+	a != 0 && b != 0 && (a % b == 0 || b % a == 0)
+}
+
+const fn max(a: usize, b: usize) -> usize {
+	if a > b {
+		a
+	} else {
+		b
+	}
+}
+
+const fn min(a: usize, b: usize) -> usize {
+	if a < b {
+		a
+	} else {
+		b
+	}
+}
+
+/// Two NOR flashes concatenated into a single address space.
+///
+/// The first flash is mapped at address 0, the second one directly after it. Reads, writes and
+/// erases crossing the boundary are split between both flashes.
+///
+/// The read, write and erase sizes are the largest of both flashes, and the program page size the
+/// smallest, as both flashes have to program a page with a single command. Combining flashes
+/// whose sizes are not multiples of each other fails to compile.
+///
+/// ```compile_fail,E0080
+/// use embedded_storage::nor_flash::{Concat, MockFlash};
+///
+/// // Writes of 4 bytes cannot be split into writes of 3 bytes
+/// let flash = Concat::new(MockFlash::<48, 1, 4, 12>::new(), MockFlash::<48, 1, 3, 12>::new());
+/// ```
+#[derive(Debug)]
+pub struct Concat<A, B> {
+	first: A,
+	second: B,
+}
+
+impl<A, B> Concat<A, B>
+where
+	A: NorFlash,
+	B: NorFlash,
+{
+	const GEOMETRY: () = assert!(
+		compatible(A::READ_SIZE, B::READ_SIZE)
+			&& compatible(A::WRITE_SIZE, B::WRITE_SIZE)
+			&& compatible(A::ERASE_SIZE, B::ERASE_SIZE)
+			&& compatible(A::PROGRAM_PAGE_SIZE, B::PROGRAM_PAGE_SIZE)
+			&& min(A::PROGRAM_PAGE_SIZE, B::PROGRAM_PAGE_SIZE) % max(A::WRITE_SIZE, B::WRITE_SIZE)
+				== 0 && A::ERASE_VALUE == B::ERASE_VALUE,
+		"Concatenated flashes have incompatible geometries"
+	);
+
+	/// Concatenate `first` and `second`.
+	///
+	/// # Errors
+	///
+	/// Returns [`NorFlashErrorKind::NotAligned`] if the capacity of either flash is not a multiple
+	/// of the combined erase size, or [`NorFlashErrorKind::OutOfBounds`] if the combined capacity
+	/// does not fit in the `u32` address space.
+	pub fn new(first: A, second: B) -> Result<Self, NorFlashErrorKind> {
+		let () = Self::GEOMETRY;
+
+		first
+			.capacity()
+			.checked_add(second.capacity())
+			.filter(|capacity| *capacity <= u32::MAX as usize)
+			.ok_or(NorFlashErrorKind::OutOfBounds)?;
+		let erase_size = max(A::ERASE_SIZE, B::ERASE_SIZE);
+		if first.capacity() % erase_size != 0 || second.capacity() % erase_size != 0 {
+			return Err(NorFlashErrorKind::NotAligned);
+		}
+		Ok(Self { first, second })
+	}
+
+	/// Release both flashes.
+	pub fn into_inner(self) -> (A, B) {
+		(self.first, self.second)
+	}
+}
+
+impl<A, B> Concat<A, B>
+where
+	A: ReadNorFlash,
+	B: ReadNorFlash,
+{
+	fn devices(&self) -> impl Iterator<Item = Device> {
+		// `new` checked that the combined capacity fits in the address space
+		let split = self.first.capacity() as u32;
+		let end = split + self.second.capacity() as u32;
+		core::iter::once(Device {
+			start: 0,
+			end: split,
+			second: false,
+		})
+		.chain(core::iter::once(Device {
+			start: split,
+			end,
+			second: true,
+		}))
+	}
+}
+
+impl<A, B> ErrorType for Concat<A, B>
+where
+	A: ErrorType,
+	B: ErrorType,
+{
+	type Error = ConcatError<A::Error, B::Error>;
+}
+
+impl<A, B> ReadNorFlash for Concat<A, B>
+where
+	A: ReadNorFlash,
+	B: ReadNorFlash,
+{
+	const READ_SIZE: usize = max(A::READ_SIZE, B::READ_SIZE);
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		check_read(self, offset, bytes.len()).map_err(ConcatError::Concat)?;
+
+		// `OverlapIterator` splits immutable slices, so collect the parts before reading into them
+		let mut parts = [None, None];
+		for (part, (data, device, addr)) in
+			parts.iter_mut().zip(self.devices().overlaps(bytes, offset))
+		{
+			*part = Some(((addr - offset) as usize, data.len(), device));
+		}
+
+		for (start, len, device) in parts.iter().flatten() {
+			let data = &mut bytes[*start..*start + *len];
+			let addr = offset + *start as u32 - device.start;
+			if data.is_empty() {
+				continue;
+			} else if device.second {
+				self.second.read(addr, data).map_err(ConcatError::Second)?;
+			} else {
+				self.first.read(addr, data).map_err(ConcatError::First)?;
+			}
+		}
+		Ok(())
+	}
+
+	fn capacity(&self) -> usize {
+		self.first.capacity() + self.second.capacity()
+	}
+}
+
+impl<A, B> NorFlash for Concat<A, B>
+where
+	A: NorFlash,
+	B: NorFlash,
+{
+	const WRITE_SIZE: usize = max(A::WRITE_SIZE, B::WRITE_SIZE);
+	const ERASE_SIZE: usize = max(A::ERASE_SIZE, B::ERASE_SIZE);
+	const PROGRAM_PAGE_SIZE: usize = min(A::PROGRAM_PAGE_SIZE, B::PROGRAM_PAGE_SIZE);
+	const ERASE_VALUE: u8 = A::ERASE_VALUE;
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		check_erase(self, from, to).map_err(ConcatError::Concat)?;
+
+		let split = self.first.capacity() as u32;
+		if from < split {
+			self.first
+				.erase(from, to.min(split))
+				.map_err(ConcatError::First)?;
+		}
+		if to > split {
+			self.second
+				.erase(from.max(split) - split, to - split)
+				.map_err(ConcatError::Second)?;
+		}
+		Ok(())
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		check_write(self, offset, bytes.len()).map_err(ConcatError::Concat)?;
+
+		for (data, device, addr) in self.devices().overlaps(bytes, offset) {
+			let addr = addr - device.start;
+			if data.is_empty() {
+				continue;
+			} else if device.second {
+				self.second.write(addr, data).map_err(ConcatError::Second)?;
+			} else {
+				self.first.write(addr, data).map_err(ConcatError::First)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl<A, B> MultiwriteNorFlash for Concat<A, B>
+where
+	A: MultiwriteNorFlash,
+	B: MultiwriteNorFlash,
+{
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::nor_flash::{MockFlash, MockFlashError};
+
+	type First = MockFlash<16, 1, 2, 8>;
+	type Second = MockFlash<32, 2, 4, 16>;
+
+	fn concat() -> Concat<First, Second> {
+		Concat::new(First::new(), Second::new()).unwrap()
+	}
+
+	/// A flash of `CAPACITY` bytes without any contents.
+	struct Empty<const CAPACITY: usize>;
+
+	impl<const CAPACITY: usize> ErrorType for Empty<CAPACITY> {
+		type Error = NorFlashErrorKind;
+	}
+
+	impl<const CAPACITY: usize> ReadNorFlash for Empty<CAPACITY> {
+		const READ_SIZE: usize = 1;
+
+		fn read(&mut self, _offset: u32, _bytes: &mut [u8]) -> Result<(), Self::Error> {
+			Ok(())
+		}
+
+		fn capacity(&self) -> usize {
+			CAPACITY
+		}
+	}
+
+	impl<const CAPACITY: usize> NorFlash for Empty<CAPACITY> {
+		const WRITE_SIZE: usize = 1;
+		const ERASE_SIZE: usize = 1;
+
+		fn erase(&mut self, _from: u32, _to: u32) -> Result<(), Self::Error> {
+			Ok(())
+		}
+
+		fn write(&mut self, _offset: u32, _bytes: &[u8]) -> Result<(), Self::Error> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn combines_the_geometries() {
+		let flash = concat();
+		assert_eq!(flash.capacity(), 48);
+		assert_eq!(Concat::<First, Second>::READ_SIZE, 2);
+		assert_eq!(Concat::<First, Second>::WRITE_SIZE, 4);
+		assert_eq!(Concat::<First, Second>::ERASE_SIZE, 16);
+		// The first flash programs at most 8 bytes at once
+		assert_eq!(Concat::<First, Second>::PROGRAM_PAGE_SIZE, 8);
+	}
+
+	#[test]
+	fn rejects_flashes_not_fitting_the_geometry() {
+		assert_eq!(
+			Concat::new(MockFlash::<8, 1, 2, 8>::new(), Second::new()).err(),
+			Some(NorFlashErrorKind::NotAligned)
+		);
+		assert_eq!(
+			Concat::new(Empty::<{ u32::MAX as usize }>, Empty::<1>).err(),
+			Some(NorFlashErrorKind::OutOfBounds)
+		);
+		assert_eq!(
+			Concat::new(Empty::<{ usize::MAX }>, Empty::<1>).err(),
+			Some(NorFlashErrorKind::OutOfBounds)
+		);
+		let flash = Concat::new(Empty::<{ u32::MAX as usize - 1 }>, Empty::<1>).unwrap();
+		assert_eq!(flash.capacity(), u32::MAX as usize);
+	}
+
+	#[test]
+	fn accesses_straddle_the_split() {
+		let mut flash = concat();
+		flash.write(12, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+
+		let mut data = [0; 4];
+		flash.read(14, &mut data).unwrap();
+		assert_eq!(data, [3, 4, 5, 6]);
+
+		let (first, second) = flash.into_inner();
+		assert_eq!(first.as_bytes()[12..], [1, 2, 3, 4]);
+		assert_eq!(second.as_bytes()[..4], [5, 6, 7, 8]);
+
+		let mut flash = Concat::new(first, second).unwrap();
+		flash.erase(0, 32).unwrap();
+		let (first, second) = flash.into_inner();
+		assert_eq!(first.as_bytes(), [0xff; 16]);
+		assert_eq!(second.as_bytes(), [0xff; 32]);
+	}
+
+	#[test]
+	fn checks_the_combined_sizes() {
+		let mut flash = concat();
+		assert_eq!(
+			flash.read(15, &mut [0; 2]),
+			Err(ConcatError::Concat(NorFlashErrorKind::NotAligned))
+		);
+		assert_eq!(
+			flash.write(14, &[0; 4]),
+			Err(ConcatError::Concat(NorFlashErrorKind::NotAligned))
+		);
+		assert_eq!(
+			flash.erase(8, 24),
+			Err(ConcatError::Concat(NorFlashErrorKind::NotAligned))
+		);
+		assert_eq!(
+			flash.write(44, &[0; 8]),
+			Err(ConcatError::Concat(NorFlashErrorKind::OutOfBounds))
+		);
+	}
+
+	#[test]
+	fn forwards_errors_of_either_flash() {
+		let mut flash = concat();
+		flash.write(12, &[0; 8]).unwrap();
+		assert_eq!(
+			flash.write(8, &[0; 8]),
+			Err(ConcatError::First(MockFlashError::NotErased))
+		);
+		assert_eq!(
+			flash.write(16, &[0; 4]),
+			Err(ConcatError::Second(MockFlashError::NotErased))
+		);
+	}
+}
@@ -0,0 +1,274 @@
+use std::{
+	fs::{File, OpenOptions},
+	io::{self, Read, Seek, SeekFrom, Write},
+	path::Path,
+	vec,
+};
+
+use crate::nor_flash::{
+	check_erase, check_read, check_write, ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError,
+	NorFlashErrorKind, ReadNorFlash,
+};
+
+/// Errors returned by [`FileFlash`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FileFlashError {
+	/// The arguments are not properly aligned.
+	NotAligned,
+
+	/// The arguments are out of bounds.
+	OutOfBounds,
+
+	/// Accessing the backing file failed.
+	Io(io::Error),
+}
+
+impl FileFlashError {
+	fn from_check(kind: NorFlashErrorKind) -> Self {
+		match kind {
+			NorFlashErrorKind::NotAligned => Self::NotAligned,
+			// The check helpers only report alignment and bounds errors
+			_ => Self::OutOfBounds,
+		}
+	}
+}
+
+impl From<io::Error> for FileFlashError {
+	fn from(error: io::Error) -> Self {
+		Self::Io(error)
+	}
+}
+
+impl NorFlashError for FileFlashError {
+	fn kind(&self) -> NorFlashErrorKind {
+		match self {
+			Self::NotAligned => NorFlashErrorKind::NotAligned,
+			Self::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+			Self::Io(_) => NorFlashErrorKind::Other,
+		}
+	}
+}
+
+impl core::fmt::Display for FileFlashError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::NotAligned => write!(f, "Arguments are not properly aligned"),
+			Self::OutOfBounds => write!(f, "Arguments are out of bounds"),
+			Self::Io(e) => write!(f, "Accessing the flash image failed: {}", e),
+		}
+	}
+}
+
+impl std::error::Error for FileFlashError {}
+
+/// NOR flash persisted to a raw image file, such as a dump of a real chip.
+///
+/// The capacity is the size of the file, while `READ`, `WRITE`, `ERASE` and `ERASED` are used as
+/// [`ReadNorFlash::READ_SIZE`], [`NorFlash::WRITE_SIZE`], [`NorFlash::ERASE_SIZE`] and
+/// [`NorFlash::ERASE_VALUE`]. `ERASED` defaults to `0xff`. Every operation is validated with
+/// [`check_read`], [`check_write`] and [`check_erase`].
+///
+/// Like on a real NOR flash, writing only ever moves bits away from their erased state: the image
+/// stores the logical AND of the previous and the written data, or the logical OR if `ERASED` is
+/// `0x00`. As the image does not record which words were written, words can be written again
+/// without being erased, so the flash is a [`MultiwriteNorFlash`]. Use
+/// [`MockFlash`](super::MockFlash) to check that code does not rely on that.
+#[derive(Debug)]
+pub struct FileFlash<
+	const READ: usize,
+	const WRITE: usize,
+	const ERASE: usize,
+	const ERASED: u8 = 0xff,
+> {
+	file: File,
+	capacity: usize,
+}
+
+impl<const READ: usize, const WRITE: usize, const ERASE: usize, const ERASED: u8>
+	FileFlash<READ, WRITE, ERASE, ERASED>
+{
+	/// Open an existing flash image.
+	///
+	/// Fails if the size of the image is not a multiple of `ERASE`.
+	pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+		let file = OpenOptions::new().read(true).write(true).open(path)?;
+		let capacity = file.metadata()?.len() as usize;
+		if capacity % ERASE != 0 {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"Flash image size is not a multiple of the erase size",
+			));
+		}
+		Ok(Self { file, capacity })
+	}
+
+	/// Create a fully erased flash image of `capacity` bytes, replacing any existing file.
+	///
+	/// Fails if `capacity` is not a multiple of `ERASE`.
+	pub fn create(path: impl AsRef<Path>, capacity: usize) -> io::Result<Self> {
+		if capacity % ERASE != 0 {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"Flash capacity is not a multiple of the erase size",
+			));
+		}
+		let mut file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(true)
+			.open(path)?;
+		file.write_all(&vec![ERASED; capacity])?;
+		Ok(Self { file, capacity })
+	}
+
+	/// Flush all changes to the image file, and return it.
+	pub fn into_inner(mut self) -> io::Result<File> {
+		self.file.flush()?;
+		Ok(self.file)
+	}
+}
+
+impl<const READ: usize, const WRITE: usize, const ERASE: usize, const ERASED: u8> ErrorType
+	for FileFlash<READ, WRITE, ERASE, ERASED>
+{
+	type Error = FileFlashError;
+}
+
+impl<const READ: usize, const WRITE: usize, const ERASE: usize, const ERASED: u8> ReadNorFlash
+	for FileFlash<READ, WRITE, ERASE, ERASED>
+{
+	const READ_SIZE: usize = READ;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		check_read(self, offset, bytes.len()).map_err(FileFlashError::from_check)?;
+		self.file.seek(SeekFrom::Start(offset as u64))?;
+		self.file.read_exact(bytes)?;
+		Ok(())
+	}
+
+	fn capacity(&self) -> usize {
+		self.capacity
+	}
+}
+
+impl<const READ: usize, const WRITE: usize, const ERASE: usize, const ERASED: u8> NorFlash
+	for FileFlash<READ, WRITE, ERASE, ERASED>
+{
+	const WRITE_SIZE: usize = WRITE;
+	const ERASE_SIZE: usize = ERASE;
+	const ERASE_VALUE: u8 = ERASED;
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		check_erase(self, from, to).map_err(FileFlashError::from_check)?;
+		self.file.seek(SeekFrom::Start(from as u64))?;
+		self.file.write_all(&vec![ERASED; (to - from) as usize])?;
+		Ok(())
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		check_write(self, offset, bytes.len()).map_err(FileFlashError::from_check)?;
+		let mut data = vec![0; bytes.len()];
+		self.file.seek(SeekFrom::Start(offset as u64))?;
+		self.file.read_exact(&mut data)?;
+		// Flip the bits so that erased bits are 1, as on a flash erasing to 0xff
+		let flip = !ERASED;
+		data.iter_mut()
+			.zip(bytes)
+			.for_each(|(old, new)| *old = ((*old ^ flip) & (*new ^ flip)) ^ flip);
+		self.file.seek(SeekFrom::Start(offset as u64))?;
+		self.file.write_all(&data)?;
+		Ok(())
+	}
+}
+
+impl<const READ: usize, const WRITE: usize, const ERASE: usize, const ERASED: u8> MultiwriteNorFlash
+	for FileFlash<READ, WRITE, ERASE, ERASED>
+{
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	/// A path in the temporary directory, unique to the test `name` and this process.
+	fn image_path(name: &str) -> PathBuf {
+		std::env::temp_dir().join(std::format!(
+			"embedded-storage-{}-{}.bin",
+			name,
+			std::process::id()
+		))
+	}
+
+	#[test]
+	fn images_persist_writes_and_erases() {
+		let path = image_path("persist");
+		let mut flash = FileFlash::<1, 4, 16>::create(&path, 32).unwrap();
+		assert_eq!(flash.capacity(), 32);
+		flash.write(16, &[1, 2, 3, 4]).unwrap();
+		flash.write(20, &[5, 6, 7, 8]).unwrap();
+		flash.erase(0, 16).unwrap();
+		flash.into_inner().unwrap();
+
+		let mut flash = FileFlash::<1, 4, 16>::open(&path).unwrap();
+		let mut data = [0; 12];
+		flash.read(12, &mut data).unwrap();
+		assert_eq!(data, [0xff, 0xff, 0xff, 0xff, 1, 2, 3, 4, 5, 6, 7, 8]);
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn rewrites_store_the_logical_and() {
+		let path = image_path("rewrite");
+		let mut flash = FileFlash::<1, 4, 16>::create(&path, 16).unwrap();
+		flash.write(4, &[0b1100; 4]).unwrap();
+		flash.write(4, &[0b1010; 4]).unwrap();
+
+		let mut data = [0; 4];
+		flash.read(4, &mut data).unwrap();
+		assert_eq!(data, [0b1000; 4]);
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn accesses_are_checked() {
+		let path = image_path("checked");
+		let mut flash = FileFlash::<1, 4, 16>::create(&path, 32).unwrap();
+		assert!(matches!(
+			flash.write(2, &[0; 4]),
+			Err(FileFlashError::NotAligned)
+		));
+		assert!(matches!(flash.erase(0, 8), Err(FileFlashError::NotAligned)));
+		assert!(matches!(
+			flash.read(30, &mut [0; 4]),
+			Err(FileFlashError::OutOfBounds)
+		));
+		assert!(FileFlash::<1, 4, 16>::create(&path, 24).is_err());
+		drop(flash);
+		std::fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn images_erase_to_the_erase_value() {
+		let path = image_path("erased");
+		let mut flash = FileFlash::<1, 1, 16, 0x00>::create(&path, 32).unwrap();
+
+		let mut data = [0xff; 4];
+		flash.read(14, &mut data).unwrap();
+		assert_eq!(data, [0x00; 4]);
+
+		flash.write(14, &[0x01; 4]).unwrap();
+		flash.write(15, &[0x02; 2]).unwrap();
+		flash.read(14, &mut data).unwrap();
+		assert_eq!(data, [0x01, 0x03, 0x03, 0x01]);
+
+		flash.erase(0, 16).unwrap();
+		let mut flash = FileFlash::<1, 1, 16, 0x00>::open(&path).unwrap();
+		flash.read(14, &mut data).unwrap();
+		assert_eq!(data, [0x00, 0x00, 0x03, 0x01]);
+		std::fs::remove_file(path).unwrap();
+	}
+}
@@ -0,0 +1,460 @@
+use crate::nor_flash::{
+	check_erase, check_read, check_write, ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError,
+	NorFlashErrorKind, ReadNorFlash,
+};
+
+/// Errors returned by [`MockFlash`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum MockFlashError {
+	/// The arguments are not properly aligned.
+	NotAligned,
+
+	/// The arguments are out of bounds.
+	OutOfBounds,
+
+	/// A word was written to twice, without being erased in between.
+	NotErased,
+
+	/// A write tried to change a bit back to its erased state.
+	///
+	/// Only reported by a [`MultiwriteMockFlash`] with strict bit transitions enabled.
+	InvalidBitTransition,
+}
+
+impl MockFlashError {
+	fn from_check(kind: NorFlashErrorKind) -> Self {
+		match kind {
+			NorFlashErrorKind::NotAligned => Self::NotAligned,
+			// The check helpers only report alignment and bounds errors
+			_ => Self::OutOfBounds,
+		}
+	}
+}
+
+impl NorFlashError for MockFlashError {
+	fn kind(&self) -> NorFlashErrorKind {
+		match self {
+			Self::NotAligned => NorFlashErrorKind::NotAligned,
+			Self::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+			Self::NotErased | Self::InvalidBitTransition => NorFlashErrorKind::Other,
+		}
+	}
+}
+
+impl core::fmt::Display for MockFlashError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::NotAligned => write!(f, "Arguments are not properly aligned"),
+			Self::OutOfBounds => write!(f, "Arguments are out of bounds"),
+			Self::NotErased => write!(f, "Word was written to twice without being erased"),
+			Self::InvalidBitTransition => write!(f, "Write tried to change a bit back to erased"),
+		}
+	}
+}
+
+/// In-memory NOR flash, for testing code built on top of [`NorFlash`] on the host.
+///
+/// The flash starts out fully erased, i.e. every byte reads as `ERASED`. Every operation is
+/// validated with [`check_read`], [`check_write`] and [`check_erase`], and writing to the same
+/// word twice without erasing it in between fails with [`MockFlashError::NotErased`], as
+/// required by [`NorFlash::write`].
+///
+/// `CAPACITY` is the size of the flash in bytes, while `READ`, `WRITE`, `ERASE` and `ERASED` are
+/// used as [`ReadNorFlash::READ_SIZE`], [`NorFlash::WRITE_SIZE`], [`NorFlash::ERASE_SIZE`] and
+/// [`NorFlash::ERASE_VALUE`]. `ERASED` defaults to `0xff`.
+#[derive(Debug, Clone)]
+pub struct MockFlash<
+	const CAPACITY: usize,
+	const READ: usize,
+	const WRITE: usize,
+	const ERASE: usize,
+	const ERASED: u8 = 0xff,
+> {
+	data: [u8; CAPACITY],
+	written: [bool; CAPACITY],
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> MockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	/// Create a new, fully erased flash.
+	pub fn new() -> Self {
+		Self {
+			data: [ERASED; CAPACITY],
+			written: [false; CAPACITY],
+		}
+	}
+
+	/// The raw contents of the flash.
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.data
+	}
+
+	/// Mutable access to the raw contents of the flash.
+	///
+	/// Changes made this way bypass all checks, and do not mark the words as written.
+	pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+		&mut self.data
+	}
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> Default for MockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> AsMut<[u8]> for MockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	fn as_mut(&mut self) -> &mut [u8] {
+		self.as_bytes_mut()
+	}
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> ErrorType for MockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	type Error = MockFlashError;
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> ReadNorFlash for MockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	const READ_SIZE: usize = READ;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		check_read(self, offset, bytes.len()).map_err(MockFlashError::from_check)?;
+		let offset = offset as usize;
+		bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+		Ok(())
+	}
+
+	fn capacity(&self) -> usize {
+		CAPACITY
+	}
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> NorFlash for MockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	const WRITE_SIZE: usize = WRITE;
+	const ERASE_SIZE: usize = ERASE;
+	const ERASE_VALUE: u8 = ERASED;
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		check_erase(self, from, to).map_err(MockFlashError::from_check)?;
+		let (from, to) = (from as usize, to as usize);
+		self.data[from..to].fill(ERASED);
+		self.written[from..to].fill(false);
+		Ok(())
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		check_write(self, offset, bytes.len()).map_err(MockFlashError::from_check)?;
+		let range = offset as usize..offset as usize + bytes.len();
+		// Writes are aligned to `WRITE`, so tracking individual bytes is the same as tracking words
+		if self.written[range.clone()].iter().any(|written| *written) {
+			return Err(MockFlashError::NotErased);
+		}
+		self.data[range.clone()].copy_from_slice(bytes);
+		self.written[range].fill(true);
+		Ok(())
+	}
+}
+
+/// In-memory [`MultiwriteNorFlash`], for testing code relying on writing to the same word
+/// multiple times.
+///
+/// Like [`MockFlash`], except that writing to a word which was already written stores the
+/// logical AND of the previous and the written data, or the logical OR if `ERASED` is `0x00`, as
+/// described on [`MultiwriteNorFlash`].
+///
+/// Real flashes silently ignore attempts to change a bit back to its erased state. With
+/// [`set_strict_bit_transitions`](Self::set_strict_bit_transitions), such writes are rejected
+/// with [`MockFlashError::InvalidBitTransition`] instead, leaving the flash unchanged.
+#[derive(Debug, Clone)]
+pub struct MultiwriteMockFlash<
+	const CAPACITY: usize,
+	const READ: usize,
+	const WRITE: usize,
+	const ERASE: usize,
+	const ERASED: u8 = 0xff,
+> {
+	flash: MockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>,
+	strict: bool,
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> MultiwriteMockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	/// Create a new, fully erased flash, with strict bit transitions disabled.
+	pub fn new() -> Self {
+		Self {
+			flash: MockFlash::new(),
+			strict: false,
+		}
+	}
+
+	/// Enable or disable rejecting writes which try to change a bit back to its erased state.
+	pub fn set_strict_bit_transitions(&mut self, strict: bool) {
+		self.strict = strict;
+	}
+
+	/// The raw contents of the flash.
+	pub fn as_bytes(&self) -> &[u8] {
+		self.flash.as_bytes()
+	}
+
+	/// Mutable access to the raw contents of the flash.
+	///
+	/// Changes made this way bypass all checks.
+	pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+		self.flash.as_bytes_mut()
+	}
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> Default for MultiwriteMockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> AsMut<[u8]> for MultiwriteMockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	fn as_mut(&mut self) -> &mut [u8] {
+		self.as_bytes_mut()
+	}
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> ErrorType for MultiwriteMockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	type Error = MockFlashError;
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> ReadNorFlash for MultiwriteMockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	const READ_SIZE: usize = READ;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.flash.read(offset, bytes)
+	}
+
+	fn capacity(&self) -> usize {
+		CAPACITY
+	}
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> NorFlash for MultiwriteMockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+	const WRITE_SIZE: usize = WRITE;
+	const ERASE_SIZE: usize = ERASE;
+	const ERASE_VALUE: u8 = ERASED;
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.flash.erase(from, to)
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		check_write(self, offset, bytes.len()).map_err(MockFlashError::from_check)?;
+		let range = offset as usize..offset as usize + bytes.len();
+		let data = &mut self.flash.data[range.clone()];
+		// Flip the bits so that erased bits are 1, as on a flash erasing to 0xff
+		let flip = !ERASED;
+		if self.strict
+			&& data
+				.iter()
+				.zip(bytes)
+				.any(|(old, new)| !(*old ^ flip) & (*new ^ flip) != 0)
+		{
+			return Err(MockFlashError::InvalidBitTransition);
+		}
+		data.iter_mut()
+			.zip(bytes)
+			.for_each(|(old, new)| *old = ((*old ^ flip) & (*new ^ flip)) ^ flip);
+		self.flash.written[range].fill(true);
+		Ok(())
+	}
+}
+
+impl<
+		const CAPACITY: usize,
+		const READ: usize,
+		const WRITE: usize,
+		const ERASE: usize,
+		const ERASED: u8,
+	> MultiwriteNorFlash for MultiwriteMockFlash<CAPACITY, READ, WRITE, ERASE, ERASED>
+{
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rejects_misaligned_and_out_of_bounds_accesses() {
+		let mut flash = MockFlash::<16, 2, 4, 8>::new();
+		assert_eq!(flash.read(1, &mut [0; 2]), Err(MockFlashError::NotAligned));
+		assert_eq!(
+			flash.read(16, &mut [0; 2]),
+			Err(MockFlashError::OutOfBounds)
+		);
+		assert_eq!(flash.write(2, &[0; 4]), Err(MockFlashError::NotAligned));
+		assert_eq!(flash.write(12, &[0; 8]), Err(MockFlashError::OutOfBounds));
+		assert_eq!(flash.erase(0, 4), Err(MockFlashError::NotAligned));
+		assert_eq!(flash.erase(8, 24), Err(MockFlashError::OutOfBounds));
+		assert_eq!(flash.as_bytes(), [0xff; 16]);
+	}
+
+	#[test]
+	fn words_are_written_once_between_erases() {
+		let mut flash = MockFlash::<16, 1, 4, 8>::new();
+		flash.write(4, &[0x12, 0x34, 0x56, 0x78]).unwrap();
+		assert_eq!(flash.write(4, &[0; 4]), Err(MockFlashError::NotErased));
+		// Even if the data would not change
+		assert_eq!(flash.write(0, &[0xff; 8]), Err(MockFlashError::NotErased));
+		flash.write(8, &[0; 4]).unwrap();
+
+		let mut data = [0; 12];
+		flash.read(0, &mut data).unwrap();
+		assert_eq!(
+			data,
+			[0xff, 0xff, 0xff, 0xff, 0x12, 0x34, 0x56, 0x78, 0, 0, 0, 0]
+		);
+
+		flash.erase(0, 8).unwrap();
+		flash.write(4, &[0; 4]).unwrap();
+		assert_eq!(flash.write(8, &[0; 4]), Err(MockFlashError::NotErased));
+		assert_eq!(flash.as_bytes()[..8], [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]);
+	}
+
+	#[test]
+	fn erases_to_the_erase_value() {
+		let mut flash = MockFlash::<16, 1, 1, 8, 0x00>::new();
+		assert_eq!(flash.as_bytes(), [0; 16]);
+		flash.write(3, &[0x5a]).unwrap();
+		flash.erase(0, 8).unwrap();
+		assert_eq!(flash.as_bytes(), [0; 16]);
+	}
+
+	#[test]
+	fn multiwrite_clears_bits_away_from_the_erase_value() {
+		let mut flash = MultiwriteMockFlash::<8, 1, 2, 8>::new();
+		flash.write(0, &[0x0f, 0x3c]).unwrap();
+		flash.write(0, &[0xf3, 0x3c]).unwrap();
+		assert_eq!(flash.as_bytes()[..2], [0x03, 0x3c]);
+
+		let mut flash = MultiwriteMockFlash::<8, 1, 2, 8, 0x00>::new();
+		flash.write(0, &[0x0f, 0x3c]).unwrap();
+		flash.write(0, &[0xf3, 0x3c]).unwrap();
+		assert_eq!(flash.as_bytes()[..2], [0xff, 0x3c]);
+
+		flash.erase(0, 8).unwrap();
+		assert_eq!(flash.as_bytes(), [0; 8]);
+	}
+
+	#[test]
+	fn multiwrite_tracks_written_words() {
+		let mut flash = MultiwriteMockFlash::<8, 1, 2, 4>::new();
+		flash.write(2, &[0x0f; 4]).unwrap();
+		flash.write(2, &[0x0f; 2]).unwrap();
+		assert_eq!(
+			flash.flash.written,
+			[false, false, true, true, true, true, false, false]
+		);
+
+		flash.erase(0, 4).unwrap();
+		assert_eq!(flash.flash.written[..4], [false; 4]);
+		assert_eq!(flash.flash.written[4..6], [true; 2]);
+
+		// Failed writes mark nothing
+		assert_eq!(flash.write(1, &[0; 2]), Err(MockFlashError::NotAligned));
+		assert_eq!(flash.flash.written[..4], [false; 4]);
+	}
+
+	#[test]
+	fn strict_bit_transitions_reject_erasing_bits() {
+		let mut flash = MultiwriteMockFlash::<8, 1, 2, 8>::new();
+		flash.set_strict_bit_transitions(true);
+		flash.write(0, &[0x0f, 0xff]).unwrap();
+		flash.write(0, &[0x03, 0xf0]).unwrap();
+		assert_eq!(
+			flash.write(0, &[0x07, 0x00]),
+			Err(MockFlashError::InvalidBitTransition)
+		);
+		assert_eq!(flash.as_bytes()[..2], [0x03, 0xf0]);
+
+		flash.set_strict_bit_transitions(false);
+		flash.write(0, &[0x07, 0x00]).unwrap();
+		assert_eq!(flash.as_bytes()[..2], [0x03, 0x00]);
+	}
+}
@@ -0,0 +1,364 @@
+use crate::nor_flash::NorFlash;
+
+/// NOR flash trait for flashes with erase commands of several sizes, like the 4K, 32K and 64K
+/// erases of most SPI NOR flashes.
+///
+/// Large erases are much faster than the equivalent sequence of small ones. Implementations can
+/// erase any range with the fewest commands by forwarding [`NorFlash::erase`] to
+/// [`erase_planned`], after checking the range with
+/// [`check_erase`](super::check_erase).
+///
+/// # Example
+///
+/// ```
+/// use embedded_storage::nor_flash::{
+///     check_erase, check_read, erase_planned, ErrorType, MultiEraseNorFlash, NorFlash,
+///     NorFlashErrorKind, ReadNorFlash,
+/// };
+///
+/// /// A 256K flash counting the erase commands it receives, by size.
+/// #[derive(Default)]
+/// struct CountingFlash {
+///     sectors: usize,
+///     blocks_32k: usize,
+///     blocks_64k: usize,
+///     chips: usize,
+/// }
+///
+/// impl ErrorType for CountingFlash {
+///     type Error = NorFlashErrorKind;
+/// }
+///
+/// impl ReadNorFlash for CountingFlash {
+///     const READ_SIZE: usize = 1;
+///
+///     fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+///         check_read(self, offset, bytes.len())?;
+///         bytes.fill(0xff);
+///         Ok(())
+///     }
+///
+///     fn capacity(&self) -> usize {
+///         256 * 1024
+///     }
+/// }
+///
+/// impl NorFlash for CountingFlash {
+///     const WRITE_SIZE: usize = 1;
+///     const ERASE_SIZE: usize = 4096;
+///
+///     fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+///         check_erase(self, from, to)?;
+///         erase_planned(self, from, to)
+///     }
+///
+///     fn write(&mut self, _offset: u32, _bytes: &[u8]) -> Result<(), Self::Error> {
+///         Ok(())
+///     }
+/// }
+///
+/// impl MultiEraseNorFlash for CountingFlash {
+///     const ERASE_SIZES: &'static [usize] = &[4096, 32 * 1024, 64 * 1024];
+///     const CHIP_ERASE: bool = true;
+///
+///     fn erase_block(&mut self, offset: u32, size: usize) -> Result<(), Self::Error> {
+///         assert_eq!(offset as usize % size, 0);
+///         match size {
+///             4096 => self.sectors += 1,
+///             0x8000 => self.blocks_32k += 1,
+///             0x10000 => self.blocks_64k += 1,
+///             _ => self.chips += 1,
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// // 4K up to the 32K boundary, 32K up to the 64K boundary, 64K blocks, then back down
+/// let mut flash = CountingFlash::default();
+/// flash.erase(0x7000, 0x2_9000).unwrap();
+/// assert_eq!(
+///     (flash.sectors, flash.blocks_32k, flash.blocks_64k, flash.chips),
+///     (2, 2, 1, 0),
+/// );
+///
+/// // The whole flash is a single chip erase
+/// let mut flash = CountingFlash::default();
+/// flash.erase(0, 0x4_0000).unwrap();
+/// assert_eq!(
+///     (flash.sectors, flash.blocks_32k, flash.blocks_64k, flash.chips),
+///     (0, 0, 0, 1),
+/// );
+///
+/// // A misaligned range is rejected before reaching `erase_block`
+/// assert_eq!(flash.erase(0x800, 0x1000), Err(NorFlashErrorKind::NotAligned));
+/// ```
+pub trait MultiEraseNorFlash: NorFlash {
+	/// The sizes of the supported erase commands in ascending order, starting with
+	/// [`NorFlash::ERASE_SIZE`]. Every size must be a multiple of the previous one.
+	const ERASE_SIZES: &'static [usize];
+
+	/// Whether the whole flash can be erased with a single command.
+	const CHIP_ERASE: bool;
+
+	/// Erase `size` bytes at `offset` with a single command.
+	///
+	/// `size` is one of [`ERASE_SIZES`](Self::ERASE_SIZES) and `offset` a multiple of it, or, if
+	/// [`CHIP_ERASE`](Self::CHIP_ERASE) is set, `offset` is 0 and `size` the capacity for a chip
+	/// erase.
+	///
+	/// If power is lost during erase, contents of the erased range are undefined.
+	fn erase_block(&mut self, offset: u32, size: usize) -> Result<(), Self::Error>;
+}
+
+impl<T: MultiEraseNorFlash> MultiEraseNorFlash for &mut T {
+	const ERASE_SIZES: &'static [usize] = T::ERASE_SIZES;
+	const CHIP_ERASE: bool = T::CHIP_ERASE;
+
+	fn erase_block(&mut self, offset: u32, size: usize) -> Result<(), Self::Error> {
+		T::erase_block(self, offset, size)
+	}
+}
+
+/// Iterator producing the fewest, largest erase commands covering a range, as offset-size pairs.
+///
+/// At every offset, the largest erase size to which the offset is aligned, and which does not go
+/// past the end of the range, is chosen. As every erase size is a multiple of the previous one,
+/// no other sequence of commands is shorter.
+///
+/// # Example
+///
+/// ```
+/// use embedded_storage::nor_flash::EraseOps;
+///
+/// let sizes = [0x1000, 0x8000, 0x1_0000];
+///
+/// // Without chip erase, a whole 256K flash takes four 64K erases
+/// let ops = EraseOps::new(&sizes, None, 0, 0x4_0000);
+/// let offsets = ops.map(|(offset, size)| {
+///     assert_eq!(size, 0x1_0000);
+///     offset
+/// });
+/// assert!(offsets.eq([0, 0x1_0000, 0x2_0000, 0x3_0000].iter().copied()));
+///
+/// // With it, a single command
+/// let ops = EraseOps::new(&sizes, Some(0x4_0000), 0, 0x4_0000);
+/// assert!(ops.eq([(0, 0x4_0000)].iter().copied()));
+///
+/// // Smaller erases cover the unaligned ends
+/// let ops = EraseOps::new(&sizes, Some(0x4_0000), 0xf000, 0x1_9000);
+/// let expected = [(0xf000, 0x1000), (0x1_0000, 0x8000), (0x1_8000, 0x1000)];
+/// assert!(ops.eq(expected.iter().copied()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct EraseOps<'a> {
+	sizes: &'a [usize],
+	chip: Option<usize>,
+	from: u32,
+	to: u32,
+}
+
+impl<'a> EraseOps<'a> {
+	/// Plan the erase of `from..to` with the erase `sizes`, given in ascending order, each size
+	/// being a multiple of the previous one. `chip` is the capacity of the flash if it supports
+	/// chip erase.
+	///
+	/// **NOTE** This will panic if `sizes` is empty, or if the range is not aligned to the
+	/// smallest erase size
+	pub fn new(sizes: &'a [usize], chip: Option<usize>, from: u32, to: u32) -> Self {
+		let smallest = match sizes.first() {
+			Some(size) => *size,
+			None => panic!("No erase size"),
+		};
+		if from as usize % smallest != 0 || to as usize % smallest != 0 {
+			panic!("Erase range is not aligned to the smallest erase size");
+		}
+		Self {
+			sizes,
+			chip,
+			from,
+			to,
+		}
+	}
+}
+
+impl<'a> Iterator for EraseOps<'a> {
+	type Item = (u32, usize);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.from >= self.to {
+			return None;
+		}
+		let left = (self.to - self.from) as usize;
+		let size = match self.chip {
+			Some(capacity) if self.from == 0 && left == capacity => capacity,
+			_ => *self
+				.sizes
+				.iter()
+				.rev()
+				// The smallest size always fits, as the range is aligned to it
+				.find(|size| self.from as usize % **size == 0 && **size <= left)?,
+		};
+		let offset = self.from;
+		self.from += size as u32;
+		Some((offset, size))
+	}
+}
+
+/// Return whether `sizes` starts with `erase_size`, every size being a multiple of the previous
+/// one.
+const fn valid_erase_sizes(sizes: &[usize], erase_size: usize) -> bool {
+	if sizes.is_empty() || sizes[0] != erase_size || erase_size == 0 {
+		return false;
+	}
+	let mut i = 1;
+	while i < sizes.len() {
+		if sizes[i] % sizes[i - 1] != 0 {
+			return false;
+		}
+		i += 1;
+	}
+	true
+}
+
+/// Compile time check of the erase sizes of a [`MultiEraseNorFlash`].
+trait EraseSizes {
+	const GEOMETRY: ();
+}
+
+impl<T: MultiEraseNorFlash> EraseSizes for T {
+	const GEOMETRY: () = assert!(
+		valid_erase_sizes(T::ERASE_SIZES, T::ERASE_SIZE),
+		"Erase sizes must start with the erase size, each being a multiple of the previous one"
+	);
+}
+
+/// Plan the erase of `from..to` on `flash`, which must be aligned to its erase size.
+///
+/// Flashes whose [`ERASE_SIZES`](MultiEraseNorFlash::ERASE_SIZES) do not start with their erase
+/// size, or are not multiples of each other, fail to compile.
+///
+/// **NOTE** This will panic if the range is not aligned to the erase size
+pub fn plan_erase<T: MultiEraseNorFlash>(flash: &T, from: u32, to: u32) -> EraseOps<'static> {
+	let () = <T as EraseSizes>::GEOMETRY;
+
+	let chip = if T::CHIP_ERASE {
+		Some(flash.capacity())
+	} else {
+		None
+	};
+	EraseOps::new(T::ERASE_SIZES, chip, from, to)
+}
+
+/// Erase `from..to` on `flash` with the fewest commands, as planned by [`plan_erase`].
+///
+/// The range must be aligned to the erase size of the flash, which can be checked with the
+/// [`check_erase`](super::check_erase) helper function.
+pub fn erase_planned<T: MultiEraseNorFlash>(
+	flash: &mut T,
+	from: u32,
+	to: u32,
+) -> Result<(), T::Error> {
+	for (offset, size) in plan_erase(flash, from, to) {
+		flash.erase_block(offset, size)?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::nor_flash::{check_erase, ErrorType, NorFlashErrorKind, ReadNorFlash};
+
+	/// A 256K flash recording the erase commands it receives.
+	#[derive(Default)]
+	struct CountingFlash {
+		erases: [(u32, usize); 8],
+		count: usize,
+	}
+
+	impl CountingFlash {
+		fn erases(&self) -> &[(u32, usize)] {
+			&self.erases[..self.count]
+		}
+	}
+
+	impl ErrorType for CountingFlash {
+		type Error = NorFlashErrorKind;
+	}
+
+	impl ReadNorFlash for CountingFlash {
+		const READ_SIZE: usize = 1;
+
+		fn read(&mut self, _offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			bytes.fill(0xff);
+			Ok(())
+		}
+
+		fn capacity(&self) -> usize {
+			0x4_0000
+		}
+	}
+
+	impl NorFlash for CountingFlash {
+		const WRITE_SIZE: usize = 1;
+		const ERASE_SIZE: usize = 0x1000;
+
+		fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+			check_erase(self, from, to)?;
+			erase_planned(self, from, to)
+		}
+
+		fn write(&mut self, _offset: u32, _bytes: &[u8]) -> Result<(), Self::Error> {
+			Ok(())
+		}
+	}
+
+	impl MultiEraseNorFlash for CountingFlash {
+		const ERASE_SIZES: &'static [usize] = &[0x1000, 0x8000, 0x1_0000];
+		const CHIP_ERASE: bool = true;
+
+		fn erase_block(&mut self, offset: u32, size: usize) -> Result<(), Self::Error> {
+			assert_eq!(offset as usize % size, 0);
+			self.erases[self.count] = (offset, size);
+			self.count += 1;
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn erases_with_the_fewest_commands() {
+		let mut flash = CountingFlash::default();
+		flash.erase(0x7000, 0x2_9000).unwrap();
+		assert_eq!(
+			flash.erases(),
+			[
+				(0x7000, 0x1000),
+				(0x8000, 0x8000),
+				(0x1_0000, 0x1_0000),
+				(0x2_0000, 0x8000),
+				(0x2_8000, 0x1000),
+			]
+		);
+
+		let mut flash = CountingFlash::default();
+		flash.erase(0, 0x4_0000).unwrap();
+		assert_eq!(flash.erases(), [(0, 0x4_0000)]);
+
+		let mut flash = CountingFlash::default();
+		flash.erase(0x1000, 0x1000).unwrap();
+		assert_eq!(flash.erases(), []);
+	}
+
+	#[test]
+	#[should_panic(expected = "Erase range is not aligned to the smallest erase size")]
+	fn misaligned_ranges_are_rejected() {
+		let mut flash = CountingFlash::default();
+		let _ = erase_planned(&mut flash, 0x8000, 0x1_0800);
+	}
+
+	#[test]
+	#[should_panic(expected = "No erase size")]
+	fn empty_erase_sizes_are_rejected() {
+		EraseOps::new(&[], None, 0, 0x1000);
+	}
+}
@@ -0,0 +1,223 @@
+use crate::nor_flash::{
+	check_erase, check_read, check_write, ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError,
+	NorFlashErrorKind, ReadNorFlash,
+};
+
+/// Errors returned by [`Partition`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum PartitionError<E> {
+	/// The arguments are not properly aligned, or out of the bounds of the partition.
+	Partition(NorFlashErrorKind),
+
+	/// The underlying flash returned an error.
+	Flash(E),
+}
+
+impl<E: NorFlashError> NorFlashError for PartitionError<E> {
+	fn kind(&self) -> NorFlashErrorKind {
+		match self {
+			Self::Partition(kind) => *kind,
+			Self::Flash(e) => e.kind(),
+		}
+	}
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for PartitionError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::Partition(kind) => kind.fmt(f),
+			Self::Flash(e) => e.fmt(f),
+		}
+	}
+}
+
+/// A sub-range of a NOR flash, exposed as a NOR flash of its own.
+///
+/// Addresses are relative to the start of the partition, and any access outside of the
+/// partition is rejected with [`NorFlashErrorKind::OutOfBounds`] before reaching the underlying
+/// flash. The underlying flash can be owned, or borrowed through `&mut`.
+#[derive(Debug)]
+pub struct Partition<F> {
+	flash: F,
+	offset: u32,
+	size: usize,
+}
+
+impl<F> Partition<F>
+where
+	F: NorFlash,
+{
+	/// Create a partition of `size` bytes, starting at `offset` in `flash`.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `offset` and `size` are not multiples of the erase size of `flash`, or
+	/// if the partition does not fit in `flash`.
+	pub fn new(flash: F, offset: u32, size: usize) -> Result<Self, NorFlashErrorKind> {
+		let end = (offset as usize)
+			.checked_add(size)
+			.filter(|end| *end <= u32::MAX as usize)
+			.ok_or(NorFlashErrorKind::OutOfBounds)?;
+		check_erase(&flash, offset, end as u32)?;
+		Ok(Self {
+			flash,
+			offset,
+			size,
+		})
+	}
+
+	/// Create a partition which is already known to fit in `flash`.
+	pub(crate) fn new_unchecked(flash: F, offset: u32, size: usize) -> Self {
+		Self {
+			flash,
+			offset,
+			size,
+		}
+	}
+
+	/// The offset of the partition in the underlying flash.
+	pub fn offset(&self) -> u32 {
+		self.offset
+	}
+
+	/// Release the underlying flash.
+	pub fn into_inner(self) -> F {
+		self.flash
+	}
+}
+
+impl<F> ErrorType for Partition<F>
+where
+	F: ErrorType,
+{
+	type Error = PartitionError<F::Error>;
+}
+
+impl<F> ReadNorFlash for Partition<F>
+where
+	F: NorFlash,
+{
+	const READ_SIZE: usize = F::READ_SIZE;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		check_read(self, offset, bytes.len()).map_err(PartitionError::Partition)?;
+		self.flash
+			.read(self.offset + offset, bytes)
+			.map_err(PartitionError::Flash)
+	}
+
+	fn capacity(&self) -> usize {
+		self.size
+	}
+}
+
+impl<F> NorFlash for Partition<F>
+where
+	F: NorFlash,
+{
+	const WRITE_SIZE: usize = F::WRITE_SIZE;
+	const ERASE_SIZE: usize = F::ERASE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = F::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = F::ERASE_VALUE;
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		check_erase(self, from, to).map_err(PartitionError::Partition)?;
+		self.flash
+			.erase(self.offset + from, self.offset + to)
+			.map_err(PartitionError::Flash)
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		check_write(self, offset, bytes.len()).map_err(PartitionError::Partition)?;
+		self.flash
+			.write(self.offset + offset, bytes)
+			.map_err(PartitionError::Flash)
+	}
+}
+
+impl<F> MultiwriteNorFlash for Partition<F> where F: MultiwriteNorFlash {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::nor_flash::{MockFlash, MockFlashError};
+
+	type Flash = MockFlash<32, 1, 4, 8>;
+
+	#[test]
+	fn rejects_partitions_not_fitting_the_flash() {
+		let mut flash = Flash::new();
+		assert_eq!(
+			Partition::new(&mut flash, 4, 8).err(),
+			Some(NorFlashErrorKind::NotAligned)
+		);
+		assert_eq!(
+			Partition::new(&mut flash, 8, 12).err(),
+			Some(NorFlashErrorKind::NotAligned)
+		);
+		assert_eq!(
+			Partition::new(&mut flash, 24, 16).err(),
+			Some(NorFlashErrorKind::OutOfBounds)
+		);
+		assert_eq!(
+			Partition::new(&mut flash, u32::MAX - 7, 16).err(),
+			Some(NorFlashErrorKind::OutOfBounds)
+		);
+	}
+
+	#[test]
+	fn translates_offsets() {
+		let mut flash = Flash::new();
+		let mut partition = Partition::new(&mut flash, 8, 16).unwrap();
+		assert_eq!(partition.offset(), 8);
+		assert_eq!(partition.capacity(), 16);
+
+		partition.write(0, &[1, 2, 3, 4]).unwrap();
+		partition.write(12, &[5, 6, 7, 8]).unwrap();
+		let mut data = [0; 16];
+		partition.read(0, &mut data).unwrap();
+		assert_eq!(data[..4], [1, 2, 3, 4]);
+		assert_eq!(data[12..], [5, 6, 7, 8]);
+
+		partition.erase(8, 16).unwrap();
+		assert_eq!(flash.as_bytes()[..8], [0xff; 8]);
+		assert_eq!(flash.as_bytes()[8..12], [1, 2, 3, 4]);
+		assert_eq!(flash.as_bytes()[12..], [0xff; 20]);
+	}
+
+	#[test]
+	fn rejects_accesses_beyond_both_edges() {
+		let mut flash = Flash::new();
+		let mut partition = Partition::new(&mut flash, 8, 16).unwrap();
+		let out_of_bounds = Err(PartitionError::Partition(NorFlashErrorKind::OutOfBounds));
+
+		assert_eq!(partition.read(15, &mut [0; 2]), out_of_bounds);
+		assert_eq!(partition.read(u32::MAX, &mut [0; 1]), out_of_bounds);
+		assert_eq!(partition.write(16, &[0; 4]), out_of_bounds);
+		assert_eq!(partition.write(12, &[0; 8]), out_of_bounds);
+		assert_eq!(partition.erase(8, 24), out_of_bounds);
+		assert_eq!(partition.erase(8, 0), out_of_bounds);
+		assert_eq!(
+			partition.erase(0, 4),
+			Err(PartitionError::Partition(NorFlashErrorKind::NotAligned))
+		);
+		assert_eq!(
+			partition.write(2, &[0; 4]),
+			Err(PartitionError::Partition(NorFlashErrorKind::NotAligned))
+		);
+
+		// Nothing reached the flash, not even the neighbouring erase units
+		assert_eq!(flash.as_bytes(), [0xff; 32]);
+	}
+
+	#[test]
+	fn forwards_flash_errors() {
+		let mut flash = Flash::new();
+		let mut partition = Partition::new(&mut flash, 8, 16).unwrap();
+		partition.write(4, &[0; 4]).unwrap();
+
+		let error = partition.write(4, &[0; 4]).unwrap_err();
+		assert_eq!(error, PartitionError::Flash(MockFlashError::NotErased));
+		assert_eq!(error.kind(), NorFlashErrorKind::Other);
+	}
+}
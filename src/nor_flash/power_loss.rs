@@ -0,0 +1,396 @@
+use crate::nor_flash::{
+	check_erase, check_write, ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError,
+	NorFlashErrorKind, ReadNorFlash,
+};
+
+/// Largest write size supported by [`PowerLossFlash`], which buffers the corrupted word.
+const MAX_WRITE_SIZE: usize = 256;
+
+/// Errors returned by [`PowerLossFlash`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum PowerLossError<E> {
+	/// Power was lost, the operation was aborted.
+	PowerLoss,
+
+	/// The wrapped flash returned an error.
+	Flash(E),
+}
+
+impl<E: NorFlashError> NorFlashError for PowerLossError<E> {
+	fn kind(&self) -> NorFlashErrorKind {
+		match self {
+			Self::PowerLoss => NorFlashErrorKind::Other,
+			Self::Flash(e) => e.kind(),
+		}
+	}
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for PowerLossError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::PowerLoss => write!(f, "Power was lost during the operation"),
+			Self::Flash(e) => e.fmt(f),
+		}
+	}
+}
+
+/// Fault injecting wrapper, simulating a power loss in the middle of a write or erase operation.
+///
+/// Every call to [`NorFlash::write`] and [`NorFlash::erase`] counts as an operation. Once the
+/// configured number of operations has completed, the next one is interrupted and leaves the
+/// flash in one of the states allowed by the documentation of the interrupted operation:
+///
+/// - An interrupted write completes a random number of words. The next word is corrupted, the
+///   remaining words are left untouched. The corrupted word is written through the wrapped flash,
+///   so that it counts as written.
+/// - An interrupted erase erases a random number of pages. The next page is corrupted, the
+///   remaining pages are left untouched.
+///
+/// Corruption only ever moves bits to or from [`NorFlash::ERASE_VALUE`] in ways permitted by
+/// [`NorFlash`] and [`MultiwriteNorFlash`], so the wrapper implements [`MultiwriteNorFlash`] if
+/// the wrapped flash does. The wrapped flash has to give raw access to its contents through
+/// [`AsMut<[u8]>`], as the in-memory flashes like [`MockFlash`](super::MockFlash) do, and its
+/// write size can be at most 256 bytes.
+///
+/// After the power loss, all operations fail with [`PowerLossError::PowerLoss`], until power is
+/// restored with [`restore_power`](Self::restore_power).
+#[derive(Debug, Clone)]
+pub struct PowerLossFlash<F> {
+	flash: F,
+	remaining: Option<usize>,
+	lost: bool,
+	operations: usize,
+	rng: u32,
+}
+
+impl<F> PowerLossFlash<F>
+where
+	F: NorFlash + AsMut<[u8]>,
+{
+	const WORD: () = assert!(
+		F::WRITE_SIZE <= MAX_WRITE_SIZE,
+		"Write size is too large to simulate power loss"
+	);
+
+	/// Wrap `flash`, without scheduling any power loss.
+	pub fn new(flash: F) -> Self {
+		let () = Self::WORD;
+
+		Self {
+			flash,
+			remaining: None,
+			lost: false,
+			operations: 0,
+			rng: 0x2545_f491,
+		}
+	}
+
+	/// Seed the pseudo random generator deciding how interrupted operations corrupt the flash.
+	pub fn set_seed(&mut self, seed: u32) {
+		// Xorshift gets stuck on zero
+		self.rng = if seed == 0 { 0x2545_f491 } else { seed };
+	}
+
+	/// Lose power during the next write or erase, after `operations` more have completed.
+	pub fn set_power_loss_after(&mut self, operations: usize) {
+		self.remaining = Some(operations);
+	}
+
+	/// Lose power at a random point during the next `max_operations` writes or erases.
+	pub fn set_random_power_loss(&mut self, max_operations: usize) {
+		let operations = self.random() as usize % max_operations.max(1);
+		self.set_power_loss_after(operations);
+	}
+
+	/// Whether power was lost.
+	pub fn has_lost_power(&self) -> bool {
+		self.lost
+	}
+
+	/// Number of writes and erases which completed.
+	pub fn operations(&self) -> usize {
+		self.operations
+	}
+
+	/// Restore power after a power loss, without scheduling a new one.
+	pub fn restore_power(&mut self) {
+		self.remaining = None;
+		self.lost = false;
+	}
+
+	/// Get a reference to the wrapped flash.
+	pub fn inner(&self) -> &F {
+		&self.flash
+	}
+
+	/// Get a mutable reference to the wrapped flash.
+	pub fn inner_mut(&mut self) -> &mut F {
+		&mut self.flash
+	}
+
+	/// Unwrap the wrapped flash.
+	pub fn into_inner(self) -> F {
+		self.flash
+	}
+
+	fn random(&mut self) -> u32 {
+		self.rng ^= self.rng << 13;
+		self.rng ^= self.rng >> 17;
+		self.rng ^= self.rng << 5;
+		self.rng
+	}
+
+	/// Count an operation, returning whether it gets interrupted.
+	fn interrupt(&mut self) -> Result<bool, PowerLossError<F::Error>> {
+		if self.lost {
+			return Err(PowerLossError::PowerLoss);
+		}
+		match self.remaining {
+			Some(0) => {
+				self.lost = true;
+				Ok(true)
+			}
+			Some(ref mut remaining) => {
+				*remaining -= 1;
+				self.operations += 1;
+				Ok(false)
+			}
+			None => {
+				self.operations += 1;
+				Ok(false)
+			}
+		}
+	}
+}
+
+impl<F> ErrorType for PowerLossFlash<F>
+where
+	F: ErrorType,
+{
+	type Error = PowerLossError<F::Error>;
+}
+
+impl<F> ReadNorFlash for PowerLossFlash<F>
+where
+	F: NorFlash + AsMut<[u8]>,
+{
+	const READ_SIZE: usize = F::READ_SIZE;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		if self.lost {
+			return Err(PowerLossError::PowerLoss);
+		}
+		self.flash
+			.read(offset, bytes)
+			.map_err(PowerLossError::Flash)
+	}
+
+	fn capacity(&self) -> usize {
+		self.flash.capacity()
+	}
+}
+
+impl<F> NorFlash for PowerLossFlash<F>
+where
+	F: NorFlash + AsMut<[u8]>,
+{
+	const WRITE_SIZE: usize = F::WRITE_SIZE;
+	const ERASE_SIZE: usize = F::ERASE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = F::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = F::ERASE_VALUE;
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		// Invalid operations are left for the wrapped flash to reject
+		if check_erase(self, from, to).is_err() || !self.interrupt()? {
+			return self.flash.erase(from, to).map_err(PowerLossError::Flash);
+		}
+
+		let pages = ((to - from) as usize / F::ERASE_SIZE) as u32;
+		let erased = self.random() % (pages + 1);
+		let split = from + erased * F::ERASE_SIZE as u32;
+		self.flash
+			.erase(from, split)
+			.map_err(PowerLossError::Flash)?;
+
+		if erased < pages {
+			// A partially erased page holds its old contents with some bits erased
+			let page = split as usize..split as usize + F::ERASE_SIZE;
+			for i in page {
+				let noise = self.random() as u8;
+				let byte = &mut self.flash.as_mut()[i];
+				*byte = (*byte & !noise) | (F::ERASE_VALUE & noise);
+			}
+		}
+		Err(PowerLossError::PowerLoss)
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		// Invalid operations are left for the wrapped flash to reject
+		if check_write(self, offset, bytes.len()).is_err() || !self.interrupt()? {
+			return self
+				.flash
+				.write(offset, bytes)
+				.map_err(PowerLossError::Flash);
+		}
+
+		let words = bytes.len() / F::WRITE_SIZE;
+		let written = self.random() as usize % (words + 1);
+		let split = written * F::WRITE_SIZE;
+		self.flash
+			.write(offset, &bytes[..split])
+			.map_err(PowerLossError::Flash)?;
+
+		if written < words {
+			// Bits being programmed by the write are undefined, all other bits keep their value
+			let start = offset as usize + split;
+			let mut torn = [0; MAX_WRITE_SIZE];
+			let torn = &mut torn[..F::WRITE_SIZE];
+			for (i, (torn, new)) in torn.iter_mut().zip(&bytes[split..]).enumerate() {
+				let old = self.flash.as_mut()[start + i];
+				let programmed = !(old ^ F::ERASE_VALUE) & (new ^ F::ERASE_VALUE);
+				let noise = self.random() as u8;
+				*torn = old ^ (programmed & noise);
+			}
+			self.flash
+				.write(start as u32, torn)
+				.map_err(PowerLossError::Flash)?;
+		}
+		Err(PowerLossError::PowerLoss)
+	}
+}
+
+impl<F> MultiwriteNorFlash for PowerLossFlash<F> where F: MultiwriteNorFlash + AsMut<[u8]> {}
+
+/// Run `scenario` once for every point at which power could be lost, and check the outcome.
+///
+/// For every cut point, a fresh flash is created with `setup`, wrapped in a [`PowerLossFlash`]
+/// losing power after that many writes and erases, and handed to `scenario`. Once power was lost,
+/// the flash is passed to `verify` together with the cut point, typically to run recovery code
+/// and assert its invariants. This repeats until `scenario` completes without losing power.
+///
+/// Returns the number of cut points which were exercised.
+pub fn simulate_power_loss<F, T, E>(
+	mut setup: impl FnMut() -> F,
+	mut scenario: impl FnMut(&mut PowerLossFlash<F>) -> Result<T, E>,
+	mut verify: impl FnMut(usize, F),
+) -> usize
+where
+	F: NorFlash + AsMut<[u8]>,
+{
+	let mut cut = 0;
+	loop {
+		let mut flash = PowerLossFlash::new(setup());
+		flash.set_seed(cut as u32 + 1);
+		flash.set_power_loss_after(cut);
+		// Errors are expected once power is lost, the outcome is up to `verify`
+		let _ = scenario(&mut flash);
+		if !flash.has_lost_power() {
+			return cut;
+		}
+		verify(cut, flash.into_inner());
+		cut += 1;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		nor_flash::{MockFlash, MockFlashError, ScratchRmwNorFlashStorage},
+		ReadStorage, Storage,
+	};
+
+	type Flash = MockFlash<64, 1, 4, 16>;
+
+	#[test]
+	fn torn_words_count_as_written() {
+		for seed in 1..16 {
+			let mut flash = PowerLossFlash::new(Flash::new());
+			flash.set_seed(seed);
+			flash.set_power_loss_after(0);
+			assert_eq!(flash.write(0, &[0x0f; 4]), Err(PowerLossError::PowerLoss));
+			assert!(flash.has_lost_power());
+
+			flash.restore_power();
+			let mut word = [0; 4];
+			flash.read(0, &mut word).unwrap();
+			assert!(word.iter().all(|byte| *byte & 0x0f == 0x0f));
+			assert_eq!(
+				flash.write(0, &[0; 4]),
+				Err(PowerLossError::Flash(MockFlashError::NotErased))
+			);
+		}
+	}
+
+	#[test]
+	fn interrupted_erases_only_erase_bits() {
+		for seed in 1..16 {
+			let mut flash = PowerLossFlash::new(Flash::new());
+			flash.write(0, &[0x5a; 64]).unwrap();
+			flash.set_seed(seed);
+			flash.set_power_loss_after(0);
+			assert_eq!(flash.erase(0, 64), Err(PowerLossError::PowerLoss));
+			assert_eq!(flash.operations(), 1);
+
+			flash.restore_power();
+			let mut data = [0; 64];
+			flash.read(0, &mut data).unwrap();
+			assert!(data.iter().all(|byte| *byte & 0x5a == 0x5a));
+		}
+	}
+
+	#[test]
+	fn corruption_respects_flashes_erasing_to_zero() {
+		for seed in 1..16 {
+			let mut flash = PowerLossFlash::new(MockFlash::<64, 1, 4, 16, 0x00>::new());
+			flash.write(0, &[0x5a; 16]).unwrap();
+			flash.set_seed(seed);
+			flash.set_power_loss_after(0);
+			assert_eq!(flash.erase(0, 16), Err(PowerLossError::PowerLoss));
+
+			flash.restore_power();
+			let mut data = [0; 16];
+			flash.read(0, &mut data).unwrap();
+			assert!(data.iter().all(|byte| *byte & !0x5a == 0));
+
+			flash.erase(0, 16).unwrap();
+			flash.set_power_loss_after(0);
+			assert_eq!(flash.write(0, &[0xf0; 4]), Err(PowerLossError::PowerLoss));
+			flash.restore_power();
+			flash.read(0, &mut data).unwrap();
+			assert!(data.iter().all(|byte| *byte & !0xf0 == 0));
+		}
+	}
+
+	#[test]
+	fn scratch_storage_survives_every_power_loss() {
+		let setup = || {
+			let mut flash = Flash::new();
+			let mut buffer = [0; 16];
+			ScratchRmwNorFlashStorage::new(&mut flash, &mut buffer)
+				.write(0, &[1; 32])
+				.unwrap();
+			flash
+		};
+		let scenario = |flash: &mut PowerLossFlash<Flash>| {
+			let mut buffer = [0; 16];
+			ScratchRmwNorFlashStorage::new(flash, &mut buffer).write(8, &[2; 16])
+		};
+		let verify = |cut, flash| {
+			let mut buffer = [0; 16];
+			let mut storage = ScratchRmwNorFlashStorage::new(flash, &mut buffer);
+			let mut data = [0; 32];
+			storage.read(0, &mut data).unwrap();
+			for page in data.chunks(16) {
+				assert!(
+					page[..8] == [1; 8] && (page[8..] == [1; 8] || page[8..] == [2; 8])
+						|| page[..8] == [2; 8] && page[8..] == [1; 8],
+					"Torn page after {} operations: {:?}",
+					cut,
+					data
+				);
+			}
+		};
+		assert!(simulate_power_loss(setup, scenario, verify) > 8);
+	}
+}
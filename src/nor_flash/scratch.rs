@@ -0,0 +1,340 @@
+use crate::{
+	crc::Crc32,
+	iter::IterableByOverlaps,
+	nor_flash::{
+		read_unaligned, write_non_erased, NorFlash, NorFlashError, NorFlashErrorKind, Page,
+	},
+	ReadStorage, Region, Storage,
+};
+
+/// Marks a complete copy in the scratch page, "SCRA" in little endian.
+const MAGIC: u32 = 0x4152_4353;
+
+/// Magic, page address and checksum.
+const HEADER_SIZE: usize = 12;
+
+/// Size of the header once padded to the read and write sizes of `S`.
+fn header_len<S: NorFlash>() -> usize {
+	let mut len = HEADER_SIZE;
+	while len % S::READ_SIZE != 0 || len % S::WRITE_SIZE != 0 {
+		len += 1;
+	}
+	len
+}
+
+/// CRC-32 of the page address followed by the page contents.
+fn checksum(address: u32, data: &[u8]) -> u32 {
+	let mut crc = Crc32::new();
+	crc.update(&address.to_le_bytes());
+	crc.update(data);
+	crc.finish()
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+	u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Errors returned by [`ScratchRmwNorFlashStorage`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ScratchRmwError<E> {
+	/// The arguments are out of the bounds of the storage, which excludes the scratch pages.
+	OutOfBounds,
+
+	/// The underlying flash returned an error.
+	Flash(E),
+}
+
+impl<E: NorFlashError> NorFlashError for ScratchRmwError<E> {
+	fn kind(&self) -> NorFlashErrorKind {
+		match self {
+			Self::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+			Self::Flash(e) => e.kind(),
+		}
+	}
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for ScratchRmwError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::OutOfBounds => write!(f, "Arguments are out of bounds"),
+			Self::Flash(e) => e.fmt(f),
+		}
+	}
+}
+
+/// A [`Storage`] implementation on top of a [`NorFlash`], doing read/modify/write operations
+/// which survive a power loss.
+///
+/// Like [`RmwNorFlashStorage`](super::RmwNorFlashStorage), except that the merged page is first
+/// copied to a scratch page, and marked as complete by a header holding its address and a CRC.
+/// Only then is the page erased and written. If power is lost in the meantime, the copy is
+/// written to the page again on the next access, or on an explicit call to
+/// [`recover`](Self::recover). Either way, each page holds either its previous or its new
+/// contents.
+///
+/// The last two erase pages of the flash are reserved for the scratch copy and its header, and
+/// are not part of the storage.
+pub struct ScratchRmwNorFlashStorage<'a, S> {
+	storage: S,
+	merge_buffer: &'a mut [u8],
+	recovered: bool,
+}
+
+impl<'a, S> ScratchRmwNorFlashStorage<'a, S>
+where
+	S: NorFlash,
+{
+	/// Instantiate a new generic `Storage` from a `NorFlash` peripheral
+	///
+	/// **NOTE** This will panic if the provided merge buffer,
+	/// is smaller than the erase size of the flash peripheral,
+	/// if the scratch header does not fit in an erase page,
+	/// or if the flash is too small to reserve the scratch pages
+	pub fn new(nor_flash: S, merge_buffer: &'a mut [u8]) -> Self {
+		if merge_buffer.len() < S::ERASE_SIZE {
+			panic!("Merge buffer is too small");
+		}
+		if header_len::<S>() > S::ERASE_SIZE {
+			panic!("Erase size is too small for the scratch header");
+		}
+		if nor_flash.capacity() < 3 * S::ERASE_SIZE {
+			panic!("Flash is too small for the scratch pages");
+		}
+
+		Self {
+			storage: nor_flash,
+			merge_buffer,
+			recovered: false,
+		}
+	}
+
+	/// Finish a write interrupted by a power loss, returning whether there was one.
+	///
+	/// This is done automatically on the first read or write, but can be triggered early, for
+	/// instance while booting.
+	pub fn recover(&mut self) -> Result<bool, S::Error> {
+		let header = self.header_page();
+		let len = header_len::<S>();
+		self.storage
+			.read(header.start, &mut self.merge_buffer[..len])?;
+
+		let magic = read_u32(&self.merge_buffer[0..4]);
+		let address = read_u32(&self.merge_buffer[4..8]);
+		let crc = read_u32(&self.merge_buffer[8..12]);
+		let erased = self.merge_buffer[..len]
+			.iter()
+			.all(|byte| *byte == S::ERASE_VALUE);
+
+		if magic == MAGIC
+			&& address as usize % S::ERASE_SIZE == 0
+			&& (address as usize) < self.capacity()
+		{
+			let scratch = self.scratch_page();
+			self.storage
+				.read(scratch.start, &mut self.merge_buffer[..S::ERASE_SIZE])?;
+			if checksum(address, &self.merge_buffer[..S::ERASE_SIZE]) == crc {
+				self.restore(Page::new(address / S::ERASE_SIZE as u32, S::ERASE_SIZE))?;
+				self.recovered = true;
+				return Ok(true);
+			}
+		}
+
+		// Leftovers of an incomplete copy, the page it was meant for was not touched yet
+		if !erased {
+			self.storage.erase(header.start, header.end())?;
+		}
+		self.recovered = true;
+		Ok(false)
+	}
+
+	fn scratch_page(&self) -> Page {
+		Page::new(
+			(self.storage.capacity() / S::ERASE_SIZE - 2) as u32,
+			S::ERASE_SIZE,
+		)
+	}
+
+	fn header_page(&self) -> Page {
+		Page::new(
+			(self.storage.capacity() / S::ERASE_SIZE - 1) as u32,
+			S::ERASE_SIZE,
+		)
+	}
+
+	/// Safely replace the contents of `page` with the merge buffer.
+	fn commit(&mut self, page: Page) -> Result<(), S::Error> {
+		let (scratch, header) = (self.scratch_page(), self.header_page());
+		let crc = checksum(page.start, &self.merge_buffer[..S::ERASE_SIZE]);
+
+		// Invalidate the previous copy before overwriting it
+		self.storage.erase(header.start, header.end())?;
+		self.storage.erase(scratch.start, scratch.end())?;
+		write_non_erased(
+			&mut self.storage,
+			scratch.start,
+			&self.merge_buffer[..S::ERASE_SIZE],
+		)?;
+
+		// The header marks the copy as complete, the merge buffer is used to pad it
+		let len = header_len::<S>();
+		self.merge_buffer[..len].fill(S::ERASE_VALUE);
+		self.merge_buffer[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+		self.merge_buffer[4..8].copy_from_slice(&page.start.to_le_bytes());
+		self.merge_buffer[8..12].copy_from_slice(&crc.to_le_bytes());
+		self.storage
+			.write(header.start, &self.merge_buffer[..len])?;
+
+		self.storage
+			.read(scratch.start, &mut self.merge_buffer[..S::ERASE_SIZE])?;
+		self.restore(page)
+	}
+
+	fn check(&self, offset: u32, length: usize) -> Result<(), ScratchRmwError<S::Error>> {
+		let capacity = self.capacity();
+		if length > capacity || offset as usize > capacity - length {
+			return Err(ScratchRmwError::OutOfBounds);
+		}
+		Ok(())
+	}
+
+	/// Write the copy held in the merge buffer to `page`, and discard the header.
+	fn restore(&mut self, page: Page) -> Result<(), S::Error> {
+		let header = self.header_page();
+		self.storage.erase(page.start, page.end())?;
+		write_non_erased(
+			&mut self.storage,
+			page.start,
+			&self.merge_buffer[..S::ERASE_SIZE],
+		)?;
+		self.storage.erase(header.start, header.end())
+	}
+}
+
+impl<'a, S> ReadStorage for ScratchRmwNorFlashStorage<'a, S>
+where
+	S: NorFlash,
+{
+	type Error = ScratchRmwError<S::Error>;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.check(offset, bytes.len())?;
+		if !self.recovered {
+			self.recover().map_err(ScratchRmwError::Flash)?;
+		}
+		read_unaligned(&mut self.storage, self.merge_buffer, offset, bytes)
+			.map_err(ScratchRmwError::Flash)
+	}
+
+	fn capacity(&self) -> usize {
+		self.storage.capacity() - 2 * S::ERASE_SIZE
+	}
+}
+
+impl<'a, S> Storage for ScratchRmwNorFlashStorage<'a, S>
+where
+	S: NorFlash,
+{
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.check(offset, bytes.len())?;
+		if !self.recovered {
+			self.recover().map_err(ScratchRmwError::Flash)?;
+		}
+
+		let last_page = self.capacity() / S::ERASE_SIZE;
+
+		// `data` is the part of `bytes` contained within `page`,
+		// and `addr` in the address offset of `page` + any offset into the page as requested by `address`
+		for (data, page, addr) in (0..last_page as u32)
+			.map(move |i| Page::new(i, S::ERASE_SIZE))
+			.overlaps(bytes, offset)
+		{
+			let offset_into_page = addr.saturating_sub(page.start) as usize;
+			let range = offset_into_page..offset_into_page + data.len();
+
+			self.storage
+				.read(page.start, &mut self.merge_buffer[..S::ERASE_SIZE])
+				.map_err(ScratchRmwError::Flash)?;
+
+			// Nothing to do if the data is already there
+			if self.merge_buffer[range.clone()] == *data {
+				continue;
+			}
+
+			self.merge_buffer[range].copy_from_slice(data);
+			self.commit(page).map_err(ScratchRmwError::Flash)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::nor_flash::{MockFlash, ReadNorFlash};
+
+	type Flash = MockFlash<64, 1, 4, 16>;
+
+	#[test]
+	fn rejects_accesses_to_the_scratch_pages() {
+		let mut buffer = [0; 16];
+		let mut storage = ScratchRmwNorFlashStorage::new(Flash::new(), &mut buffer);
+		assert_eq!(storage.capacity(), 32);
+
+		storage.write(30, &[1, 2]).unwrap();
+		assert_eq!(
+			storage.write(31, &[1, 2]),
+			Err(ScratchRmwError::OutOfBounds)
+		);
+		assert_eq!(storage.write(32, &[]), Ok(()));
+		assert_eq!(storage.write(33, &[]), Err(ScratchRmwError::OutOfBounds));
+		let mut bytes = [0; 4];
+		assert_eq!(
+			storage.read(30, &mut bytes),
+			Err(ScratchRmwError::OutOfBounds)
+		);
+		storage.read(28, &mut bytes).unwrap();
+		assert_eq!(bytes, [0xff, 0xff, 1, 2]);
+	}
+
+	#[test]
+	fn recovers_an_interrupted_commit() {
+		let mut buffer = [0; 16];
+		let mut storage = ScratchRmwNorFlashStorage::new(Flash::new(), &mut buffer);
+		storage.write(0, &[1; 16]).unwrap();
+		storage.write(4, &[2; 4]).unwrap();
+
+		// Bring the copy back, as if power was lost before the page was erased
+		let mut flash = storage.storage;
+		let mut page = [0; 16];
+		flash.read(0, &mut page).unwrap();
+		flash.erase(32, 64).unwrap();
+		flash.write(32, &page).unwrap();
+		let mut header = [0xff; 12];
+		header[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+		header[4..8].copy_from_slice(&0u32.to_le_bytes());
+		header[8..12].copy_from_slice(&checksum(0, &page).to_le_bytes());
+		flash.write(48, &header).unwrap();
+		flash.erase(0, 16).unwrap();
+
+		let mut storage = ScratchRmwNorFlashStorage::new(flash, &mut buffer);
+		assert_eq!(storage.recover(), Ok(true));
+		assert_eq!(storage.recover(), Ok(false));
+		let mut bytes = [0; 8];
+		storage.read(0, &mut bytes).unwrap();
+		assert_eq!(bytes, [1, 1, 1, 1, 2, 2, 2, 2]);
+	}
+
+	#[test]
+	#[should_panic(expected = "Erase size is too small for the scratch header")]
+	fn rejects_erase_pages_smaller_than_the_header() {
+		let mut buffer = [0; 16];
+		ScratchRmwNorFlashStorage::new(MockFlash::<64, 1, 1, 8>::new(), &mut buffer);
+	}
+
+	#[test]
+	#[should_panic(expected = "Merge buffer is too small")]
+	fn rejects_small_merge_buffers() {
+		let mut buffer = [0; 8];
+		ScratchRmwNorFlashStorage::new(Flash::new(), &mut buffer);
+	}
+}
@@ -0,0 +1,467 @@
+use crate::{
+	nor_flash::{
+		check_slice, rmw_regions, EraseWrite, ErrorType, NorFlashErrorKind, ReadNorFlash,
+		RmwNorFlashStorage,
+	},
+	Region, Storage,
+};
+
+/// Errors returned by [`RmwNorFlashStorage::try_with_sectors`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum SectorStorageError {
+	/// The merge buffer is smaller than the largest sector.
+	MergeBufferTooSmall,
+
+	/// The sector map does not cover exactly the capacity of the flash, or does not fit into the
+	/// 4 GiB reachable with `u32` offsets.
+	SectorMapMismatch,
+
+	/// The program page size does not divide every sector.
+	ProgramPageMismatch,
+}
+
+impl core::fmt::Display for SectorStorageError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::MergeBufferTooSmall => write!(f, "Merge buffer is too small"),
+			Self::SectorMapMismatch => write!(f, "Sector map must cover the capacity"),
+			Self::ProgramPageMismatch => write!(f, "Program page size must divide every sector"),
+		}
+	}
+}
+
+/// Return whether `sector_map` covers exactly `capacity` bytes, which fit into a `u32`, in sectors
+/// made of whole program pages of `page_size` bytes.
+pub fn check_sector_map(
+	sector_map: &[SectorRun],
+	capacity: usize,
+	page_size: usize,
+) -> Result<(), SectorStorageError> {
+	let size = sector_map.iter().try_fold(0usize, |size, run| {
+		run.count
+			.checked_mul(run.size)
+			.and_then(|run| size.checked_add(run))
+	});
+	match size {
+		Some(size) if size == capacity && size <= u32::MAX as usize => {}
+		_ => return Err(SectorStorageError::SectorMapMismatch),
+	}
+	if page_size == 0 || sector_map.iter().any(|run| run.size % page_size != 0) {
+		return Err(SectorStorageError::ProgramPageMismatch);
+	}
+	Ok(())
+}
+
+/// A run of `count` consecutive sectors of `size` bytes each, in a sector map.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct SectorRun {
+	/// The number of sectors in the run
+	pub count: usize,
+
+	/// The size of each sector of the run in bytes
+	pub size: usize,
+}
+
+impl SectorRun {
+	/// Create a run of `count` sectors of `size` bytes.
+	pub const fn new(count: usize, size: usize) -> Self {
+		Self { count, size }
+	}
+}
+
+/// A sector of a [`SectorNorFlash`], the smallest range it can erase.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Sector {
+	start: u32,
+	size: usize,
+}
+
+impl Sector {
+	/// The size of the sector in bytes.
+	pub fn size(&self) -> usize {
+		self.size
+	}
+}
+
+impl Region for Sector {
+	fn start(&self) -> u32 {
+		self.start
+	}
+
+	fn end(&self) -> u32 {
+		self.start + self.size as u32
+	}
+}
+
+/// Iterator over the sectors of a sector map, in ascending address order.
+#[derive(Debug, Clone)]
+pub struct Sectors {
+	runs: &'static [SectorRun],
+	index: usize,
+	start: u32,
+}
+
+impl Sectors {
+	/// Iterate over the sectors of the sector map `runs`, starting at address 0.
+	pub fn new(runs: &'static [SectorRun]) -> Self {
+		Self {
+			runs,
+			index: 0,
+			start: 0,
+		}
+	}
+}
+
+impl Iterator for Sectors {
+	type Item = Sector;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while let Some((run, rest)) = self.runs.split_first() {
+			if self.index < run.count {
+				let sector = Sector {
+					start: self.start,
+					size: run.size,
+				};
+				self.index += 1;
+				self.start += run.size as u32;
+				return Some(sector);
+			}
+			self.runs = rest;
+			self.index = 0;
+		}
+		None
+	}
+}
+
+/// NOR flash trait for flashes with sectors of different sizes, like the internal flash of many
+/// MCUs or SPI NOR flashes with small boot sectors.
+///
+/// The layout of the flash is described by a sector map, and erases operate on whole sectors.
+/// Flashes whose sectors all have the same size should implement [`NorFlash`](super::NorFlash)
+/// instead.
+pub trait SectorNorFlash: ReadNorFlash {
+	/// The minumum number of bytes the storage peripheral can write
+	const WRITE_SIZE: usize;
+
+	/// The largest number of bytes the storage peripheral programs with a single command, like
+	/// [`NorFlash::PROGRAM_PAGE_SIZE`](super::NorFlash::PROGRAM_PAGE_SIZE). Program pages start at
+	/// multiples of `PROGRAM_PAGE_SIZE`, which is a multiple of `WRITE_SIZE` and divides the size
+	/// of every sector.
+	///
+	/// It defaults to `WRITE_SIZE`, which is always valid but has writes split into single words.
+	const PROGRAM_PAGE_SIZE: usize = Self::WRITE_SIZE;
+
+	/// The value every byte reads as after an erase, like
+	/// [`NorFlash::ERASE_VALUE`](super::NorFlash::ERASE_VALUE). It defaults to `0xff`.
+	const ERASE_VALUE: u8 = 0xff;
+
+	/// The sector map of the flash, as runs of equally sized sectors in ascending address order
+	/// covering the whole capacity. It is usually a constant table.
+	fn sector_map(&self) -> &'static [SectorRun];
+
+	/// The sectors of the flash, in ascending address order.
+	fn sectors(&self) -> Sectors {
+		Sectors::new(self.sector_map())
+	}
+
+	/// Erase the given storage range, clearing all data within `[from..to]`.
+	/// Every byte of the given range will read as `ERASE_VALUE` afterwards.
+	///
+	/// If power is lost during erase, contents of the sectors are undefined.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `from` and `to` are not sector boundaries, or out of bounds (the case
+	/// where `from > to` is considered out of bounds). The implementation can use the
+	/// [`check_sector_erase`] helper function.
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error>;
+
+	/// If power is lost during write, the contents of the written words are undefined,
+	/// but the rest of the sector is guaranteed to be unchanged.
+	/// It is not allowed to write to the same word twice.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are not aligned or out of bounds. The implementation
+	/// can use the [`check_sector_write`] helper function.
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Return whether an erase operation is on sector boundaries and within bounds.
+pub fn check_sector_erase<T: SectorNorFlash>(
+	flash: &T,
+	from: u32,
+	to: u32,
+) -> Result<(), NorFlashErrorKind> {
+	if from > to || to as usize > flash.capacity() {
+		return Err(NorFlashErrorKind::OutOfBounds);
+	}
+	let boundary = |address: u32| {
+		address as usize == flash.capacity() || flash.sectors().any(|s| s.start() == address)
+	};
+	if !boundary(from) || !boundary(to) {
+		return Err(NorFlashErrorKind::NotAligned);
+	}
+	Ok(())
+}
+
+/// Return whether a write operation is aligned and within bounds.
+pub fn check_sector_write<T: SectorNorFlash>(
+	flash: &T,
+	offset: u32,
+	length: usize,
+) -> Result<(), NorFlashErrorKind> {
+	check_slice(flash, T::WRITE_SIZE, offset, length)
+}
+
+impl<T: SectorNorFlash> SectorNorFlash for &mut T {
+	const WRITE_SIZE: usize = T::WRITE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = T::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = T::ERASE_VALUE;
+
+	fn sector_map(&self) -> &'static [SectorRun] {
+		T::sector_map(self)
+	}
+
+	fn sectors(&self) -> Sectors {
+		T::sectors(self)
+	}
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		T::erase(self, from, to)
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		T::write(self, offset, bytes)
+	}
+}
+
+/// A [`SectorNorFlash`] wrapped by [`RmwNorFlashStorage`], which then does read/modify/write
+/// operations sector by sector. It is created by [`RmwNorFlashStorage::with_sectors`].
+#[derive(Debug)]
+pub struct SectorMapped<S>(S);
+
+impl<S: ErrorType> ErrorType for SectorMapped<S> {
+	type Error = S::Error;
+}
+
+impl<S: ReadNorFlash> ReadNorFlash for SectorMapped<S> {
+	const READ_SIZE: usize = S::READ_SIZE;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.0.read(offset, bytes)
+	}
+
+	fn capacity(&self) -> usize {
+		self.0.capacity()
+	}
+}
+
+impl<S: SectorNorFlash> EraseWrite for SectorMapped<S> {
+	const WRITE_SIZE: usize = S::WRITE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = S::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = S::ERASE_VALUE;
+
+	// Whether it divides the sectors is only known at runtime
+	const GEOMETRY: () = assert!(
+		S::PROGRAM_PAGE_SIZE % S::WRITE_SIZE == 0,
+		"Program page size must be a multiple of the write size"
+	);
+
+	fn erase_range(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.0.erase(from, to)
+	}
+
+	fn write_page(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.0.write(offset, bytes)
+	}
+}
+
+impl<'a, S> RmwNorFlashStorage<'a, SectorMapped<S>>
+where
+	S: SectorNorFlash,
+{
+	/// Instantiate a new generic `Storage` from a `SectorNorFlash` peripheral
+	///
+	/// **NOTE** This will panic if the provided merge buffer,
+	/// is smaller than the largest sector of the flash peripheral,
+	/// if the sector map does not cover the capacity of the flash peripheral,
+	/// or if the program page size does not divide every sector
+	pub fn with_sectors(nor_flash: S, merge_buffer: &'a mut [u8]) -> Self {
+		match Self::try_with_sectors(nor_flash, merge_buffer) {
+			Ok(storage) => storage,
+			Err(error) => panic!("{}", error),
+		}
+	}
+
+	/// Instantiate a new generic `Storage` from a `SectorNorFlash` peripheral, failing if the
+	/// provided merge buffer is smaller than the largest sector of the flash peripheral, if the
+	/// sector map does not cover the capacity of the flash peripheral, or if the program page size
+	/// does not divide every sector
+	pub fn try_with_sectors(
+		nor_flash: S,
+		merge_buffer: &'a mut [u8],
+	) -> Result<Self, SectorStorageError> {
+		let sector_map = nor_flash.sector_map();
+		check_sector_map(sector_map, nor_flash.capacity(), S::PROGRAM_PAGE_SIZE)?;
+
+		let largest = sector_map.iter().map(|run| run.size).max();
+		if merge_buffer.len() < largest.unwrap_or(0).max(S::READ_SIZE) {
+			return Err(SectorStorageError::MergeBufferTooSmall);
+		}
+
+		Ok(Self {
+			storage: SectorMapped(nor_flash),
+			merge_buffer,
+		})
+	}
+}
+
+impl<'a, S> Storage for RmwNorFlashStorage<'a, SectorMapped<S>>
+where
+	S: SectorNorFlash,
+{
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		let sectors = self.storage.0.sectors();
+		rmw_regions(
+			&mut self.storage,
+			sectors,
+			self.merge_buffer,
+			offset,
+			bytes,
+			false,
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::nor_flash::{MockFlash, MockFlashError, NorFlash};
+	use crate::ReadStorage;
+
+	static SECTOR_MAP: [SectorRun; 2] = [SectorRun::new(2, 8), SectorRun::new(1, 16)];
+
+	/// Sectors of 8, 8 and 16 bytes, checking that no write crosses a program page of `PAGE`
+	/// bytes.
+	struct SectorMock<const PAGE: usize>(MockFlash<32, 1, 2, 8>, &'static [SectorRun]);
+
+	impl<const PAGE: usize> SectorMock<PAGE> {
+		fn new() -> Self {
+			Self(MockFlash::new(), &SECTOR_MAP)
+		}
+	}
+
+	impl<const PAGE: usize> ErrorType for SectorMock<PAGE> {
+		type Error = MockFlashError;
+	}
+
+	impl<const PAGE: usize> ReadNorFlash for SectorMock<PAGE> {
+		const READ_SIZE: usize = 1;
+
+		fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			self.0.read(offset, bytes)
+		}
+
+		fn capacity(&self) -> usize {
+			self.0.capacity()
+		}
+	}
+
+	impl<const PAGE: usize> SectorNorFlash for SectorMock<PAGE> {
+		const WRITE_SIZE: usize = 2;
+		const PROGRAM_PAGE_SIZE: usize = PAGE;
+
+		fn sector_map(&self) -> &'static [SectorRun] {
+			self.1
+		}
+
+		fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+			check_sector_erase(self, from, to).map_err(|_| MockFlashError::NotAligned)?;
+			self.0.erase(from, to)
+		}
+
+		fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+			let page = PAGE as u32;
+			assert_eq!(offset / page, (offset + bytes.len() as u32 - 1) / page);
+			self.0.write(offset, bytes)
+		}
+	}
+
+	#[test]
+	fn rmw_writes_stay_within_program_pages() {
+		let mut buffer = [0; 16];
+		let mut storage = RmwNorFlashStorage::with_sectors(SectorMock::<4>::new(), &mut buffer);
+
+		storage.write(3, &[0x5a; 26]).unwrap();
+		storage.write(12, &[0xa5; 8]).unwrap();
+
+		let mut data = [0; 32];
+		storage.read(0, &mut data).unwrap();
+		assert_eq!(data[..3], [0xff; 3]);
+		assert_eq!(data[3..12], [0x5a; 9]);
+		assert_eq!(data[12..20], [0xa5; 8]);
+		assert_eq!(data[20..29], [0x5a; 9]);
+		assert_eq!(data[29..], [0xff; 3]);
+	}
+
+	#[test]
+	#[should_panic(expected = "Program page size must divide every sector")]
+	fn rejects_program_pages_larger_than_a_sector() {
+		let mut buffer = [0; 16];
+		RmwNorFlashStorage::with_sectors(SectorMock::<16>::new(), &mut buffer);
+	}
+
+	#[test]
+	fn reports_invalid_geometries() {
+		static SHORT_MAP: [SectorRun; 1] = [SectorRun::new(2, 8)];
+		let mut buffer = [0; 16];
+		assert_eq!(
+			RmwNorFlashStorage::try_with_sectors(SectorMock::<16>::new(), &mut buffer).err(),
+			Some(SectorStorageError::ProgramPageMismatch)
+		);
+		assert_eq!(
+			RmwNorFlashStorage::try_with_sectors(
+				SectorMock::<4>(MockFlash::new(), &SHORT_MAP),
+				&mut buffer,
+			)
+			.err(),
+			Some(SectorStorageError::SectorMapMismatch)
+		);
+		assert_eq!(
+			RmwNorFlashStorage::try_with_sectors(SectorMock::<4>::new(), &mut buffer[..8]).err(),
+			Some(SectorStorageError::MergeBufferTooSmall)
+		);
+	}
+
+	#[test]
+	#[cfg(target_pointer_width = "64")]
+	fn sector_maps_must_fit_the_address_space() {
+		let huge = [SectorRun::new(1 << 16, 1 << 16)];
+		assert_eq!(
+			check_sector_map(&huge, 1 << 32, 4),
+			Err(SectorStorageError::SectorMapMismatch)
+		);
+		let overflowing = [SectorRun::new(2, usize::MAX / 2 + 1)];
+		assert_eq!(
+			check_sector_map(&overflowing, 0, 4),
+			Err(SectorStorageError::SectorMapMismatch)
+		);
+		assert_eq!(
+			check_sector_map(&SECTOR_MAP, 32, 0),
+			Err(SectorStorageError::ProgramPageMismatch)
+		);
+		assert_eq!(check_sector_map(&SECTOR_MAP, 32, 8), Ok(()));
+	}
+
+	#[test]
+	#[should_panic(expected = "Sector map must cover the capacity")]
+	fn rejects_sector_maps_not_covering_the_flash() {
+		static SHORT_MAP: [SectorRun; 1] = [SectorRun::new(2, 8)];
+		let mut buffer = [0; 16];
+		RmwNorFlashStorage::with_sectors(
+			SectorMock::<4>(MockFlash::new(), &SHORT_MAP),
+			&mut buffer,
+		);
+	}
+}
@@ -0,0 +1,340 @@
+use core::cell::RefCell;
+
+use crate::nor_flash::{
+	ErrorType, MultiwriteNorFlash, NorFlash, NorFlashErrorKind, Partition, PartitionError,
+	ReadNorFlash,
+};
+
+/// Hands out disjoint partitions of a flash shared between multiple drivers.
+///
+/// `T` is the shared flash, like a `RefCell<F>`, or a `critical_section::Mutex<RefCell<F>>` when
+/// the `critical-section` feature is enabled. Partitions have to be requested in increasing
+/// address order, which guarantees that they never overlap.
+#[derive(Debug)]
+pub struct SharedPartitions<'a, T> {
+	flash: &'a T,
+	next: u32,
+}
+
+impl<'a, T> SharedPartitions<'a, T> {
+	/// Start handing out partitions of `flash`.
+	pub fn new(flash: &'a T) -> Self {
+		Self { flash, next: 0 }
+	}
+
+	/// Reserve `size` bytes at `offset`, returning the end of the reserved range.
+	fn reserve(&mut self, offset: u32, size: usize) -> Result<u32, NorFlashErrorKind> {
+		if offset < self.next {
+			return Err(NorFlashErrorKind::OutOfBounds);
+		}
+		let end = (offset as usize)
+			.checked_add(size)
+			.filter(|end| *end <= u32::MAX as usize)
+			.ok_or(NorFlashErrorKind::OutOfBounds)?;
+		Ok(end as u32)
+	}
+}
+
+impl<'a, F> SharedPartitions<'a, RefCell<F>>
+where
+	F: NorFlash,
+{
+	/// Hand out the partition of `size` bytes starting at `offset`.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `offset` and `size` are not multiples of the erase size of the flash,
+	/// if the partition does not fit in the flash, or if it starts before the end of the previous
+	/// partition.
+	pub fn partition(
+		&mut self,
+		offset: u32,
+		size: usize,
+	) -> Result<RefCellPartition<'a, F>, NorFlashErrorKind> {
+		let end = self.reserve(offset, size)?;
+		let partition = RefCellPartition::new(self.flash, offset, size)?;
+		self.next = end;
+		Ok(partition)
+	}
+}
+
+/// A partition of a flash shared through a [`RefCell`], handed out by [`SharedPartitions`].
+///
+/// Sharing through a [`RefCell`] is only possible within a single thread or interrupt priority
+/// level. Accessing the flash while it is already borrowed panics.
+#[derive(Debug)]
+pub struct RefCellPartition<'a, F> {
+	flash: &'a RefCell<F>,
+	offset: u32,
+	size: usize,
+}
+
+impl<'a, F> RefCellPartition<'a, F>
+where
+	F: NorFlash,
+{
+	/// Create a partition of `size` bytes, starting at `offset` in the shared `flash`.
+	///
+	/// Only [`SharedPartitions`] creates partitions, making sure that they do not overlap.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `offset` and `size` are not multiples of the erase size of `flash`, or
+	/// if the partition does not fit in `flash`.
+	pub(crate) fn new(
+		flash: &'a RefCell<F>,
+		offset: u32,
+		size: usize,
+	) -> Result<Self, NorFlashErrorKind> {
+		Partition::new(&mut *flash.borrow_mut(), offset, size)?;
+		Ok(Self {
+			flash,
+			offset,
+			size,
+		})
+	}
+
+	fn with<R>(&self, f: impl FnOnce(&mut Partition<&mut F>) -> R) -> R {
+		let mut flash = self.flash.borrow_mut();
+		f(&mut Partition::new_unchecked(
+			&mut *flash,
+			self.offset,
+			self.size,
+		))
+	}
+}
+
+impl<'a, F> ErrorType for RefCellPartition<'a, F>
+where
+	F: ErrorType,
+{
+	type Error = PartitionError<F::Error>;
+}
+
+impl<'a, F> ReadNorFlash for RefCellPartition<'a, F>
+where
+	F: NorFlash,
+{
+	const READ_SIZE: usize = F::READ_SIZE;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.with(|partition| partition.read(offset, bytes))
+	}
+
+	fn capacity(&self) -> usize {
+		self.size
+	}
+}
+
+impl<'a, F> NorFlash for RefCellPartition<'a, F>
+where
+	F: NorFlash,
+{
+	const WRITE_SIZE: usize = F::WRITE_SIZE;
+	const ERASE_SIZE: usize = F::ERASE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = F::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = F::ERASE_VALUE;
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.with(|partition| partition.erase(from, to))
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.with(|partition| partition.write(offset, bytes))
+	}
+}
+
+impl<'a, F> MultiwriteNorFlash for RefCellPartition<'a, F> where F: MultiwriteNorFlash {}
+
+#[cfg(feature = "critical-section")]
+impl<'a, F> SharedPartitions<'a, critical_section::Mutex<RefCell<F>>>
+where
+	F: NorFlash,
+{
+	/// Hand out the partition of `size` bytes starting at `offset`.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `offset` and `size` are not multiples of the erase size of the flash,
+	/// if the partition does not fit in the flash, or if it starts before the end of the previous
+	/// partition.
+	pub fn partition(
+		&mut self,
+		offset: u32,
+		size: usize,
+	) -> Result<CriticalSectionPartition<'a, F>, NorFlashErrorKind> {
+		let end = self.reserve(offset, size)?;
+		let partition = CriticalSectionPartition::new(self.flash, offset, size)?;
+		self.next = end;
+		Ok(partition)
+	}
+}
+
+/// A partition of a flash shared through a critical section [`Mutex`](critical_section::Mutex),
+/// handed out by [`SharedPartitions`].
+///
+/// Every operation runs inside a critical section, so the flash can be shared between threads
+/// and interrupt handlers, at the cost of blocking them for the duration of the operation.
+#[cfg(feature = "critical-section")]
+#[derive(Debug)]
+pub struct CriticalSectionPartition<'a, F> {
+	flash: &'a critical_section::Mutex<RefCell<F>>,
+	offset: u32,
+	size: usize,
+}
+
+#[cfg(feature = "critical-section")]
+impl<'a, F> CriticalSectionPartition<'a, F>
+where
+	F: NorFlash,
+{
+	/// Create a partition of `size` bytes, starting at `offset` in the shared `flash`.
+	///
+	/// Only [`SharedPartitions`] creates partitions, making sure that they do not overlap.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `offset` and `size` are not multiples of the erase size of `flash`, or
+	/// if the partition does not fit in `flash`.
+	pub(crate) fn new(
+		flash: &'a critical_section::Mutex<RefCell<F>>,
+		offset: u32,
+		size: usize,
+	) -> Result<Self, NorFlashErrorKind> {
+		critical_section::with(|cs| {
+			Partition::new(&mut *flash.borrow_ref_mut(cs), offset, size).map(|_| ())
+		})?;
+		Ok(Self {
+			flash,
+			offset,
+			size,
+		})
+	}
+
+	fn with<R>(&self, f: impl FnOnce(&mut Partition<&mut F>) -> R) -> R {
+		critical_section::with(|cs| {
+			let mut flash = self.flash.borrow_ref_mut(cs);
+			f(&mut Partition::new_unchecked(
+				&mut *flash,
+				self.offset,
+				self.size,
+			))
+		})
+	}
+}
+
+#[cfg(feature = "critical-section")]
+impl<'a, F> ErrorType for CriticalSectionPartition<'a, F>
+where
+	F: ErrorType,
+{
+	type Error = PartitionError<F::Error>;
+}
+
+#[cfg(feature = "critical-section")]
+impl<'a, F> ReadNorFlash for CriticalSectionPartition<'a, F>
+where
+	F: NorFlash,
+{
+	const READ_SIZE: usize = F::READ_SIZE;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		self.with(|partition| partition.read(offset, bytes))
+	}
+
+	fn capacity(&self) -> usize {
+		self.size
+	}
+}
+
+#[cfg(feature = "critical-section")]
+impl<'a, F> NorFlash for CriticalSectionPartition<'a, F>
+where
+	F: NorFlash,
+{
+	const WRITE_SIZE: usize = F::WRITE_SIZE;
+	const ERASE_SIZE: usize = F::ERASE_SIZE;
+	const PROGRAM_PAGE_SIZE: usize = F::PROGRAM_PAGE_SIZE;
+	const ERASE_VALUE: u8 = F::ERASE_VALUE;
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		self.with(|partition| partition.erase(from, to))
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		self.with(|partition| partition.write(offset, bytes))
+	}
+}
+
+#[cfg(feature = "critical-section")]
+impl<'a, F> MultiwriteNorFlash for CriticalSectionPartition<'a, F> where F: MultiwriteNorFlash {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::nor_flash::{MockFlash, MockFlashError};
+
+	type Flash = MockFlash<32, 1, 4, 8>;
+
+	#[test]
+	fn hands_out_disjoint_partitions() {
+		let flash = RefCell::new(Flash::new());
+		let mut partitions = SharedPartitions::new(&flash);
+		let mut first = partitions.partition(0, 8).unwrap();
+		let mut second = partitions.partition(16, 16).unwrap();
+		assert_eq!(first.capacity(), 8);
+		assert_eq!(second.capacity(), 16);
+
+		first.write(4, &[1, 2, 3, 4]).unwrap();
+		second.write(0, &[5, 6, 7, 8]).unwrap();
+		second.erase(8, 16).unwrap();
+		assert_eq!(
+			second.write(16, &[0; 4]),
+			Err(PartitionError::Partition(NorFlashErrorKind::OutOfBounds))
+		);
+		assert_eq!(
+			first.write(4, &[0; 4]),
+			Err(PartitionError::Flash(MockFlashError::NotErased))
+		);
+
+		let mut data = [0; 4];
+		first.read(4, &mut data).unwrap();
+		assert_eq!(data, [1, 2, 3, 4]);
+		assert_eq!(flash.borrow().as_bytes()[16..20], [5, 6, 7, 8]);
+	}
+
+	#[test]
+	fn rejects_overlapping_partitions() {
+		let flash = RefCell::new(Flash::new());
+		let mut partitions = SharedPartitions::new(&flash);
+		partitions.partition(8, 16).unwrap();
+
+		assert_eq!(
+			partitions.partition(0, 8).err(),
+			Some(NorFlashErrorKind::OutOfBounds)
+		);
+		assert_eq!(
+			partitions.partition(16, 8).err(),
+			Some(NorFlashErrorKind::OutOfBounds)
+		);
+		assert_eq!(
+			partitions.partition(u32::MAX - 7, 16).err(),
+			Some(NorFlashErrorKind::OutOfBounds)
+		);
+	}
+
+	#[test]
+	fn failed_requests_do_not_reserve_the_range() {
+		let flash = RefCell::new(Flash::new());
+		let mut partitions = SharedPartitions::new(&flash);
+		assert_eq!(
+			partitions.partition(8, 4).err(),
+			Some(NorFlashErrorKind::NotAligned)
+		);
+		assert_eq!(
+			partitions.partition(8, 32).err(),
+			Some(NorFlashErrorKind::OutOfBounds)
+		);
+		partitions.partition(8, 8).unwrap();
+	}
+}
@@ -0,0 +1,64 @@
+use crate::StorageError;
+
+mod nor;
+mod storage;
+
+pub use self::nor::{NvramNorFlash, NvramNorFlashError};
+pub use self::storage::NvramStorage;
+
+/// A trait that Nvram implementations can use to share an error type.
+pub trait ErrorType {
+	/// Errors returned by this memory.
+	type Error: StorageError;
+}
+
+/// Read only non-volatile RAM trait.
+///
+/// Non-volatile RAMs, like FRAM or MRAM, are byte addressable, need no erase and can rewrite
+/// any byte an unlimited number of times. [`NvramStorage`] exposes them as a
+/// [`Storage`](crate::Storage) writing in place, without any read/modify/write cycle.
+pub trait ReadNvram: ErrorType {
+	/// Read a slice of data from the memory, starting at `offset` and reading `bytes.len()`
+	/// bytes.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds.
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error>;
+
+	/// The capacity of the peripheral in bytes.
+	fn capacity(&self) -> usize;
+}
+
+/// Non-volatile RAM trait.
+pub trait Nvram: ReadNvram {
+	/// Write a slice of data to the memory, starting at `offset`. Bytes outside of the slice are
+	/// left unchanged.
+	///
+	/// If power is lost during write, the contents of the bytes being written are undefined.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the arguments are out of bounds.
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl<T: ErrorType> ErrorType for &mut T {
+	type Error = T::Error;
+}
+
+impl<T: ReadNvram> ReadNvram for &mut T {
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		T::read(self, offset, bytes)
+	}
+
+	fn capacity(&self) -> usize {
+		T::capacity(self)
+	}
+}
+
+impl<T: Nvram> Nvram for &mut T {
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		T::write(self, offset, bytes)
+	}
+}
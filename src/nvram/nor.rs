@@ -0,0 +1,265 @@
+use crate::{
+	nor_flash::{
+		check_erase, check_read, check_write, ErrorType, MultiwriteNorFlash, NorFlash,
+		NorFlashError, NorFlashErrorKind, ReadNorFlash,
+	},
+	nvram::{Nvram, ReadNvram},
+	StorageError, StorageErrorKind,
+};
+
+/// Number of bytes erased or merged at once.
+const CHUNK_SIZE: usize = 32;
+
+/// Errors returned by [`NvramNorFlash`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum NvramNorFlashError<E> {
+	/// The arguments are not properly aligned, or out of bounds.
+	NorFlash(NorFlashErrorKind),
+
+	/// The underlying memory returned an error.
+	Nvram(E),
+}
+
+impl<E: StorageError> NorFlashError for NvramNorFlashError<E> {
+	fn kind(&self) -> NorFlashErrorKind {
+		match self {
+			Self::NorFlash(kind) => *kind,
+			Self::Nvram(e) => match e.kind() {
+				StorageErrorKind::NotAligned => NorFlashErrorKind::NotAligned,
+				StorageErrorKind::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+				StorageErrorKind::Other => NorFlashErrorKind::Other,
+				StorageErrorKind::WriteProtected => NorFlashErrorKind::WriteProtected,
+				StorageErrorKind::ProgramFailed => NorFlashErrorKind::ProgramFailed,
+				StorageErrorKind::EraseFailed => NorFlashErrorKind::EraseFailed,
+				StorageErrorKind::Timeout => NorFlashErrorKind::Timeout,
+				StorageErrorKind::UncorrectableEcc => NorFlashErrorKind::UncorrectableEcc,
+				StorageErrorKind::Busy => NorFlashErrorKind::Busy,
+			},
+		}
+	}
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for NvramNorFlashError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::NorFlash(kind) => kind.fmt(f),
+			Self::Nvram(e) => e.fmt(f),
+		}
+	}
+}
+
+/// A non-volatile RAM exposed as a [`MultiwriteNorFlash`], so that code written for NOR flashes
+/// runs on it unchanged.
+///
+/// Reads and writes work on single bytes, and erases on pages of `ERASE_SIZE` bytes, which set
+/// all of their bytes to `0xff`. Like on a real NOR flash, writes only clear bits: the result is
+/// the logical AND of the previous data and the written data. Any capacity beyond the last full
+/// page is left unused.
+#[derive(Debug)]
+pub struct NvramNorFlash<T, const ERASE_SIZE: usize> {
+	nvram: T,
+}
+
+impl<T, const ERASE_SIZE: usize> NvramNorFlash<T, ERASE_SIZE>
+where
+	T: Nvram,
+{
+	const GEOMETRY: () = assert!(ERASE_SIZE > 0, "Erase size must not be zero");
+
+	/// Expose `nvram` as a NOR flash with pages of `ERASE_SIZE` bytes.
+	pub fn new(nvram: T) -> Self {
+		let () = Self::GEOMETRY;
+
+		Self { nvram }
+	}
+
+	/// Release the underlying memory.
+	pub fn into_inner(self) -> T {
+		self.nvram
+	}
+}
+
+impl<T, const ERASE_SIZE: usize> ErrorType for NvramNorFlash<T, ERASE_SIZE>
+where
+	T: Nvram,
+{
+	type Error = NvramNorFlashError<T::Error>;
+}
+
+impl<T, const ERASE_SIZE: usize> ReadNorFlash for NvramNorFlash<T, ERASE_SIZE>
+where
+	T: Nvram,
+{
+	const READ_SIZE: usize = 1;
+
+	fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+		check_read(self, offset, bytes.len()).map_err(NvramNorFlashError::NorFlash)?;
+		ReadNvram::read(&mut self.nvram, offset, bytes).map_err(NvramNorFlashError::Nvram)
+	}
+
+	fn capacity(&self) -> usize {
+		ReadNvram::capacity(&self.nvram) / ERASE_SIZE * ERASE_SIZE
+	}
+}
+
+impl<T, const ERASE_SIZE: usize> NorFlash for NvramNorFlash<T, ERASE_SIZE>
+where
+	T: Nvram,
+{
+	const WRITE_SIZE: usize = 1;
+	const ERASE_SIZE: usize = ERASE_SIZE;
+
+	fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+		check_erase(self, from, to).map_err(NvramNorFlashError::NorFlash)?;
+		let erased = [Self::ERASE_VALUE; CHUNK_SIZE];
+		let mut offset = from;
+		while offset < to {
+			let length = core::cmp::min(CHUNK_SIZE, (to - offset) as usize);
+			Nvram::write(&mut self.nvram, offset, &erased[..length])
+				.map_err(NvramNorFlashError::Nvram)?;
+			offset += length as u32;
+		}
+		Ok(())
+	}
+
+	fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+		check_write(self, offset, bytes.len()).map_err(NvramNorFlashError::NorFlash)?;
+		let mut merged = [0; CHUNK_SIZE];
+		for (offset, chunk) in (offset..).step_by(CHUNK_SIZE).zip(bytes.chunks(CHUNK_SIZE)) {
+			let merged = &mut merged[..chunk.len()];
+			ReadNvram::read(&mut self.nvram, offset, merged).map_err(NvramNorFlashError::Nvram)?;
+			for (merged, byte) in merged.iter_mut().zip(chunk) {
+				*merged &= byte;
+			}
+			Nvram::write(&mut self.nvram, offset, merged).map_err(NvramNorFlashError::Nvram)?;
+		}
+		Ok(())
+	}
+}
+
+impl<T, const ERASE_SIZE: usize> MultiwriteNorFlash for NvramNorFlash<T, ERASE_SIZE> where T: Nvram {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A memory of `N` bytes, recording the length of the writes it receives.
+	struct RamNvram<const N: usize> {
+		data: [u8; N],
+		writes: [usize; 8],
+		count: usize,
+	}
+
+	impl<const N: usize> RamNvram<N> {
+		fn new() -> Self {
+			Self {
+				data: [0; N],
+				writes: [0; 8],
+				count: 0,
+			}
+		}
+
+		fn writes(&self) -> &[usize] {
+			&self.writes[..self.count]
+		}
+	}
+
+	impl<const N: usize> crate::nvram::ErrorType for RamNvram<N> {
+		type Error = StorageErrorKind;
+	}
+
+	impl<const N: usize> ReadNvram for RamNvram<N> {
+		fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+			let offset = offset as usize;
+			let data = self.data.get(offset..offset + bytes.len());
+			bytes.copy_from_slice(data.ok_or(StorageErrorKind::OutOfBounds)?);
+			Ok(())
+		}
+
+		fn capacity(&self) -> usize {
+			N
+		}
+	}
+
+	impl<const N: usize> Nvram for RamNvram<N> {
+		fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+			let offset = offset as usize;
+			let data = self.data.get_mut(offset..offset + bytes.len());
+			data.ok_or(StorageErrorKind::OutOfBounds)?
+				.copy_from_slice(bytes);
+			self.writes[self.count] = bytes.len();
+			self.count += 1;
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn capacity_covers_full_pages_only() {
+		let mut flash = NvramNorFlash::<_, 16>::new(RamNvram::<40>::new());
+		assert_eq!(flash.capacity(), 32);
+		assert_eq!(
+			flash.read(32, &mut [0; 1]),
+			Err(NvramNorFlashError::NorFlash(NorFlashErrorKind::OutOfBounds))
+		);
+		assert_eq!(
+			flash.write(30, &[0; 4]),
+			Err(NvramNorFlashError::NorFlash(NorFlashErrorKind::OutOfBounds))
+		);
+		assert_eq!(flash.into_inner().writes(), []);
+	}
+
+	#[test]
+	fn writes_merge_chunk_by_chunk() {
+		let mut flash = NvramNorFlash::<_, 64>::new(RamNvram::<64>::new());
+		flash.erase(0, 64).unwrap();
+		flash.write(4, &[0b1100; 40]).unwrap();
+		flash.write(20, &[0b1010; 8]).unwrap();
+
+		let mut data = [0; 64];
+		flash.read(0, &mut data).unwrap();
+		assert_eq!(data[..4], [0xff; 4]);
+		assert_eq!(data[4..20], [0b1100; 16]);
+		assert_eq!(data[20..28], [0b1000; 8]);
+		assert_eq!(data[28..44], [0b1100; 16]);
+		assert_eq!(data[44..], [0xff; 20]);
+
+		// Erases and writes go through the memory 32 bytes at most at a time
+		assert_eq!(flash.into_inner().writes(), [32, 32, 32, 8, 8]);
+	}
+
+	#[test]
+	fn erases_set_bits_back() {
+		let mut flash = NvramNorFlash::<_, 16>::new(RamNvram::<64>::new());
+		flash.erase(16, 48).unwrap();
+		flash.write(16, &[0; 32]).unwrap();
+		flash.write(16, &[0xff; 32]).unwrap();
+
+		let mut data = [0xff; 32];
+		flash.read(16, &mut data).unwrap();
+		assert_eq!(data, [0; 32]);
+
+		flash.erase(16, 32).unwrap();
+		flash.read(16, &mut data).unwrap();
+		assert_eq!(data[..16], [0xff; 16]);
+		assert_eq!(data[16..], [0; 16]);
+
+		assert_eq!(
+			flash.erase(8, 16),
+			Err(NvramNorFlashError::NorFlash(NorFlashErrorKind::NotAligned))
+		);
+		assert_eq!(
+			flash.erase(48, 80),
+			Err(NvramNorFlashError::NorFlash(NorFlashErrorKind::OutOfBounds))
+		);
+	}
+
+	#[test]
+	fn memory_errors_keep_their_kind() {
+		let error = NvramNorFlashError::Nvram(StorageErrorKind::WriteProtected);
+		assert_eq!(
+			NorFlashError::kind(&error),
+			NorFlashErrorKind::WriteProtected
+		);
+		assert_eq!(StorageError::kind(&error), StorageErrorKind::WriteProtected);
+	}
+}